@@ -1,16 +1,15 @@
-use std::{
-    array,
-    fs::File,
-    io::{Result, Write},
-};
+use std::{io::Result, thread};
 
 extern crate rtracer;
 
+use rtracer::bvh::BvhNode;
+use rtracer::cli::{image_height, parse_render_args, RenderArgs, USAGE};
+use rtracer::image::PostProcess;
 use rtracer::rng::rand_f64;
-use rtracer::shape::Sphere;
-use rtracer::vec::{Color, Vec3};
-use rtracer::{camera::setup_camera, vec3};
-use rtracer::{get_ray_color, write_color};
+use rtracer::shape::{Collidable, Sphere};
+use rtracer::vec::Vec3;
+use rtracer::{camera::setup_camera_unchecked, vec3};
+use rtracer::{render_parallel_with_progress, RenderConfig};
 use rtracer::{
     material::{Material, WINDOW_GLASS_REFRACTION},
     rng,
@@ -25,6 +24,7 @@ fn rand_sphere() -> Sphere {
         material: Material::Metal {
             albedo: rtracer::vec3!(rand_f64(0.0, 1.0), rand_f64(0.0, 1.0), rand_f64(0.0, 1.0)),
             fuzzyness: rand_f64(0.0, 1.0),
+            f0: None,
         },
     };
     println!("{:?}", sphere);
@@ -65,7 +65,11 @@ fn random_world() -> Vec<Sphere> {
                     world.push(Sphere {
                         center,
                         radius: 0.2,
-                        material: Material::Metal { albedo, fuzzyness },
+                        material: Material::Metal {
+                            albedo,
+                            fuzzyness,
+                            f0: None,
+                        },
                     });
                 } else {
                     world.push(Sphere {
@@ -73,6 +77,8 @@ fn random_world() -> Vec<Sphere> {
                         radius: 0.2,
                         material: Material::Dialectric {
                             refraction_index: WINDOW_GLASS_REFRACTION,
+                            attenuation: vec3!(1.0, 1.0, 1.0),
+                            absorption: 0.0,
                         },
                     });
                 }
@@ -84,6 +90,8 @@ fn random_world() -> Vec<Sphere> {
         radius: 1.0,
         material: Material::Dialectric {
             refraction_index: WINDOW_GLASS_REFRACTION,
+            attenuation: vec3!(1.0, 1.0, 1.0),
+            absorption: 0.0,
         },
     });
     world.push(Sphere {
@@ -99,22 +107,33 @@ fn random_world() -> Vec<Sphere> {
         material: Material::Metal {
             albedo: vec3!(0.7, 0.6, 0.5),
             fuzzyness: 0.5,
+            f0: None,
         },
     });
 
-    return world;
+    world
 }
 
-fn write_ray_tracer_image(file_name: &str, image_width: usize) -> std::io::Result<()> {
+fn write_ray_tracer_image(args: &RenderArgs) -> std::io::Result<()> {
     // image specs
     let aspect_ratio = 3.0 / 2.0;
-    let image_height = ((image_width as f64) / aspect_ratio) as usize;
-    let samples_per_pixel = 200;
-    let max_depth = 20;
-
-    let normalization_factor = 1.0 / samples_per_pixel as f64;
+    let image_width = args.image_width;
+    let image_height = image_height(image_width, aspect_ratio).unwrap_or_else(|error| {
+        eprintln!("{}\n{}", error, USAGE);
+        std::process::exit(2)
+    });
+    let samples_per_pixel = args.samples_per_pixel;
+    let config = RenderConfig {
+        max_depth: 20,
+        per_pixel_seed: args.seed,
+        ..Default::default()
+    };
 
-    let world: Vec<Sphere> = random_world();
+    let spheres: Vec<Box<dyn Collidable>> = random_world()
+        .into_iter()
+        .map(|sphere| Box::new(sphere) as Box<dyn Collidable>)
+        .collect();
+    let world: Vec<Box<dyn Collidable>> = vec![Box::new(BvhNode::new(spheres))];
 
     let look_from = rtracer::vec3!(13.0, 2.0, 3.0);
     let look_at = rtracer::vec3!(0.0, 0.0, 0.0);
@@ -123,7 +142,7 @@ fn write_ray_tracer_image(file_name: &str, image_width: usize) -> std::io::Resul
     let distance_to_focus_plane = 10.0;
     let aperture = 0.1;
 
-    let camera = setup_camera(
+    let camera = setup_camera_unchecked(
         look_from,
         look_at,
         up,
@@ -134,31 +153,38 @@ fn write_ray_tracer_image(file_name: &str, image_width: usize) -> std::io::Resul
     );
 
     // render
-    let mut file = File::create(file_name)?;
-    file.write_fmt(format_args!("P3\n{} {}\n255\n", image_width, image_height))?;
-    // println!("P3\n{} {}\n255\n", image_width, image_height);
-
-    for i in (0..image_height).rev() {
-        for j in 0..image_width {
-            let mut pixel_color: Color = rtracer::vec3!(0.0, 0.0, 0.0);
-            // antialise by using samples_per_pixel random points close to the actual pixels
-            for _sample in 0..samples_per_pixel {
-                let u = ((j as f64) + rand_f64(0.0, 0.999)) / ((image_width - 1) as f64);
-                let v = ((i as f64) + rand_f64(0.0, 0.999)) / ((image_height - 1) as f64);
-                // send a ray towards the current pixel
-                // actually, we pick samples_per_pixel many random points close to the normalized pixel
-                let ray = camera.send_ray_towards(u, v);
-                // add the ray color to our pixel color
-                pixel_color = pixel_color + get_ray_color(ray, &world, max_depth);
-            }
-            write_color(&mut file, pixel_color, normalization_factor)?;
-        }
-        println!("row = {:?};", image_height-1-i);
-    }
+    let thread_count = thread::available_parallelism().map_or(1, |n| n.get());
+    let framebuffer = render_parallel_with_progress(
+        &world,
+        &camera,
+        image_width,
+        image_height,
+        samples_per_pixel,
+        &config,
+        thread_count,
+        |rows_done, total_rows| eprint!("\rscanlines done: {}/{}", rows_done, total_rows),
+    );
+    eprintln!();
+
+    framebuffer.write_ppm(&args.output, &PostProcess::default())?;
 
     Result::Ok(())
 }
 
 fn main() -> std::io::Result<()> {
-    write_ray_tracer_image("random_spheres.ppm", 1200)
+    let defaults = RenderArgs {
+        image_width: 1200,
+        samples_per_pixel: 200,
+        output: "random_spheres.ppm".to_string(),
+        seed: None,
+    };
+    let args = parse_render_args(std::env::args().skip(1), defaults).unwrap_or_else(|error| {
+        eprintln!("{}\n{}", error, USAGE);
+        std::process::exit(2)
+    });
+    // the seed also determines the random world
+    if let Some(seed) = args.seed {
+        rng::seed_thread_rng(seed);
+    }
+    write_ray_tracer_image(&args)
 }
@@ -0,0 +1,124 @@
+use std::f64::consts::PI;
+
+use crate::image::{Framebuffer, DEFAULT_GAMMA};
+use crate::ray::Ray;
+use crate::vec::{Color, Vec3};
+use crate::vec3;
+
+// color of the light arriving from rays which miss every body of the world.
+// Sync so that a background can be shared between render threads
+pub trait Background: Sync {
+    fn sample(&self, ray: &Ray) -> Color;
+}
+
+// the same color in every direction
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SolidBackground(pub Color);
+
+impl Background for SolidBackground {
+    fn sample(&self, _ray: &Ray) -> Color {
+        self.0
+    }
+}
+
+// linear blend from bottom (looking straight down) to top (looking straight up)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GradientBackground {
+    pub bottom: Color,
+    pub top: Color,
+}
+
+impl Background for GradientBackground {
+    fn sample(&self, ray: &Ray) -> Color {
+        let unit_direction: Vec3 = ray.direction.to_unit_vec_unchecked();
+        let t: f64 = (0.5 * (unit_direction.y + 1.0)).clamp(0.0, 1.0);
+        self.bottom.lerp(&self.top, t)
+    }
+}
+
+// equirectangular environment map: the columns span the full circle of azimuths
+// starting at +z (behind a viewer looking along -z), the rows run from straight
+// up to straight down
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageBackground {
+    pub image: Framebuffer,
+}
+
+impl ImageBackground {
+    // png encoded for DEFAULT_GAMMA, e.g. written by Framebuffer::write_png
+    pub fn from_png(path: &str) -> std::io::Result<ImageBackground> {
+        Ok(ImageBackground {
+            image: Framebuffer::read_png(path, DEFAULT_GAMMA)?,
+        })
+    }
+}
+
+impl Background for ImageBackground {
+    fn sample(&self, ray: &Ray) -> Color {
+        let unit_direction: Vec3 = ray.direction.to_unit_vec_unchecked();
+        // looking along -z is the center of the image
+        let u = 0.5 + unit_direction.x.atan2(-unit_direction.z) / (2.0 * PI);
+        let v = unit_direction.y.clamp(-1.0, 1.0).acos() / PI;
+        // the columns at u = 0 and u = 1 meet behind the viewer
+        self.image.sample_bilinear_wrapped(u, v)
+    }
+}
+
+// white to light blue sky, the default background
+pub const SKY: GradientBackground = GradientBackground {
+    bottom: vec3!(1.0, 1.0, 1.0),
+    top: vec3!(0.5, 0.7, 1.0),
+};
+
+#[test]
+fn test_sky_blends_bottom_to_top() {
+    let ray_towards = |direction: Vec3| Ray {
+        origin: vec3!(),
+        direction,
+    };
+    assert_eq!(SKY.sample(&ray_towards(vec3!(0.0, -2.0, 0.0))), SKY.bottom);
+    assert_eq!(SKY.sample(&ray_towards(vec3!(0.0, 3.0, 0.0))), SKY.top);
+    assert_eq!(
+        SKY.sample(&ray_towards(vec3!(1.0, 0.0, 0.0))),
+        vec3!(0.75, 0.85, 1.0)
+    );
+}
+
+#[test]
+fn test_image_background_looks_up_pixel_in_direction() {
+    let mut image = Framebuffer::new(4, 2);
+    image.set_pixel(1, 0, vec3!(1.0, 0.0, 0.0));
+    image.set_pixel(2, 1, vec3!(0.0, 1.0, 0.0));
+    let path = std::env::temp_dir().join("rtracer_test_image_background.png");
+    let path = path.to_str().unwrap();
+    image
+        .write_png(path, &crate::image::PostProcess::default())
+        .unwrap();
+    let background = ImageBackground::from_png(path).unwrap();
+    std::fs::remove_file(path).unwrap();
+    assert_eq!(background.image, image);
+
+    // spherical coordinates of the pixel center (u, v)
+    let ray_towards = |u: f64, v: f64| {
+        let (azimuth, polar) = ((u - 0.5) * 2.0 * PI, v * PI);
+        Ray {
+            origin: vec3!(),
+            direction: vec3!(
+                polar.sin() * azimuth.sin(),
+                polar.cos(),
+                -polar.sin() * azimuth.cos()
+            ),
+        }
+    };
+    let red = background.sample(&ray_towards(1.5 / 4.0, 0.5 / 2.0));
+    assert!((red - vec3!(1.0, 0.0, 0.0)).norm() < 1e-9, "{:?}", red);
+    let green = background.sample(&ray_towards(2.5 / 4.0, 1.5 / 2.0));
+    assert!((green - vec3!(0.0, 1.0, 0.0)).norm() < 1e-9, "{:?}", green);
+    // looking straight ahead is the middle of the image
+    let ahead = background.sample(&ray_towards(0.5, 0.5));
+    assert!(
+        (ahead - vec3!(0.25, 0.25, 0.0)).norm() < 1e-9,
+        "{:?}",
+        ahead
+    );
+}
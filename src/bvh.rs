@@ -0,0 +1,221 @@
+use crate::ray::Ray;
+use crate::shape::{Aabb, Collidable, Collision};
+
+// bounding volume hierarchy: a binary tree of bodies in which every node
+// stores the box enclosing its children, so a ray only needs to be tested
+// against the bodies whose enclosing boxes it actually passes through
+pub struct BvhNode {
+    left: Box<dyn Collidable>,
+    right: Option<Box<dyn Collidable>>,
+    bounding_box: Aabb,
+}
+
+fn box_of(object: &dyn Collidable) -> Aabb {
+    object
+        .bounding_box()
+        .expect("BvhNode requires bounded bodies, keep e.g. planes outside of the hierarchy")
+}
+
+fn centroid(bounding_box: &Aabb, axis: usize) -> f64 {
+    0.5 * (bounding_box.min[axis] + bounding_box.max[axis])
+}
+
+fn longest_axis(bounding_box: &Aabb) -> usize {
+    let extent = bounding_box.max - bounding_box.min;
+    if extent.x >= extent.y && extent.x >= extent.z {
+        0
+    } else if extent.y >= extent.z {
+        1
+    } else {
+        2
+    }
+}
+
+impl BvhNode {
+    // recursively split the bodies in half along the longest axis of their enclosing box
+    pub fn new(mut objects: Vec<Box<dyn Collidable>>) -> BvhNode {
+        assert!(!objects.is_empty(), "BvhNode requires at least one body");
+        let bounding_box = objects
+            .iter()
+            .map(|object| box_of(object.as_ref()))
+            .reduce(|a, b| a.surrounding(&b))
+            .unwrap();
+
+        if objects.len() == 1 {
+            return BvhNode {
+                left: objects.pop().unwrap(),
+                right: None,
+                bounding_box,
+            };
+        }
+
+        let axis = longest_axis(&bounding_box);
+        objects.sort_by(|a, b| {
+            centroid(&box_of(a.as_ref()), axis).total_cmp(&centroid(&box_of(b.as_ref()), axis))
+        });
+
+        let right_objects = objects.split_off(objects.len() / 2);
+        let (left, right): (Box<dyn Collidable>, Box<dyn Collidable>) =
+            if objects.len() == 1 && right_objects.len() == 1 {
+                (
+                    objects.pop().unwrap(),
+                    right_objects.into_iter().next().unwrap(),
+                )
+            } else {
+                (
+                    Box::new(BvhNode::new(objects)),
+                    Box::new(BvhNode::new(right_objects)),
+                )
+            };
+
+        BvhNode {
+            left,
+            right: Some(right),
+            bounding_box,
+        }
+    }
+}
+
+impl Collidable for BvhNode {
+    fn collide(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Collision<'_>> {
+        self.collide_counted(ray, t_min, t_max, &mut 0)
+    }
+
+    // the box tests of the nodes are not counted, only those of the bodies
+    fn collide_counted(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        tests: &mut usize,
+    ) -> Option<Collision<'_>> {
+        if !self.bounding_box.hit(ray, t_min, t_max) {
+            return None;
+        }
+        let left_collision = self.left.collide_counted(ray, t_min, t_max, tests);
+        // anything behind the left collision is hidden anyway
+        let closest = left_collision
+            .as_ref()
+            .map_or(t_max, |collision| collision.t);
+        let right_collision = self
+            .right
+            .as_ref()
+            .and_then(|right| right.collide_counted(ray, t_min, closest, tests));
+        right_collision.or(left_collision)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bounding_box)
+    }
+}
+
+#[cfg(test)]
+use crate::material::Material;
+#[cfg(test)]
+use crate::rng::Rng;
+#[cfg(test)]
+use crate::shape::Sphere;
+#[cfg(test)]
+use crate::vec::Vec3;
+#[cfg(test)]
+use crate::vec3;
+
+#[cfg(test)]
+fn random_spheres(rng: &mut Rng, count: usize) -> Vec<Box<dyn Collidable>> {
+    (0..count)
+        .map(|_| {
+            Box::new(Sphere {
+                center: rng.vec(-10.0, 10.0),
+                radius: rng.next_f64(0.1, 1.5),
+                material: Material::Lambertian {
+                    albedo: rng.vec(0.0, 1.0),
+                },
+            }) as Box<dyn Collidable>
+        })
+        .collect()
+}
+
+#[test]
+fn test_bvh_matches_brute_force_closest_collision() {
+    let mut rng = Rng::new(3);
+    let brute_force_world = random_spheres(&mut rng, 200);
+    let mut rng = Rng::new(3);
+    let bvh = BvhNode::new(random_spheres(&mut rng, 200));
+
+    let mut hit_count = 0;
+    for _ in 0..2000 {
+        let ray = Ray {
+            origin: rng.vec(-15.0, 15.0),
+            direction: rng.unit_vec(),
+        };
+        let expected = crate::get_closest_collision(&ray, &brute_force_world, 0.001);
+        let actual = bvh.collide(&ray, 0.001, f64::MAX);
+        hit_count += expected.is_some() as usize;
+        assert_eq!(actual, expected);
+    }
+    // make sure the comparison is not dominated by misses
+    assert!(hit_count > 200, "only {} rays hit the scene", hit_count);
+}
+
+#[test]
+fn test_bvh_counts_the_bodies_it_tests() {
+    let mut rng = Rng::new(5);
+    let bvh = BvhNode::new(random_spheres(&mut rng, 200));
+
+    // a ray missing the enclosing box tests no body at all
+    let away = Ray {
+        origin: vec3!(0.0, 20.0, 0.0),
+        direction: vec3!(0.0, 1.0, 0.0),
+    };
+    let mut tests = 0;
+    assert!(bvh
+        .collide_counted(&away, 0.001, f64::MAX, &mut tests)
+        .is_none());
+    assert_eq!(tests, 0);
+
+    let mut total_tests = 0;
+    for _ in 0..100 {
+        let ray = Ray {
+            origin: rng.vec(-15.0, 15.0),
+            direction: rng.unit_vec(),
+        };
+        let mut tests = 0;
+        let collision = bvh.collide_counted(&ray, 0.001, f64::MAX, &mut tests);
+        assert_eq!(collision, bvh.collide(&ray, 0.001, f64::MAX));
+        assert!(tests <= 200);
+        total_tests += tests;
+    }
+    // more than one test per ray, but far less than brute force
+    assert!(
+        total_tests > 100 && total_tests < 100 * 200 / 4,
+        "{}",
+        total_tests
+    );
+}
+
+#[test]
+fn test_bvh_bounding_box_encloses_all_bodies() {
+    let world: Vec<Box<dyn Collidable>> = vec![
+        Box::new(Sphere {
+            center: vec3!(-2.0, 0.0, 0.0),
+            radius: 1.0,
+            material: Material::Lambertian {
+                albedo: vec3!(0.5, 0.5, 0.5),
+            },
+        }),
+        Box::new(Sphere {
+            center: vec3!(3.0, 1.0, -1.0),
+            radius: 0.5,
+            material: Material::Lambertian {
+                albedo: vec3!(0.5, 0.5, 0.5),
+            },
+        }),
+    ];
+    let bvh = BvhNode::new(world);
+
+    let expected = Aabb {
+        min: vec3!(-3.0, -1.0, -1.5),
+        max: vec3!(3.5, 1.5, 1.0),
+    };
+    assert_eq!(bvh.bounding_box(), Some(expected));
+}
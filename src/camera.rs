@@ -1,9 +1,18 @@
+use std::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
 use crate::ray::Ray;
-use crate::rng::rand_f64;
+use crate::rng::rand_in_unit_disk;
+#[cfg(test)]
+use crate::rng::{rand_f64, seed_thread_rng};
+use crate::shape::Aabb;
 use crate::vec::Vec3;
 use crate::vec3;
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Camera {
     pub origin: Vec3,
     pub lower_left_corner: Vec3,
@@ -14,7 +23,76 @@ pub struct Camera {
     pub u: Vec3,
     pub v: Vec3,
     pub w: Vec3,
-    pub lens_radius: f64,
+    pub projection: Projection,
+}
+
+// how the rays of a camera pass its viewport
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Projection {
+    // rays start on the lens of the given radius around the origin and meet on the
+    // focus plane, which holds the viewport. A radius of 0 is a pinhole camera
+    Perspective { lens_radius: f64 },
+    // parallel projection: all rays share the direction -w and start on the viewport,
+    // which is centered around the origin and spanned by horizontal and vertical
+    Orthographic,
+}
+
+// invalid parameters of setup_camera, which would yield a camera of NaNs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CameraError {
+    // look_from and look_at (almost) coincide
+    ZeroViewDirection,
+    // up is parallel to the view direction, so there is no unique horizontal axis
+    UpParallelToViewDirection,
+    NonPositiveAspectRatio(f64),
+    // the field of view must lie in (0, 180) degrees
+    FieldOfViewOutOfRange(f64),
+    // the viewport height of an orthographic camera must be positive
+    NonPositiveViewportHeight(f64),
+}
+
+impl fmt::Display for CameraError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CameraError::ZeroViewDirection => write!(f, "look_from and look_at must differ"),
+            CameraError::UpParallelToViewDirection => {
+                write!(f, "up must not be parallel to the viewing direction")
+            }
+            CameraError::NonPositiveAspectRatio(aspect_ratio) => {
+                write!(f, "aspect ratio {} must be positive", aspect_ratio)
+            }
+            CameraError::FieldOfViewOutOfRange(field_of_view) => write!(
+                f,
+                "field of view {} must lie in (0, 180) degrees",
+                field_of_view
+            ),
+            CameraError::NonPositiveViewportHeight(viewport_height) => {
+                write!(f, "viewport height {} must be positive", viewport_height)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CameraError {}
+
+// the checks of setup_camera which also apply to setup_orthographic_camera
+fn check_view(
+    look_from: Vec3,
+    look_at: Vec3,
+    up: Vec3,
+    aspect_ratio: f64,
+) -> Result<(), CameraError> {
+    let w = (look_from - look_at)
+        .to_unit_vec()
+        .ok_or(CameraError::ZeroViewDirection)?;
+    if w.cross(&up).to_unit_vec().is_none() {
+        return Err(CameraError::UpParallelToViewDirection);
+    }
+    if aspect_ratio.is_nan() || aspect_ratio <= 0.0 {
+        return Err(CameraError::NonPositiveAspectRatio(aspect_ratio));
+    }
+    Ok(())
 }
 
 pub fn setup_camera(
@@ -25,6 +103,32 @@ pub fn setup_camera(
     aspect_ratio: f64,
     aperture: f64, // control deblurring
     focus_distance: f64,
+) -> Result<Camera, CameraError> {
+    check_view(look_from, look_at, up, aspect_ratio)?;
+    if field_of_view.is_nan() || field_of_view <= 0.0 || field_of_view >= 180.0 {
+        return Err(CameraError::FieldOfViewOutOfRange(field_of_view));
+    }
+    Ok(setup_camera_unchecked(
+        look_from,
+        look_at,
+        up,
+        field_of_view,
+        aspect_ratio,
+        aperture,
+        focus_distance,
+    ))
+}
+
+// like setup_camera but without validation, panics for a zero view direction or up
+// parallel to it and yields a degenerate camera for invalid angles and aspect ratios
+pub fn setup_camera_unchecked(
+    look_from: Vec3,
+    look_at: Vec3,
+    up: Vec3, // ortorgonal to view direction look_from -> look_at
+    field_of_view: f64,
+    aspect_ratio: f64,
+    aperture: f64, // control deblurring
+    focus_distance: f64,
 ) -> Camera {
     let theta = field_of_view.to_radians();
     let h = (theta / 2.0).tan();
@@ -33,10 +137,14 @@ pub fn setup_camera(
     let viewport_width = aspect_ratio * viewport_height;
 
     // define the virtual hyperplanes horizontal, vertical etc.
-    let w = (look_from - look_at).to_unit_vec();
+    let w = (look_from - look_at)
+        .to_unit_vec()
+        .expect("look_from and look_at must differ");
     // get normal (perpendicular) vector w.r.t. to viewing direction
     // and "up". Flip, to let u point upwards aswell
-    let u = -(w.cross(&up)).to_unit_vec();
+    let u = -(w.cross(&up))
+        .to_unit_vec()
+        .expect("up must not be parallel to the viewing direction");
     // get normal (perpendicular) w.r.t. w and viewing direction
     let v = w.cross(&u);
 
@@ -57,16 +165,103 @@ pub fn setup_camera(
         u,
         v,
         w,
-        lens_radius,
+        projection: Projection::Perspective { lens_radius },
+    }
+}
+
+pub fn setup_orthographic_camera(
+    look_from: Vec3,
+    look_at: Vec3,
+    up: Vec3, // ortorgonal to view direction look_from -> look_at
+    viewport_height: f64,
+    aspect_ratio: f64,
+) -> Result<Camera, CameraError> {
+    check_view(look_from, look_at, up, aspect_ratio)?;
+    if viewport_height.is_nan() || viewport_height <= 0.0 {
+        return Err(CameraError::NonPositiveViewportHeight(viewport_height));
+    }
+    Ok(setup_orthographic_camera_unchecked(
+        look_from,
+        look_at,
+        up,
+        viewport_height,
+        aspect_ratio,
+    ))
+}
+
+// like setup_orthographic_camera but without validation, see setup_camera_unchecked
+pub fn setup_orthographic_camera_unchecked(
+    look_from: Vec3,
+    look_at: Vec3,
+    up: Vec3, // ortorgonal to view direction look_from -> look_at
+    viewport_height: f64,
+    aspect_ratio: f64,
+) -> Camera {
+    let viewport_width = aspect_ratio * viewport_height;
+
+    // same camera coordinates as for the perspective camera
+    let w = (look_from - look_at)
+        .to_unit_vec()
+        .expect("look_from and look_at must differ");
+    let u = -(w.cross(&up))
+        .to_unit_vec()
+        .expect("up must not be parallel to the viewing direction");
+    let v = w.cross(&u);
+
+    let origin = look_from;
+    let horizontal = viewport_width * u;
+    let vertical = viewport_height * v;
+    // the viewport is centered around the camera origin
+    let lower_left_corner = origin - horizontal * 0.5 - vertical * 0.5;
+
+    Camera {
+        origin,
+        lower_left_corner,
+        horizontal,
+        vertical,
+        u,
+        v,
+        w,
+        projection: Projection::Orthographic,
     }
 }
 
-// Return the ray starting from camera origin and moving through the
-// normalized image pixle coordinates (x, y)
 impl Camera {
+    // pinhole camera looking along -z at the center of bounds, just far enough away
+    // for the sphere enclosing bounds to fit into the field of view (in degrees).
+    // Fails like setup_camera for invalid angles and aspect ratios
+    pub fn frame_scene(
+        bounds: &Aabb,
+        aspect_ratio: f64,
+        field_of_view: f64,
+    ) -> Result<Camera, CameraError> {
+        let center = 0.5 * (bounds.min + bounds.max);
+        // a point-like scene still needs a camera away from it
+        let radius = (0.5 * (bounds.max - bounds.min).norm()).max(1e-9);
+        let half_vertical = 0.5 * field_of_view.to_radians();
+        let half_horizontal = (aspect_ratio * half_vertical.tan()).atan();
+        let distance = radius / half_vertical.min(half_horizontal).sin();
+        setup_camera(
+            center + vec3!(0.0, 0.0, distance),
+            center,
+            vec3!(0.0, 1.0, 0.0),
+            field_of_view,
+            aspect_ratio,
+            0.0,
+            distance,
+        )
+    }
+
+    // Return the ray starting from camera origin and moving through the
+    // normalized image pixle coordinates (x, y)
     pub fn send_ray_towards(&self, x: f64, y: f64) -> Ray {
-        let random_xy_unit_vec = vec3!(rand_f64(-1.0, 1.0), rand_f64(-1.0, 1.0), 0.0).to_unit_vec();
-        let random_direction = self.lens_radius * random_xy_unit_vec;
+        let lens_radius = match self.projection {
+            Projection::Perspective { lens_radius } if lens_radius != 0.0 => lens_radius,
+            // a pinhole camera has no lens to sample, keep the rng stream untouched
+            _ => return self.ray_through(x, y),
+        };
+        // sample the whole lens disk, not only its boundary circle
+        let random_direction = lens_radius * rand_in_unit_disk();
         let offset: Vec3 = self.u * random_direction.x + self.v * random_direction.y;
 
         Ray {
@@ -75,4 +270,177 @@ impl Camera {
                 - (self.origin + offset),
         }
     }
+
+    // like send_ray_towards but as if the lens was a pinhole, i.e. the ray starts at
+    // the camera origin and no random numbers are drawn, e.g. to pick the object
+    // under the mouse cursor
+    pub fn ray_through(&self, x: f64, y: f64) -> Ray {
+        let viewport_point = self.lower_left_corner + x * self.horizontal + y * self.vertical;
+        match self.projection {
+            Projection::Perspective { .. } => Ray {
+                origin: self.origin,
+                direction: viewport_point - self.origin,
+            },
+            Projection::Orthographic => Ray {
+                origin: viewport_point,
+                direction: -self.w,
+            },
+        }
+    }
+}
+
+#[test]
+fn test_orthographic_rays_are_parallel() {
+    let camera = setup_orthographic_camera(
+        vec3!(0.0, 0.0, 0.0),
+        vec3!(0.0, 0.0, -1.0),
+        vec3!(0.0, 1.0, 0.0),
+        2.0,
+        2.0,
+    )
+    .unwrap();
+    let ray_1 = camera.send_ray_towards(0.1, 0.2);
+    let ray_2 = camera.send_ray_towards(0.9, 0.5);
+
+    assert_eq!(ray_1.direction, vec3!(0.0, 0.0, -1.0));
+    assert_eq!(ray_1.direction, ray_2.direction);
+    assert_ne!(ray_1.origin, ray_2.origin);
+    // the viewport is 4 units wide and 2 units high
+    let center = camera.send_ray_towards(0.5, 0.5);
+    assert_eq!(center.origin, vec3!(0.0, 0.0, 0.0));
+    assert_eq!(ray_2.origin - ray_1.origin, vec3!(3.2, 0.6, 0.0));
+}
+
+#[test]
+fn test_projections_of_the_same_view() {
+    let look_from = vec3!(0.0, 1.0, 2.0);
+    let look_at = vec3!(0.0, 1.0, -1.0);
+    let up = vec3!(0.0, 1.0, 0.0);
+    let perspective = setup_camera_unchecked(look_from, look_at, up, 90.0, 2.0, 0.0, 1.0);
+    let orthographic = setup_orthographic_camera_unchecked(look_from, look_at, up, 2.0, 2.0);
+    assert_eq!(
+        perspective.projection,
+        Projection::Perspective { lens_radius: 0.0 }
+    );
+    assert_eq!(orthographic.projection, Projection::Orthographic);
+
+    let corners = [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)];
+    for camera in [perspective, orthographic] {
+        let center = camera.send_ray_towards(0.5, 0.5);
+        for (x, y) in corners {
+            let ray = camera.send_ray_towards(x, y);
+            let cos = ray.direction.to_unit_vec_unchecked().dot(&center.direction);
+            match camera.projection {
+                // the rays of a perspective camera diverge from its origin
+                Projection::Perspective { .. } => {
+                    assert_eq!(ray.origin, look_from);
+                    assert!(cos < 0.9 * center.direction.norm(), "{:?}", ray);
+                }
+                Projection::Orthographic => {
+                    assert_ne!(ray.origin, look_from);
+                    assert_eq!(ray.direction, center.direction);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_pinhole_camera_does_not_consume_rng() {
+    let camera = setup_camera_unchecked(
+        vec3!(1.0, 2.0, 3.0),
+        vec3!(0.0, 0.0, -1.0),
+        vec3!(0.0, 1.0, 0.0),
+        60.0,
+        1.5,
+        0.0,
+        4.0,
+    );
+    seed_thread_rng(11);
+    let expected_draw = rand_f64(0.0, 1.0);
+
+    seed_thread_rng(11);
+    let ray_1 = camera.send_ray_towards(0.3, 0.7);
+    let ray_2 = camera.send_ray_towards(0.3, 0.7);
+
+    assert_eq!(ray_1.origin, vec3!(1.0, 2.0, 3.0));
+    assert_eq!(ray_1.origin, ray_2.origin);
+    assert_eq!(ray_1.direction, ray_2.direction);
+    assert_eq!(rand_f64(0.0, 1.0), expected_draw);
+}
+
+#[test]
+fn test_setup_camera_validates_parameters() {
+    let look_from = vec3!(0.0, 0.0, 0.0);
+    let look_at = vec3!(0.0, 0.0, -1.0);
+    let up = vec3!(0.0, 1.0, 0.0);
+
+    let camera = setup_camera(look_from, look_at, up, 90.0, 2.0, 0.0, 1.0).unwrap();
+    let expected = setup_camera_unchecked(look_from, look_at, up, 90.0, 2.0, 0.0, 1.0);
+    assert_eq!(camera.lower_left_corner, expected.lower_left_corner);
+    assert!((camera.lower_left_corner - vec3!(-2.0, -1.0, -1.0)).norm() < 1e-12);
+
+    assert_eq!(
+        setup_camera(look_from, look_from, up, 90.0, 2.0, 0.0, 1.0).unwrap_err(),
+        CameraError::ZeroViewDirection
+    );
+    assert_eq!(
+        setup_camera(
+            look_from,
+            look_at,
+            vec3!(0.0, 0.0, 2.0),
+            90.0,
+            2.0,
+            0.0,
+            1.0
+        )
+        .unwrap_err(),
+        CameraError::UpParallelToViewDirection
+    );
+    assert_eq!(
+        setup_camera(look_from, look_at, up, 90.0, 0.0, 0.0, 1.0).unwrap_err(),
+        CameraError::NonPositiveAspectRatio(0.0)
+    );
+    assert_eq!(
+        setup_camera(look_from, look_at, up, 180.0, 2.0, 0.0, 1.0).unwrap_err(),
+        CameraError::FieldOfViewOutOfRange(180.0)
+    );
+    assert_eq!(
+        setup_camera(look_from, look_at, up, -10.0, 2.0, 0.0, 1.0).unwrap_err(),
+        CameraError::FieldOfViewOutOfRange(-10.0)
+    );
+}
+
+#[test]
+fn test_ray_through_ignores_the_lens() {
+    let look_from = vec3!(1.0, 2.0, 3.0);
+    let look_at = vec3!(0.0, 0.0, -1.0);
+    let camera = setup_camera_unchecked(
+        look_from,
+        look_at,
+        vec3!(0.0, 1.0, 0.0),
+        60.0,
+        1.5,
+        2.0,
+        4.0,
+    );
+    seed_thread_rng(3);
+    let expected_draw = rand_f64(0.0, 1.0);
+    seed_thread_rng(3);
+
+    let ray = camera.ray_through(0.5, 0.5);
+    assert_eq!(ray.origin, look_from);
+    // the center of the viewport lies on the focus plane in the view direction
+    let view_direction = (look_at - look_from).to_unit_vec_unchecked();
+    let direction = ray.direction.to_unit_vec_unchecked();
+    assert!(
+        (direction - view_direction).norm() < 1e-12,
+        "{:?}",
+        direction
+    );
+    assert!((ray.direction.norm() - 4.0).abs() < 1e-12);
+    assert_eq!(camera.ray_through(0.5, 0.5), ray);
+    assert_eq!(rand_f64(0.0, 1.0), expected_draw);
+    // the lens still blurs the rays of send_ray_towards
+    assert_ne!(camera.send_ray_towards(0.5, 0.5).origin, look_from);
 }
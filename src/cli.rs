@@ -0,0 +1,162 @@
+use std::fmt;
+
+pub const USAGE: &str =
+    "usage: [--width <pixels>] [--samples <per pixel>] [--output <path>] [--seed <u64>]";
+
+// settings of the render binaries which can be overridden on the command line
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderArgs {
+    pub image_width: usize,
+    pub samples_per_pixel: usize,
+    pub output: String,
+    // Some(seed) renders reproducibly, see RenderConfig::per_pixel_seed
+    pub seed: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArgsError {
+    UnknownArgument(String),
+    MissingValue(String),
+    InvalidNumber { flag: String, value: String },
+    TooSmall { flag: String, min: usize },
+}
+
+impl fmt::Display for ArgsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArgsError::UnknownArgument(argument) => write!(f, "unknown argument '{}'", argument),
+            ArgsError::MissingValue(flag) => write!(f, "{} needs a value", flag),
+            ArgsError::InvalidNumber { flag, value } => {
+                write!(f, "{} expects a number, got '{}'", flag, value)
+            }
+            ArgsError::TooSmall { flag, min } => write!(f, "{} must be at least {}", flag, min),
+        }
+    }
+}
+
+impl std::error::Error for ArgsError {}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, ArgsError> {
+    value.parse().map_err(|_| ArgsError::InvalidNumber {
+        flag: flag.to_string(),
+        value: value.to_string(),
+    })
+}
+
+fn parse_at_least(flag: &str, value: &str, min: usize) -> Result<usize, ArgsError> {
+    let number = parse_number(flag, value)?;
+    if number < min {
+        return Err(ArgsError::TooSmall {
+            flag: flag.to_string(),
+            min,
+        });
+    }
+    Ok(number)
+}
+
+// parse "--flag value" pairs (without the program name) on top of defaults,
+// e.g. parse_render_args(std::env::args().skip(1), defaults)
+pub fn parse_render_args<I: IntoIterator<Item = String>>(
+    args: I,
+    defaults: RenderArgs,
+) -> Result<RenderArgs, ArgsError> {
+    let mut render_args = defaults;
+    let mut args = args.into_iter();
+    while let Some(flag) = args.next() {
+        let value = match flag.as_str() {
+            "--width" | "--samples" | "--output" | "--seed" => args
+                .next()
+                .ok_or_else(|| ArgsError::MissingValue(flag.clone()))?,
+            _ => return Err(ArgsError::UnknownArgument(flag)),
+        };
+        match flag.as_str() {
+            // the pixel coordinates are normalized by width - 1
+            "--width" => render_args.image_width = parse_at_least(&flag, &value, 2)?,
+            "--samples" => render_args.samples_per_pixel = parse_at_least(&flag, &value, 1)?,
+            "--seed" => render_args.seed = Some(parse_number(&flag, &value)?),
+            _ => render_args.output = value,
+        }
+    }
+    Ok(render_args)
+}
+
+// height of an image of image_width pixels with the given aspect ratio (width / height).
+// Like the width it must be at least 2, otherwise --width is reported as too small
+pub fn image_height(image_width: usize, aspect_ratio: f64) -> Result<usize, ArgsError> {
+    let height_of = |width: usize| ((width as f64) / aspect_ratio) as usize;
+    let image_height = height_of(image_width);
+    if image_height < 2 {
+        let mut min = (2.0 * aspect_ratio).ceil() as usize;
+        while height_of(min) < 2 {
+            min += 1;
+        }
+        return Err(ArgsError::TooSmall {
+            flag: "--width".to_string(),
+            min,
+        });
+    }
+    Ok(image_height)
+}
+
+#[cfg(test)]
+fn test_defaults() -> RenderArgs {
+    RenderArgs {
+        image_width: 400,
+        samples_per_pixel: 50,
+        output: "foo.ppm".to_string(),
+        seed: None,
+    }
+}
+
+#[cfg(test)]
+fn to_args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+}
+
+#[test]
+fn test_parse_render_args() {
+    assert_eq!(
+        parse_render_args(vec![], test_defaults()),
+        Ok(test_defaults())
+    );
+
+    let args = to_args(&["--seed", "7", "--width", "64", "--output", "out.ppm"]);
+    let expected = RenderArgs {
+        image_width: 64,
+        output: "out.ppm".to_string(),
+        seed: Some(7),
+        ..test_defaults()
+    };
+    assert_eq!(parse_render_args(args, test_defaults()), Ok(expected));
+
+    let args = to_args(&["--samples", "lots"]);
+    let error = parse_render_args(args, test_defaults()).unwrap_err();
+    assert_eq!(error.to_string(), "--samples expects a number, got 'lots'");
+    let args = to_args(&["--width", "-3"]);
+    assert!(parse_render_args(args, test_defaults()).is_err());
+    let args = to_args(&["--width", "1"]);
+    let error = parse_render_args(args, test_defaults()).unwrap_err();
+    assert_eq!(error.to_string(), "--width must be at least 2");
+    let args = to_args(&["--samples", "0"]);
+    let expected = ArgsError::TooSmall {
+        flag: "--samples".to_string(),
+        min: 1,
+    };
+    assert_eq!(parse_render_args(args, test_defaults()), Err(expected));
+    let args = to_args(&["--seed"]);
+    let expected = ArgsError::MissingValue("--seed".to_string());
+    assert_eq!(parse_render_args(args, test_defaults()), Err(expected));
+    let args = to_args(&["400"]);
+    let expected = ArgsError::UnknownArgument("400".to_string());
+    assert_eq!(parse_render_args(args, test_defaults()), Err(expected));
+}
+
+#[test]
+fn test_image_height_is_at_least_two() {
+    assert_eq!(image_height(400, 16.0 / 9.0), Ok(225));
+    assert_eq!(image_height(4, 16.0 / 9.0), Ok(2));
+    let error = image_height(3, 16.0 / 9.0).unwrap_err();
+    assert_eq!(error.to_string(), "--width must be at least 4");
+    assert_eq!(image_height(3, 1.5), Ok(2));
+    assert!(image_height(2, 1.5).is_err());
+}
@@ -0,0 +1,693 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+};
+
+use crate::vec::{Color, Vec3};
+use crate::{vec3, write_batch_color, write_ppm_header};
+
+const COLOR_MAX: f64 = 255f64;
+
+// keeps the square root encoding the output used to be hardwired to. Displays
+// expect sRGB, which is closer to gamma 2.2
+pub const DEFAULT_GAMMA: f64 = 2.0;
+
+// encode a linear color component for a display with the given gamma,
+// gamma 1 keeps the linear value (e.g. to debug raw output)
+fn gamma_correct(value: f64, gamma: f64) -> f64 {
+    let value = value.max(0.0);
+    if gamma == 1.0 {
+        value
+    } else if gamma == 2.0 {
+        value.sqrt()
+    } else {
+        value.powf(1.0 / gamma)
+    }
+}
+
+// maps linear colors of arbitrary brightness (e.g. lit by emissive
+// materials) into the displayable range [0, 1]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ToneMap {
+    // cut off every channel at 1, blows out highlights
+    #[default]
+    Clamp,
+    // c / (1 + c) per channel, compresses highlights instead of cutting them off
+    Reinhard,
+}
+
+impl ToneMap {
+    pub fn apply(&self, color: Color) -> Color {
+        let map = |value: f64| match self {
+            ToneMap::Clamp => value.clamp(0.0, 1.0),
+            ToneMap::Reinhard => {
+                let value = value.max(0.0);
+                value / (1.0 + value)
+            }
+        };
+        vec3!(map(color.x), map(color.y), map(color.z))
+    }
+}
+
+// maps the linear colors of a render to 8-bit rgb in the stages
+// exposure multiply -> tone map -> gamma -> quantize
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PostProcess {
+    // scale of the linear colors, e.g. to average accumulated samples
+    pub exposure: f64,
+    pub tone_map: ToneMap,
+    pub gamma: f64,
+}
+
+impl Default for PostProcess {
+    fn default() -> Self {
+        PostProcess {
+            exposure: 1.0,
+            tone_map: ToneMap::Clamp,
+            gamma: DEFAULT_GAMMA,
+        }
+    }
+}
+
+impl PostProcess {
+    pub fn expose(&self, linear: Color) -> Color {
+        linear * self.exposure
+    }
+
+    // encode the (tone mapped) color for a display with the gamma of self
+    pub fn gamma_correct(&self, color: Color) -> Color {
+        vec3!(
+            gamma_correct(color.x, self.gamma),
+            gamma_correct(color.y, self.gamma),
+            gamma_correct(color.z, self.gamma)
+        )
+    }
+
+    pub fn apply(&self, linear: Color) -> [u8; 3] {
+        quantize(self.gamma_correct(self.tone_map.apply(self.expose(linear))))
+    }
+}
+
+// 8-bit rgb of a color in [0, 1], the channels are truncated and clamped to [0, 255]
+pub fn quantize(color: Color) -> [u8; 3] {
+    let color_x = (COLOR_MAX * color.x) as i32;
+    let color_y = (COLOR_MAX * color.y) as i32;
+    let color_z = (COLOR_MAX * color.z) as i32;
+    let r = color_x.clamp(0i32, 255i32);
+    let g = color_y.clamp(0i32, 255i32);
+    let b = color_z.clamp(0i32, 255i32);
+    [r as u8, g as u8, b as u8]
+}
+
+// convert a linear color to 8-bit rgb, see PostProcess
+pub fn color_to_rgb(
+    pixel_color: Color,
+    gamma_scale: f64,
+    tone_map: ToneMap,
+    gamma: f64,
+) -> [u8; 3] {
+    PostProcess {
+        exposure: gamma_scale,
+        tone_map,
+        gamma,
+    }
+    .apply(pixel_color)
+}
+
+impl Color {
+    // 8-bit rgb of a linear color in [0, 1] for a display with the given gamma
+    pub fn to_rgb8(&self, gamma: f64) -> [u8; 3] {
+        PostProcess {
+            gamma,
+            ..Default::default()
+        }
+        .apply(*self)
+    }
+}
+
+// binary ppm (P6) image, several times smaller than the ascii P3 format
+pub fn write_ppm_binary<W: Write>(
+    writer: &mut W,
+    framebuffer: &Framebuffer,
+    post_process: &PostProcess,
+) -> std::io::Result<()> {
+    writer.write_fmt(format_args!(
+        "P6\n{} {}\n255\n",
+        framebuffer.width, framebuffer.height
+    ))?;
+    let bytes: Vec<u8> = framebuffer
+        .pixels
+        .iter()
+        .flat_map(|pixel_color| post_process.apply(*pixel_color))
+        .collect();
+    writer.write_all(&bytes)
+}
+
+// radiance rgbe encoding: the 8 bit mantissas of all channels share the exponent
+// of the largest one, i.e. the channel c is decoded as c_byte * 2^(e_byte - 136).
+// Negative and NaN channels are clamped to 0, channels beyond the largest encodable
+// value 255 * 2^119 (e.g. infinite ones) to that value
+fn color_to_rgbe(color: Color) -> [u8; 4] {
+    let max_encodable = 255.0 * 2f64.powi(119);
+    let clamp = |channel: f64| {
+        if channel.is_nan() {
+            0.0
+        } else {
+            channel.clamp(0.0, max_encodable)
+        }
+    };
+    let color = vec3!(clamp(color.x), clamp(color.y), clamp(color.z));
+    let max_channel = color.x.max(color.y).max(color.z);
+    if max_channel < 1e-32 {
+        return [0, 0, 0, 0];
+    }
+    // max_channel = mantissa * 2^exponent with mantissa in [0.5, 1)
+    let exponent = max_channel.log2().floor() as i32 + 1;
+    let scale = 256.0 / 2f64.powi(exponent);
+    [
+        (color.x * scale).min(255.0) as u8,
+        (color.y * scale).min(255.0) as u8,
+        (color.z * scale).min(255.0) as u8,
+        (exponent + 128).clamp(0, 255) as u8,
+    ]
+}
+
+// linear colors of an image, stored row by row starting at the top row
+#[derive(Debug, Clone, PartialEq)]
+pub struct Framebuffer {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Color>,
+}
+
+impl Framebuffer {
+    // black image of the given size
+    pub fn new(width: usize, height: usize) -> Framebuffer {
+        Framebuffer {
+            width,
+            height,
+            pixels: vec![vec3!(); width * height],
+        }
+    }
+
+    // (x, y) = (column, row) with (0, 0) being the top left pixel
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: Color) {
+        assert!(x < self.width && y < self.height, "pixel out of bounds");
+        self.pixels[y * self.width + x] = color;
+    }
+
+    pub fn get_pixel(&self, x: usize, y: usize) -> Color {
+        assert!(x < self.width && y < self.height, "pixel out of bounds");
+        self.pixels[y * self.width + x]
+    }
+
+    fn to_rgb_bytes(&self, post_process: &PostProcess) -> Vec<u8> {
+        self.pixels
+            .iter()
+            .flat_map(|pixel_color| post_process.apply(*pixel_color))
+            .collect()
+    }
+
+    // ascii ppm (P3) image
+    pub fn write_ppm_to<W: Write>(
+        &self,
+        writer: &mut W,
+        post_process: &PostProcess,
+    ) -> std::io::Result<()> {
+        write_ppm_header(writer, self.width, self.height)?;
+        write_batch_color(writer, &self.pixels, post_process)
+    }
+
+    pub fn write_ppm(&self, path: &str, post_process: &PostProcess) -> std::io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        self.write_ppm_to(&mut file, post_process)?;
+        file.flush()
+    }
+
+    // 8-bit rgb png image
+    pub fn write_png(&self, path: &str, post_process: &PostProcess) -> std::io::Result<()> {
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = png::Encoder::new(file, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.to_rgb_bytes(post_process))?;
+        writer.finish()?;
+        Ok(())
+    }
+
+    // radiance hdr image of the linear colors, neither tone mapped nor gamma
+    // corrected. The scanlines are stored flat (without run length encoding)
+    pub fn write_hdr_to<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        writer.write_fmt(format_args!(
+            "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+            self.height, self.width
+        ))?;
+        let bytes: Vec<u8> = self
+            .pixels
+            .iter()
+            .flat_map(|pixel_color| color_to_rgbe(*pixel_color))
+            .collect();
+        writer.write_all(&bytes)
+    }
+
+    pub fn write_hdr(&self, path: &str) -> std::io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        self.write_hdr_to(&mut file)?;
+        file.flush()
+    }
+
+    // load an 8-bit png image whose colors were encoded for the given gamma,
+    // the framebuffer holds the linear colors
+    pub fn read_png(path: &str, gamma: f64) -> std::io::Result<Framebuffer> {
+        let mut decoder = png::Decoder::new(File::open(path)?);
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info()?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer)?;
+
+        let channels = info.color_type.samples();
+        let to_linear = |byte: u8| (byte as f64 / COLOR_MAX).powf(gamma);
+        let pixels = buffer[..info.buffer_size()]
+            .chunks_exact(channels)
+            .map(|pixel| match pixel.len() {
+                // gray (with alpha)
+                1 | 2 => vec3!(
+                    to_linear(pixel[0]),
+                    to_linear(pixel[0]),
+                    to_linear(pixel[0])
+                ),
+                // rgb (with alpha)
+                _ => vec3!(
+                    to_linear(pixel[0]),
+                    to_linear(pixel[1]),
+                    to_linear(pixel[2])
+                ),
+            })
+            .collect();
+        Ok(Framebuffer {
+            width: info.width as usize,
+            height: info.height as usize,
+            pixels,
+        })
+    }
+
+    // see sample_bilinear
+    pub fn sample_bilinear(&self, u: f64, v: f64) -> Color {
+        sample_bilinear(&self.pixels, self.width, self.height, u, v)
+    }
+
+    // see sample_bilinear_wrapped
+    pub fn sample_bilinear_wrapped(&self, u: f64, v: f64) -> Color {
+        sample_bilinear_wrapped(&self.pixels, self.width, self.height, u, v)
+    }
+
+    // box filter: every factor x factor block becomes the average pixel of the block.
+    // Blocks at the right and bottom border may be smaller if the size is not a
+    // multiple of factor, they are averaged over the pixels they contain
+    pub fn downscale(&self, factor: usize) -> Framebuffer {
+        assert!(factor > 0, "downscale factor must be positive");
+        let width = self.width.div_ceil(factor);
+        let height = self.height.div_ceil(factor);
+        let mut downscaled = Framebuffer::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let columns = x * factor..((x + 1) * factor).min(self.width);
+                let rows = y * factor..((y + 1) * factor).min(self.height);
+                let pixel_count = columns.len() * rows.len();
+                let mut sum = vec3!();
+                for row in rows {
+                    for column in columns.clone() {
+                        sum += self.get_pixel(column, row);
+                    }
+                }
+                downscaled.set_pixel(x, y, sum / pixel_count as f64);
+            }
+        }
+        downscaled
+    }
+}
+
+// running sum of progressively rendered frames, e.g. of one sample per pixel each, whose
+// average converges to the final image
+#[derive(Debug, Clone, PartialEq)]
+pub struct Accumulator {
+    pub sums: Vec<Color>,
+    // number of frames added so far
+    pub count: usize,
+}
+
+impl Accumulator {
+    pub fn new(pixel_count: usize) -> Accumulator {
+        Accumulator {
+            sums: vec![vec3!(); pixel_count],
+            count: 0,
+        }
+    }
+
+    pub fn add_frame(&mut self, frame: &[Color]) {
+        assert_eq!(frame.len(), self.sums.len(), "frame size changed");
+        for (sum, pixel) in self.sums.iter_mut().zip(frame) {
+            *sum += *pixel;
+        }
+        self.count += 1;
+    }
+
+    // mean of all frames added so far, black before the first one
+    pub fn average(&self) -> Vec<Color> {
+        let normalization_factor = 1.0 / self.count.max(1) as f64;
+        self.sums
+            .iter()
+            .map(|sum| *sum * normalization_factor)
+            .collect()
+    }
+}
+
+// bilinearly interpolated color at (u, v) in [0, 1] x [0, 1] of an image stored row
+// by row, (0, 0) being the top left corner. Beyond the outermost pixel centers the
+// border is repeated. Panics for an empty image
+pub fn sample_bilinear(pixels: &[Color], width: usize, height: usize, u: f64, v: f64) -> Color {
+    interpolate_bilinear(pixels, width, height, u, v, false)
+}
+
+// like sample_bilinear, but the left and right border are adjacent and u wraps around,
+// e.g. for an environment map spanning the full circle of azimuths
+pub fn sample_bilinear_wrapped(
+    pixels: &[Color],
+    width: usize,
+    height: usize,
+    u: f64,
+    v: f64,
+) -> Color {
+    interpolate_bilinear(pixels, width, height, u, v, true)
+}
+
+fn interpolate_bilinear(
+    pixels: &[Color],
+    width: usize,
+    height: usize,
+    u: f64,
+    v: f64,
+    wrap_horizontally: bool,
+) -> Color {
+    assert!(width > 0 && height > 0, "cannot sample an empty image");
+    let x = u * width as f64 - 0.5;
+    let x = if wrap_horizontally {
+        x.rem_euclid(width as f64)
+    } else {
+        x.clamp(0.0, (width - 1) as f64)
+    };
+    let y = (v * height as f64 - 0.5).clamp(0.0, (height - 1) as f64);
+    // rem_euclid may round up to width for a tiny negative x
+    let (x_0, y_0) = ((x.floor() as usize).min(width - 1), y.floor() as usize);
+    let x_1 = if wrap_horizontally {
+        (x_0 + 1) % width
+    } else {
+        (x_0 + 1).min(width - 1)
+    };
+    let y_1 = (y_0 + 1).min(height - 1);
+    let (s, t) = (x - x_0 as f64, y - y_0 as f64);
+
+    let pixel = |x: usize, y: usize| pixels[y * width + x];
+    let top = pixel(x_0, y_0).lerp(&pixel(x_1, y_0), s);
+    let bottom = pixel(x_0, y_1).lerp(&pixel(x_1, y_1), s);
+    top.lerp(&bottom, t)
+}
+
+#[test]
+fn test_color_to_rgb() {
+    assert_eq!(
+        color_to_rgb(vec3!(0.25, 1.0, 4.0), 1.0, ToneMap::Clamp, DEFAULT_GAMMA),
+        [127, 255, 255]
+    );
+    assert_eq!(
+        color_to_rgb(vec3!(1.0, 0.0, -1.0), 0.25, ToneMap::Clamp, DEFAULT_GAMMA),
+        [127, 0, 0]
+    );
+}
+
+#[test]
+fn test_color_to_rgb_linear_gamma() {
+    assert_eq!(
+        color_to_rgb(vec3!(0.5, 0.25, 1.0), 1.0, ToneMap::Clamp, 1.0),
+        [127, 63, 255]
+    );
+    // negative components are clamped before the gamma is applied
+    assert_eq!(
+        color_to_rgb(vec3!(-0.5, 0.0, 2.0), 1.0, ToneMap::Clamp, 1.0),
+        [0, 0, 255]
+    );
+}
+
+#[test]
+fn test_color_to_rgb_gamma_2_2() {
+    // 255 * 0.5^(1/2.2) = 186.08, 255 * 0.25^(1/2.2) = 135.79, 255 * 0.8^(1/2.2) = 230.40
+    assert_eq!(
+        color_to_rgb(vec3!(0.5, 0.25, 0.8), 1.0, ToneMap::Clamp, 2.2),
+        [186, 135, 230]
+    );
+    assert_eq!(
+        color_to_rgb(vec3!(-1.0, 0.0, 1.0), 1.0, ToneMap::Clamp, 2.2),
+        [0, 0, 255]
+    );
+}
+
+#[test]
+fn test_mid_gray_to_rgb8() {
+    let mid_gray = vec3!(0.5, 0.5, 0.5);
+    // 255 * sqrt(0.5) = 180.31
+    assert_eq!(mid_gray.to_rgb8(DEFAULT_GAMMA), [180, 180, 180]);
+    assert_eq!(mid_gray.to_rgb8(1.0), [127, 127, 127]);
+    assert_eq!(vec3!(2.0, -1.0, 0.5).to_rgb8(1.0), [255, 0, 127]);
+}
+
+#[test]
+fn test_reinhard_tone_map_keeps_highlights_below_one() {
+    let color = vec3!(4.0, 2.0, 1.0);
+
+    assert_eq!(ToneMap::Clamp.apply(color), vec3!(1.0, 1.0, 1.0));
+    let mapped = ToneMap::Reinhard.apply(color);
+    assert_eq!(mapped, vec3!(0.8, 2.0 / 3.0, 0.5));
+    assert!(mapped.x < 1.0 && mapped.y < 1.0 && mapped.z < 1.0);
+
+    // clamping blows out all channels while reinhard keeps them distinguishable
+    assert_eq!(
+        color_to_rgb(color, 1.0, ToneMap::Clamp, 1.0),
+        [255, 255, 255]
+    );
+    assert_eq!(
+        color_to_rgb(color, 1.0, ToneMap::Reinhard, 1.0),
+        [204, 170, 127]
+    );
+}
+
+#[test]
+fn test_post_process_stages() {
+    let post_process = PostProcess {
+        exposure: 4.0,
+        tone_map: ToneMap::Reinhard,
+        gamma: 2.0,
+    };
+    let color = vec3!(0.25, 1.0, -0.5);
+    let exposed = post_process.expose(color);
+    assert_eq!(exposed, vec3!(1.0, 4.0, -2.0));
+    let mapped = post_process.tone_map.apply(exposed);
+    assert_eq!(mapped, vec3!(0.5, 0.8, 0.0));
+    let encoded = post_process.gamma_correct(vec3!(0.25, 0.64, 1.0));
+    assert_eq!(encoded, vec3!(0.5, 0.8, 1.0));
+    // truncated and clamped to [0, 255]
+    assert_eq!(quantize(vec3!(0.5, 1.5, -0.1)), [127, 255, 0]);
+    // sqrt(0.5) = 0.707, sqrt(0.8) = 0.894
+    assert_eq!(post_process.apply(color), [180, 228, 0]);
+}
+
+#[test]
+fn test_default_post_process_clamps_and_applies_default_gamma() {
+    let post_process = PostProcess::default();
+    for color in [vec3!(0.25, 1.0, 4.0), vec3!(0.5, -1.0, 0.01)] {
+        assert_eq!(
+            post_process.apply(color),
+            color_to_rgb(color, 1.0, ToneMap::Clamp, DEFAULT_GAMMA)
+        );
+    }
+    assert_eq!(post_process.apply(vec3!(0.25, 1.0, 4.0)), [127, 255, 255]);
+}
+
+#[test]
+fn test_framebuffer_set_and_get_pixel() {
+    let mut framebuffer = Framebuffer::new(3, 2);
+    framebuffer.set_pixel(2, 1, vec3!(0.1, 0.2, 0.3));
+
+    assert_eq!(framebuffer.get_pixel(2, 1), vec3!(0.1, 0.2, 0.3));
+    assert_eq!(framebuffer.pixels[5], vec3!(0.1, 0.2, 0.3));
+    assert_eq!(framebuffer.get_pixel(0, 0), vec3!());
+}
+
+#[test]
+fn test_framebuffer_png_round_trip() {
+    let mut framebuffer = Framebuffer::new(2, 2);
+    framebuffer.set_pixel(0, 0, vec3!(1.0, 0.0, 0.0));
+    framebuffer.set_pixel(1, 0, vec3!(0.0, 1.0, 0.0));
+    framebuffer.set_pixel(0, 1, vec3!(0.0, 0.0, 1.0));
+    framebuffer.set_pixel(1, 1, vec3!(0.25, 0.25, 0.25));
+
+    let path = std::env::temp_dir().join("rtracer_test_framebuffer_png_round_trip.png");
+    let path = path.to_str().unwrap();
+    framebuffer
+        .write_png(path, &PostProcess::default())
+        .unwrap();
+
+    let decoder = png::Decoder::new(File::open(path).unwrap());
+    let mut reader = decoder.read_info().unwrap();
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).unwrap();
+    std::fs::remove_file(path).unwrap();
+
+    assert_eq!((info.width, info.height), (2, 2));
+    assert_eq!(info.color_type, png::ColorType::Rgb);
+    let expected: Vec<u8> = vec![255, 0, 0, 0, 255, 0, 0, 0, 255, 127, 127, 127];
+    assert_eq!(&buffer[..info.buffer_size()], &expected[..]);
+}
+
+#[test]
+fn test_framebuffer_sample_bilinear() {
+    let mut framebuffer = Framebuffer::new(2, 2);
+    framebuffer.set_pixel(0, 0, vec3!(1.0, 0.0, 0.0));
+    framebuffer.set_pixel(1, 0, vec3!(0.0, 1.0, 0.0));
+    framebuffer.set_pixel(0, 1, vec3!(0.0, 0.0, 1.0));
+    framebuffer.set_pixel(1, 1, vec3!(1.0, 1.0, 1.0));
+
+    // pixel centers and the corners return the pixel color
+    assert_eq!(
+        framebuffer.sample_bilinear(0.25, 0.25),
+        vec3!(1.0, 0.0, 0.0)
+    );
+    assert_eq!(framebuffer.sample_bilinear(1.0, 0.0), vec3!(0.0, 1.0, 0.0));
+    assert_eq!(framebuffer.sample_bilinear(0.0, 1.0), vec3!(0.0, 0.0, 1.0));
+    assert_eq!(framebuffer.sample_bilinear(0.5, 0.25), vec3!(0.5, 0.5, 0.0));
+    assert_eq!(framebuffer.sample_bilinear(0.5, 0.5), vec3!(0.5, 0.5, 0.5));
+}
+
+#[test]
+#[should_panic(expected = "cannot sample an empty image")]
+fn test_sample_bilinear_of_empty_image_panics() {
+    Framebuffer::new(0, 0).sample_bilinear(0.5, 0.5);
+}
+
+#[test]
+fn test_framebuffer_sample_bilinear_wrapped() {
+    let mut framebuffer = Framebuffer::new(4, 1);
+    framebuffer.set_pixel(0, 0, vec3!(1.0, 0.0, 0.0));
+    framebuffer.set_pixel(3, 0, vec3!(0.0, 0.0, 1.0));
+
+    // the left and right border blend into each other instead of being repeated
+    let seam = vec3!(0.5, 0.0, 0.5);
+    assert_eq!(framebuffer.sample_bilinear_wrapped(0.0, 0.5), seam);
+    assert_eq!(framebuffer.sample_bilinear_wrapped(1.0, 0.5), seam);
+    assert_eq!(framebuffer.sample_bilinear(0.0, 0.5), vec3!(1.0, 0.0, 0.0));
+    assert_eq!(
+        framebuffer.sample_bilinear_wrapped(0.125, 0.5),
+        vec3!(1.0, 0.0, 0.0)
+    );
+    assert_eq!(
+        framebuffer.sample_bilinear_wrapped(-0.875, 0.5),
+        vec3!(1.0, 0.0, 0.0)
+    );
+}
+
+#[test]
+fn test_framebuffer_downscale() {
+    let mut framebuffer = Framebuffer::new(4, 4);
+    for y in 0..4 {
+        for x in 0..4 {
+            framebuffer.set_pixel(x, y, vec3!((x + 4 * y) as f64, 1.0, 0.0));
+        }
+    }
+    let downscaled = framebuffer.downscale(2);
+    assert_eq!((downscaled.width, downscaled.height), (2, 2));
+    // e.g. the top left block holds 0, 1, 4 and 5
+    assert_eq!(downscaled.get_pixel(0, 0), vec3!(2.5, 1.0, 0.0));
+    assert_eq!(downscaled.get_pixel(1, 0), vec3!(4.5, 1.0, 0.0));
+    assert_eq!(downscaled.get_pixel(0, 1), vec3!(10.5, 1.0, 0.0));
+    assert_eq!(downscaled.get_pixel(1, 1), vec3!(12.5, 1.0, 0.0));
+    assert_eq!(framebuffer.downscale(1), framebuffer);
+
+    // the partial blocks of the last column and row (10, 11, 14, 15 | 8, 9, 12, 13)
+    let downscaled = framebuffer.downscale(3);
+    assert_eq!((downscaled.width, downscaled.height), (2, 2));
+    assert_eq!(downscaled.get_pixel(0, 0), vec3!(5.0, 1.0, 0.0));
+    assert_eq!(downscaled.get_pixel(1, 0), vec3!(7.0, 1.0, 0.0));
+    assert_eq!(downscaled.get_pixel(0, 1), vec3!(13.0, 1.0, 0.0));
+    assert_eq!(downscaled.get_pixel(1, 1), vec3!(15.0, 1.0, 0.0));
+}
+
+#[test]
+fn test_write_ppm_binary() {
+    let mut framebuffer = Framebuffer::new(2, 3);
+    framebuffer.set_pixel(0, 0, vec3!(1.0, 0.0, 0.0));
+    framebuffer.set_pixel(1, 0, vec3!(0.0, 1.0, 0.0));
+    framebuffer.set_pixel(0, 1, vec3!(0.0, 0.0, 1.0));
+    framebuffer.set_pixel(1, 1, vec3!(0.5, 0.5, 0.5));
+    framebuffer.set_pixel(0, 2, vec3!(2.0, 2.0, 2.0));
+    framebuffer.set_pixel(1, 2, vec3!(0.25, 0.0, 1.0));
+
+    let mut buffer: Vec<u8> = vec![];
+    let linear = PostProcess {
+        gamma: 1.0,
+        ..Default::default()
+    };
+    write_ppm_binary(&mut buffer, &framebuffer, &linear).unwrap();
+
+    let header = b"P6\n2 3\n255\n";
+    assert_eq!(&buffer[..header.len()], header);
+    assert_eq!(
+        &buffer[header.len()..],
+        [255, 0, 0, 0, 255, 0, 0, 0, 255, 127, 127, 127, 255, 255, 255, 63, 0, 255]
+    );
+}
+
+#[test]
+fn test_color_to_rgbe_of_non_finite_channels() {
+    assert_eq!(
+        color_to_rgbe(vec3!(f64::INFINITY, 1.0, f64::NAN)),
+        [255, 0, 0, 255]
+    );
+    assert_eq!(
+        color_to_rgbe(vec3!(f64::NAN, f64::NEG_INFINITY, 0.0)),
+        [0, 0, 0, 0]
+    );
+    assert_eq!(color_to_rgbe(vec3!(1e300, 0.0, 0.0)), [255, 0, 0, 255]);
+}
+
+#[test]
+fn test_write_hdr_keeps_values_above_one() {
+    let mut framebuffer = Framebuffer::new(2, 1);
+    framebuffer.set_pixel(0, 0, vec3!(8.0, 0.5, 0.25));
+    framebuffer.set_pixel(1, 0, vec3!(0.3, 0.0, 0.1));
+
+    let mut buffer: Vec<u8> = vec![];
+    framebuffer.write_hdr_to(&mut buffer).unwrap();
+
+    let header = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 1 +X 2\n";
+    assert_eq!(&buffer[..header.len()], header);
+    let rgbe = &buffer[header.len()..];
+    assert_eq!(rgbe.len(), 8);
+    assert_eq!(&rgbe[..4], [128, 8, 4, 132]);
+
+    for (pixel, expected) in rgbe.chunks_exact(4).zip(framebuffer.pixels.iter()) {
+        let scale = 2f64.powi(pixel[3] as i32 - 136);
+        let decoded = vec3!(
+            pixel[0] as f64 * scale,
+            pixel[1] as f64 * scale,
+            pixel[2] as f64 * scale
+        );
+        // the mantissas have 8 bits relative to the largest channel
+        let precision = expected.x.max(expected.y).max(expected.z) / 128.0;
+        assert!(
+            (decoded - *expected).norm() < precision,
+            "{:?} != {:?}",
+            decoded,
+            expected
+        );
+    }
+}
@@ -4,83 +4,2839 @@ pub mod camera;
 pub mod shape;
 pub mod material;
 pub mod ray;
+pub mod bvh;
+pub mod image;
+pub mod texture;
+#[cfg(feature = "serde")]
+pub mod scene;
+pub mod background;
+pub mod sampling;
+pub mod cli;
 
 use std::{
-    fs::File,
+    fmt::{self, Write as _},
     io::{Write},
+    ops::Range,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
 };
 
+use background::{Background, SKY};
+use camera::Camera;
+use image::{Framebuffer, PostProcess};
 use vec::{Color, Vec3};
 use ray::Ray;
-use shape::{Collidable, Collision};
-use material::{Material, Reflectable};
-
-const COLOR_MAX: f64 = 255f64;
-
-
-pub fn write_color(file: &mut File, pixel_color: Color, gamma_scale: f64) -> std::io::Result<()> {
-    let color_x = (COLOR_MAX * (pixel_color.x * gamma_scale).sqrt()) as i32;
-    let color_y = (COLOR_MAX * (pixel_color.y * gamma_scale).sqrt()) as i32;
-    let color_z = (COLOR_MAX * (pixel_color.z * gamma_scale).sqrt()) as i32;
-    let r = color_x.clamp(0i32, 255i32);
-    let g = color_y.clamp(0i32, 255i32);
-    let b = color_z.clamp(0i32, 255i32);
-    file.write_fmt(format_args!("{} {} {}\n", r, g, b))?;
-    return Ok(());
-}
-
-// pub fn write_batch_color(file: &mut File, pixel_colors: Vec<&Color>, gamma_scale: f64)-> std::io::Result<()> {
-//     let rgb_count = pixel_colors.len();
-//     let color_bytes: Vec<u8> = Vec::new();
-//     for pixel_color in pixel_colors.iter() {
-//         let color_x = (COLOR_MAX * (pixel_color.x * gamma_scale).sqrt()) as i32;
-//         let color_y = (COLOR_MAX * (pixel_color.y * gamma_scale).sqrt()) as i32;
-//         let color_z = (COLOR_MAX * (pixel_color.z * gamma_scale).sqrt()) as i32;
-//         let r: i32 = color_x.clamp(0i32, 255i32);
-//         let g: i32 = color_y.clamp(0i32, 255i32);
-//         let b: i32 = color_z.clamp(0i32, 255i32);
-//     }
-//     file.write_all(buf)
-//     file.write_fmt(format_args!("{} {} {}\n", r, g, b))?;
-//     return Ok(());
-// }
-
-
-fn get_closest_collision<T: Collidable>(ray: &Ray, hit_ables: &Vec<T>) -> Option<Collision> {
+use sampling::SampleStrategy;
+use shape::{Aabb, Collidable, Collision, Emitter};
+use material::{Material, Reflectable, VACUUM_REFRACTION};
+use rng::rand_f64;
+
+pub fn write_ppm_header<W: Write>(
+    writer: &mut W,
+    image_width: usize,
+    image_height: usize,
+) -> std::io::Result<()> {
+    writer.write_fmt(format_args!("P3\n{} {}\n255\n", image_width, image_height))
+}
+
+pub fn write_color<W: Write>(
+    writer: &mut W,
+    pixel_color: Color,
+    post_process: &PostProcess,
+) -> std::io::Result<()> {
+    let [r, g, b] = post_process.apply(pixel_color);
+    writer.write_fmt(format_args!("{} {} {}\n", r, g, b))?;
+    Ok(())
+}
+
+// same output as write_color for every pixel, but formatted into one buffer
+// which is written at once instead of one write per pixel
+pub fn write_batch_color<W: Write>(
+    writer: &mut W,
+    pixel_colors: &[Color],
+    post_process: &PostProcess,
+) -> std::io::Result<()> {
+    // at most 12 bytes "255 255 255\n" per pixel
+    let mut buffer = String::with_capacity(12 * pixel_colors.len());
+    for pixel_color in pixel_colors {
+        let [r, g, b] = post_process.apply(*pixel_color);
+        // writing to a String cannot fail
+        let _ = writeln!(buffer, "{} {} {}", r, g, b);
+    }
+    writer.write_all(buffer.as_bytes())
+}
+
+// nearest collision farther away than t_min, see RenderConfig::t_min
+pub(crate) fn get_closest_collision<'a>(
+    ray: &Ray,
+    hit_ables: &'a [Box<dyn Collidable>],
+    t_min: f64,
+) -> Option<Collision<'a>> {
+    get_closest_collision_counted(ray, hit_ables, t_min, None)
+}
+
+// get_closest_collision which counts its intersection tests in stats (if any)
+fn get_closest_collision_counted<'a>(
+    ray: &Ray,
+    hit_ables: &'a [Box<dyn Collidable>],
+    t_min: f64,
+    stats: Option<&RenderStatsCounter>,
+) -> Option<Collision<'a>> {
+    let mut tests = 0;
     let mut closest = f64::MAX;
     let mut closest_collision: Option<Collision> = None;
     for hit_able in hit_ables {
-        if let Some(collision) = hit_able.collide(ray, 0.001, closest) {
+        if let Some(collision) = hit_able.collide_counted(ray, t_min, closest, &mut tests) {
             closest = collision.t;
             closest_collision = Some(collision);
         }
     }
-    return closest_collision;
+    if let Some(stats) = stats {
+        stats.intersection_tests.fetch_add(tests, Ordering::Relaxed);
+    }
+    closest_collision
+}
+
+// smallest box enclosing every bounded shape of the world, unbounded shapes
+// such as planes are skipped. None if no shape is bounded
+pub fn scene_bounds(world: &[Box<dyn Collidable>]) -> Option<Aabb> {
+    world
+        .iter()
+        .filter_map(|shape| shape.bounding_box())
+        .reduce(|bounds, bounding_box| bounds.surrounding(&bounding_box))
+}
+
+// light of the paths which are still bouncing after max_depth scatters
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DepthLimitPolicy {
+    // no further light, darkens e.g. the inside of glass and mirrors
+    #[default]
+    ReturnBlack,
+    // as if the ray left the world, i.e. the background in its direction
+    ReturnBackground,
+    // as if the ray hit a white light, i.e. the attenuation accumulated so far
+    ReturnAttenuation,
+}
+
+// homogeneous distance fog: the light travelling the distance d along a ray keeps the
+// fraction exp(-density * d) and the rest is replaced by the fog color
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fog {
+    pub color: Color,
+    pub density: f64,
+}
+
+// counts of a render for performance tuning, see RenderStatsCounter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RenderStats {
+    // camera rays, i.e. paths traced
+    pub primary_rays: usize,
+    // rays scattered at a surface
+    pub scatter_rays: usize,
+    // ray-shape tests searching the nearest hit of a path, see
+    // Collidable::collide_counted. The shapes within a BvhNode count as far as the
+    // ray reaches them, the shadow rays of the light sampling are not included
+    pub intersection_tests: usize,
+    // largest number of surface hits of a single path
+    pub max_depth_reached: usize,
+}
+
+// accumulates the RenderStats of all rays traced with a RenderConfig referring to
+// it, atomics so that the render threads can share it
+#[derive(Debug, Default)]
+pub struct RenderStatsCounter {
+    primary_rays: AtomicUsize,
+    scatter_rays: AtomicUsize,
+    intersection_tests: AtomicUsize,
+    max_depth_reached: AtomicUsize,
+}
+
+impl RenderStatsCounter {
+    pub fn stats(&self) -> RenderStats {
+        RenderStats {
+            primary_rays: self.primary_rays.load(Ordering::Relaxed),
+            scatter_rays: self.scatter_rays.load(Ordering::Relaxed),
+            intersection_tests: self.intersection_tests.load(Ordering::Relaxed),
+            max_depth_reached: self.max_depth_reached.load(Ordering::Relaxed),
+        }
+    }
+}
+
+// t_min of the default RenderConfig, suited for scenes of roughly unit size
+pub const DEFAULT_T_MIN: f64 = 0.001;
+
+// settings of the light transport which are shared by all rays of a render
+#[derive(Clone, Copy)]
+pub struct RenderConfig<'a> {
+    // maximal number of bounces followed per ray
+    pub max_depth: usize,
+    // light of the paths cut off at max_depth, see DepthLimitPolicy
+    pub depth_limit_policy: DepthLimitPolicy,
+    // index of refraction of the medium the scene is embedded in,
+    // e.g. WATER_20_CELSIUS_REFRACTION for an underwater scene
+    pub ambient_ior: f64,
+    // light of the rays which miss the world, e.g. &SolidBackground(color) for a
+    // flat color as in product shots
+    pub background: &'a dyn Background,
+    // lights sampled explicitly at every diffuse hit
+    pub lights: &'a [PointLight<'a>],
+    // like lights, but each one only lights the points within its cone
+    pub spot_lights: &'a [SpotLight],
+    // emissive shapes of the world sampled at every diffuse hit in addition to the
+    // scattered ray (next event estimation), see sample_lights
+    pub area_lights: &'a [&'a dyn Emitter],
+    // placement of the samples within a pixel
+    pub sample_strategy: SampleStrategy,
+    // after this many bounces paths are terminated with a probability of one minus
+    // the largest channel of their attenuation (russian roulette), the surviving
+    // paths are boosted accordingly. None follows every path up to max_depth
+    pub roulette_min_bounces: Option<usize>,
+    // collisions closer than t_min to the ray origin are ignored. Rays starting on a
+    // surface would otherwise hit it again due to rounding errors (shadow acne), but
+    // a too large t_min skips nearby geometry, e.g. detaches contact shadows.
+    // Scale it with the scene
+    pub t_min: f64,
+    // only the pixels (x, y) with x0 <= x < x1 and y0 <= y < y1 of crop = (x0, y0, x1, y1)
+    // are rendered, (0, 0) being the top left pixel. The rays are still those of the
+    // full image, all other pixels stay black
+    pub crop: Option<(usize, usize, usize, usize)>,
+    // Some(global_seed) reseeds the rng before every pixel (x, y) with a hash of
+    // (x, y, global_seed), hence the noise of a pixel neither depends on the other
+    // pixels rendered nor on their order or the number of threads, see render_rayon.
+    // None continues the rng stream of the rendering thread, except for render_rayon
+    // which always seeds per pixel and takes None as 0
+    pub per_pixel_seed: Option<u64>,
+    // fog in between the surfaces, rays missing the world keep the plain background
+    pub fog: Option<Fog>,
+    // Some(counter) counts the rays and intersection tests, see render_with_stats
+    pub stats: Option<&'a RenderStatsCounter>,
+    // Some(material) shades every hit with this material instead of the one of the
+    // shape, e.g. a neutral Lambertian for a clay render to judge the lighting.
+    // Emissive shapes keep emitting their own light
+    pub material_override: Option<&'a Material>,
+    // constant light added at the first surface hit of every path, a cheap fake of
+    // the indirect light which brightens cavities the bounces do not escape.
+    // Added once per primary path only, otherwise it would add up with every bounce
+    pub ambient: Color,
+}
+
+impl RenderConfig<'_> {
+    // whether the pixel (x, y) lies within the crop window (if any)
+    pub fn renders_pixel(&self, x: usize, y: usize) -> bool {
+        match self.crop {
+            Some((x0, y0, x1, y1)) => (x0..x1).contains(&x) && (y0..y1).contains(&y),
+            None => true,
+        }
+    }
+}
+
+impl Default for RenderConfig<'_> {
+    fn default() -> Self {
+        RenderConfig {
+            max_depth: 50,
+            depth_limit_policy: DepthLimitPolicy::ReturnBlack,
+            ambient_ior: VACUUM_REFRACTION,
+            background: &SKY,
+            lights: &[],
+            spot_lights: &[],
+            area_lights: &[],
+            sample_strategy: SampleStrategy::Random,
+            roulette_min_bounces: None,
+            t_min: DEFAULT_T_MIN,
+            crop: None,
+            per_pixel_seed: None,
+            fog: None,
+            stats: None,
+            material_override: None,
+            ambient: vec3!(0.0, 0.0, 0.0),
+        }
+    }
+}
+
+// the trait objects are printed by address, they need not implement Debug
+impl fmt::Debug for RenderConfig<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let area_lights: Vec<_> = self
+            .area_lights
+            .iter()
+            .map(|light| *light as *const dyn Emitter)
+            .collect();
+        f.debug_struct("RenderConfig")
+            .field("max_depth", &self.max_depth)
+            .field("depth_limit_policy", &self.depth_limit_policy)
+            .field("ambient_ior", &self.ambient_ior)
+            .field("background", &(self.background as *const dyn Background))
+            .field("lights", &self.lights)
+            .field("spot_lights", &self.spot_lights)
+            .field("area_lights", &area_lights)
+            .field("sample_strategy", &self.sample_strategy)
+            .field("roulette_min_bounces", &self.roulette_min_bounces)
+            .field("t_min", &self.t_min)
+            .field("crop", &self.crop)
+            .field("per_pixel_seed", &self.per_pixel_seed)
+            .field("fog", &self.fog)
+            .field("stats", &self.stats)
+            .field("material_override", &self.material_override)
+            .field("ambient", &self.ambient)
+            .finish()
+    }
+}
+
+// infinitely small light source, its light falls off with the squared distance
+#[derive(Clone, Copy)]
+pub struct PointLight<'a> {
+    pub position: Vec3,
+    pub intensity: Color,
+    // Some(shape) softens the shadows: the light is dimmed by the fraction of
+    // shadow_samples random points of the shape visible from the hit instead of being
+    // hidden if position is, see soft_shadow_factor. Meant for a shape around
+    // position, e.g. a lamp, which is neither emissive in the world nor listed in
+    // RenderConfig::area_lights, otherwise its light would be counted twice
+    pub shape: Option<&'a dyn Emitter>,
+    // ignored without a shape
+    pub shadow_samples: usize,
 }
 
+// the shape is printed by address like the trait objects of RenderConfig
+impl fmt::Debug for PointLight<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PointLight")
+            .field("position", &self.position)
+            .field("intensity", &self.intensity)
+            .field(
+                "shape",
+                &self.shape.map(|shape| shape as *const dyn Emitter),
+            )
+            .field("shadow_samples", &self.shadow_samples)
+            .finish()
+    }
+}
+
+// light arriving directly from the point lights at a diffuse collision:
+// every light which is not hidden behind some body of the world (farther
+// away than t_min) contributes max(0, normal·L) * intensity / distance²
+pub fn direct_light(
+    collision: &Collision,
+    world: &[Box<dyn Collidable>],
+    lights: &[PointLight],
+    t_min: f64,
+) -> Color {
+    let mut color: Color = vec3!(0.0, 0.0, 0.0);
+    for light in lights {
+        let visible = match light.shape {
+            None => light_from(collision, world, &light.position, t_min),
+            // the shadow rays go to the shape instead of position
+            Some(shape) => {
+                light_from(collision, &[], &light.position, t_min)
+                    * soft_shadow_factor(&collision.pos, shape, world, light.shadow_samples, t_min)
+            }
+        };
+        color += visible * light.intensity;
+    }
+    color
+}
+
+// point light shining into a cone around the (not necessarily unit) direction: fully
+// up to inner_angle_deg off the axis, fading out smoothly until outer_angle_deg.
+// With outer_angle_deg <= inner_angle_deg the cone has a hard edge at inner_angle_deg
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpotLight {
+    pub position: Vec3,
+    pub direction: Vec3,
+    pub intensity: Color,
+    pub inner_angle_deg: f64,
+    pub outer_angle_deg: f64,
+}
+
+impl SpotLight {
+    // fraction of the intensity sent towards point
+    pub fn falloff(&self, point: &Vec3) -> f64 {
+        let angle = (*point - self.position)
+            .angle_between(&self.direction)
+            .to_degrees();
+        if self.outer_angle_deg <= self.inner_angle_deg {
+            return if angle <= self.inner_angle_deg {
+                1.0
+            } else {
+                0.0
+            };
+        }
+        let t = ((angle - self.inner_angle_deg) / (self.outer_angle_deg - self.inner_angle_deg))
+            .clamp(0.0, 1.0);
+        // 1 - smoothstep
+        1.0 - t * t * (3.0 - 2.0 * t)
+    }
+}
+
+// direct_light of spot lights
+pub fn direct_spot_light(
+    collision: &Collision,
+    world: &[Box<dyn Collidable>],
+    lights: &[SpotLight],
+    t_min: f64,
+) -> Color {
+    let mut color: Color = vec3!(0.0, 0.0, 0.0);
+    for light in lights {
+        let falloff = light.falloff(&collision.pos);
+        if falloff > 0.0 {
+            color +=
+                light_from(collision, world, &light.position, t_min) * falloff * light.intensity;
+        }
+    }
+    color
+}
+
+// max(0, normal·L) / distance² of a light at position unless it is hidden behind
+// some body of the world
+fn light_from(
+    collision: &Collision,
+    world: &[Box<dyn Collidable>],
+    position: &Vec3,
+    t_min: f64,
+) -> f64 {
+    let to_light = *position - collision.pos;
+    let distance = to_light.norm();
+    let direction = to_light / distance;
+    let cos_theta = collision.normal.dot(&direction);
+    if cos_theta <= 0.0 {
+        return 0.0;
+    }
+    let shadow_ray = Ray {
+        origin: collision.pos,
+        direction,
+    };
+    let is_occluded = world
+        .iter()
+        .any(|body| body.collide(&shadow_ray, t_min, distance).is_some());
+    if is_occluded {
+        0.0
+    } else {
+        cos_theta / (distance * distance)
+    }
+}
+
+// density (w.r.t. solid angle) with which sample_lights picks the given direction
+fn light_pdf(lights: &[&dyn Emitter], origin: &Vec3, direction: &Vec3) -> f64 {
+    let pdf_sum: f64 = lights
+        .iter()
+        .map(|light| light.pdf_value(origin, direction))
+        .sum();
+    pdf_sum / lights.len() as f64
+}
+
+// density of the cosine weighted scatter direction of diffuse materials
+fn diffuse_pdf(normal: &Vec3, direction: &Vec3) -> f64 {
+    (normal.dot(direction) / direction.norm()).max(0.0) / std::f64::consts::PI
+}
+
+// multiple importance sampling weight of a sample drawn with density pdf
+// which could also have been drawn with other_pdf (power heuristic)
+fn mis_weight(pdf: f64, other_pdf: f64) -> f64 {
+    let pdf_squared = pdf * pdf;
+    pdf_squared / (pdf_squared + other_pdf * other_pdf)
+}
+
+// light arriving at a diffuse collision from a random point of a random light,
+// weighted against the chance that the scattered ray finds the same light.
+// The lights have to be part of the world, the result is scaled by the
+// albedo of the collision like direct_light
+pub fn sample_lights(
+    collision: &Collision,
+    world: &[Box<dyn Collidable>],
+    lights: &[&dyn Emitter],
+    t_min: f64,
+) -> Color {
+    let black = vec3!(0.0, 0.0, 0.0);
+    if lights.is_empty() {
+        return black;
+    }
+    let index = ((rand_f64(0.0, 1.0) * lights.len() as f64) as usize).min(lights.len() - 1);
+    let to_light = lights[index].random_point(&collision.pos) - collision.pos;
+    if collision.normal.dot(&to_light) <= 0.0 {
+        return black;
+    }
+    // the sampled point lies at t = 1, anything closer blocks it
+    let shadow_ray = Ray {
+        origin: collision.pos,
+        direction: to_light,
+    };
+    let light_collision = match get_closest_collision(&shadow_ray, world, t_min) {
+        Some(light_collision) if light_collision.t >= 1.0 - LIGHT_SAMPLE_EPSILON => light_collision,
+        _ => return black,
+    };
+
+    let pdf = light_pdf(lights, &collision.pos, &to_light);
+    if pdf <= 0.0 {
+        return black;
+    }
+    let scatter_pdf = diffuse_pdf(&collision.normal, &to_light);
+    // the lambertian brdf albedo / pi times cos(theta) is the albedo times scatter_pdf
+    let emitted = light_collision.material.emitted(&light_collision);
+    emitted * (scatter_pdf / pdf) * mis_weight(pdf, scatter_pdf)
+}
+
+// fraction of samples random points of the light which are visible from point, i.e.
+// 1 if point is fully lit, 0 in the umbra and in between in the penumbra of the soft
+// shadow. The light may be part of the world. Panics if samples is 0
+pub fn soft_shadow_factor(
+    point: &Vec3,
+    light: &dyn Emitter,
+    world: &[Box<dyn Collidable>],
+    samples: usize,
+    t_min: f64,
+) -> f64 {
+    assert!(samples > 0, "soft_shadow_factor needs at least one sample");
+    let visible_count = (0..samples)
+        .filter(|_| {
+            // the sampled point lies at t = 1, anything closer blocks it
+            let shadow_ray = Ray {
+                origin: *point,
+                direction: light.random_point(point) - *point,
+            };
+            let t_max = 1.0 - LIGHT_SAMPLE_EPSILON;
+            !world
+                .iter()
+                .any(|body| body.collide(&shadow_ray, t_min, t_max).is_some())
+        })
+        .count();
+    visible_count as f64 / samples as f64
+}
+
+// relative tolerance of the distance to sampled light points
+const LIGHT_SAMPLE_EPSILON: f64 = 1e-6;
+
+// dielectric bodies a path currently travels through, the innermost one last. The
+// bodies are assumed to be nested: a ray leaves the body it entered last first, e.g.
+// a glass inside a sphere of water
+#[derive(Default)]
+struct MediumStack<'a> {
+    media: Vec<&'a Material>,
+}
+
+impl<'a> MediumStack<'a> {
+    // medium on the outer side of the surface a ray hits from inside (leaving the
+    // innermost body) or outside (entering a body), None for the ambient medium
+    fn outside(&self, ray_is_inside: bool) -> Option<&'a Material> {
+        let depth = if ray_is_inside { 1 } else { 0 };
+        self.media.iter().rev().nth(depth).copied()
+    }
+
+    fn enter(&mut self, body: &'a Material) {
+        self.media.push(body);
+    }
+
+    fn leave(&mut self) {
+        self.media.pop();
+    }
+}
 
 // the ray emits "photons" i.e. light through the space
 // if it collides with some object it should change the color
-// depending on the hit angle + material of the collision color
-pub fn get_ray_color<T: Collidable>(ray: Ray, world: &Vec<T>, max_depth: usize) -> Color {
+// depending on the hit angle + material of the collision color.
+// The bounces are followed iteratively: attenuation is the product of all
+// scatter colors so far and weights the light gathered at the current bounce
+pub fn get_ray_color(ray: Ray, world: &[Box<dyn Collidable>], config: &RenderConfig) -> Color {
+    trace_path(ray, world, config).0
+}
+
+// get_ray_color which additionally returns the number of scatter calls
+fn trace_path(ray: Ray, world: &[Box<dyn Collidable>], config: &RenderConfig) -> (Color, usize) {
+    let mut color: Color = vec3!(0.0, 0.0, 0.0);
+    let mut attenuation: Color = vec3!(1.0, 1.0, 1.0);
+    let mut ray = ray;
+    let mut scatter_count = 0;
+    // density of the last scatter direction if the lights were sampled explicitly at
+    // its origin, the light found by the ray is then weighted against that sample
+    let mut light_sampled_scatter_pdf: Option<f64> = None;
+    let mut media = MediumStack::default();
+    if let Some(stats) = config.stats {
+        stats.primary_rays.fetch_add(1, Ordering::Relaxed);
+    }
+    for depth in 0..config.max_depth {
+        if config
+            .roulette_min_bounces
+            .is_some_and(|min_bounces| depth >= min_bounces)
+        {
+            // dividing by the survival probability keeps the estimate unbiased
+            let survival_probability = attenuation.x.max(attenuation.y).max(attenuation.z).min(1.0);
+            if rand_f64(0.0, 1.0) >= survival_probability {
+                return (color, scatter_count);
+            }
+            attenuation /= survival_probability;
+        }
+        let collision = match get_closest_collision_counted(&ray, world, config.t_min, config.stats)
+        {
+            Some(collision) => collision,
+            None => {
+                return (
+                    color + attenuation * config.background.sample(&ray),
+                    scatter_count,
+                )
+            }
+        };
+        if let Some(fog) = config.fog {
+            // the light of the surface is blended toward the fog color
+            let distance = collision.t * ray.direction.norm();
+            let transmittance = (-fog.density * distance).exp();
+            color += attenuation * (1.0 - transmittance) * fog.color;
+            attenuation = transmittance * attenuation;
+        }
+        if depth == 0 {
+            color += attenuation * config.ambient;
+        }
+        if let Some(stats) = config.stats {
+            stats
+                .max_depth_reached
+                .fetch_max(depth + 1, Ordering::Relaxed);
+        }
+        let emitted = collision.material.emitted(&collision);
+        let emission_weight = match light_sampled_scatter_pdf {
+            Some(scatter_pdf) => mis_weight(
+                scatter_pdf,
+                light_pdf(config.area_lights, &ray.origin, &ray.direction),
+            ),
+            None => 1.0,
+        };
+        color += attenuation * emitted * emission_weight;
+        let collision = match config.material_override {
+            Some(material) => Collision {
+                material,
+                ..collision
+            },
+            None => collision,
+        };
+        if let Some(albedo) = collision.material.diffuse_albedo(&collision) {
+            color +=
+                attenuation * albedo * direct_light(&collision, world, config.lights, config.t_min);
+            color += attenuation
+                * albedo
+                * direct_spot_light(&collision, world, config.spot_lights, config.t_min);
+        }
+        // the scatter pdf of the light samples is only known for plain diffuse materials
+        let samples_lights = !config.area_lights.is_empty()
+            && matches!(
+                collision.material,
+                Material::Lambertian { .. } | Material::Textured { .. }
+            );
+        if samples_lights {
+            if let Some(albedo) = collision.material.diffuse_albedo(&collision) {
+                color += attenuation
+                    * albedo
+                    * sample_lights(&collision, world, config.area_lights, config.t_min);
+            }
+        }
+        scatter_count += 1;
+        let source_material = media.outside(collision.ray_is_inside);
+        match collision
+            .material
+            .scatter(&ray, &collision, source_material, config.ambient_ior)
+        {
+            Some((scattered_ray, scattered_color)) => {
+                if let Some(stats) = config.stats {
+                    stats.scatter_rays.fetch_add(1, Ordering::Relaxed);
+                }
+                // a refracted ray enters or leaves the body
+                let is_refracted = matches!(collision.material, Material::Dialectric { .. })
+                    && scattered_ray.direction.dot(&collision.normal) < 0.0;
+                if is_refracted {
+                    if collision.ray_is_inside {
+                        media.leave();
+                    } else {
+                        media.enter(collision.material);
+                    }
+                }
+                attenuation = attenuation * scattered_color;
+                light_sampled_scatter_pdf = if samples_lights {
+                    Some(diffuse_pdf(&collision.normal, &scattered_ray.direction))
+                } else {
+                    None
+                };
+                ray = scattered_ray;
+            }
+            None => return (color, scatter_count),
+        }
+    }
+    // the bounce limit is reached
+    let remaining_light = match config.depth_limit_policy {
+        DepthLimitPolicy::ReturnBlack => vec3!(0.0, 0.0, 0.0),
+        DepthLimitPolicy::ReturnBackground => attenuation * config.background.sample(&ray),
+        DepthLimitPolicy::ReturnAttenuation => attenuation,
+    };
+    (color + remaining_light, scatter_count)
+}
+
+
+// render the given scanlines (0 being the top row of the image) and
+// return the pixel colors averaged over all samples
+fn render_scanlines(
+    world: &[Box<dyn Collidable>],
+    camera: &Camera,
+    image_width: usize,
+    image_height: usize,
+    scanlines: Range<usize>,
+    samples_per_pixel: usize,
+    config: &RenderConfig,
+) -> Vec<Color> {
+    let mut pixels = vec![vec3!(0.0, 0.0, 0.0); scanlines.len() * image_width];
+    render_scanlines_into(
+        world,
+        camera,
+        (image_width, image_height),
+        scanlines,
+        samples_per_pixel,
+        config,
+        &mut pixels,
+    );
+    pixels
+}
+
+// see render_scanlines, the pixels of the scanlines are written to out row by row
+fn render_scanlines_into(
+    world: &[Box<dyn Collidable>],
+    camera: &Camera,
+    (image_width, image_height): (usize, usize),
+    scanlines: Range<usize>,
+    samples_per_pixel: usize,
+    config: &RenderConfig,
+    out: &mut [Color],
+) {
+    let pixel_coordinates = scanlines.flat_map(|y| (0..image_width).map(move |x| (x, y)));
+    for ((x, scanline), pixel) in pixel_coordinates.zip(out.iter_mut()) {
+        if !config.renders_pixel(x, scanline) {
+            *pixel = vec3!(0.0, 0.0, 0.0);
+            continue;
+        }
+        if let Some(global_seed) = config.per_pixel_seed {
+            rng::seed_thread_rng(rng::pixel_seed(x, scanline, global_seed));
+        }
+        *pixel = render_pixel(
+            world,
+            camera,
+            (image_width, image_height),
+            (x, scanline),
+            samples_per_pixel,
+            config,
+        );
+    }
+}
+
+// color of the pixel (x, y), (0, 0) being the top left pixel, averaged over all samples
+fn render_pixel(
+    world: &[Box<dyn Collidable>],
+    camera: &Camera,
+    (image_width, image_height): (usize, usize),
+    (x, y): (usize, usize),
+    samples_per_pixel: usize,
+    config: &RenderConfig,
+) -> Color {
+    let normalization_factor = 1.0 / samples_per_pixel as f64;
+    let (j, i) = (x, image_height - 1 - y);
+    let mut pixel_color: Color = vec3!(0.0, 0.0, 0.0);
+    // antialise by using samples_per_pixel random points close to the actual pixels
+    for (offset_u, offset_v) in config.sample_strategy.offsets(samples_per_pixel) {
+        let u = ((j as f64) + offset_u) / ((image_width - 1) as f64);
+        let v = ((i as f64) + offset_v) / ((image_height - 1) as f64);
+        let ray = camera.send_ray_towards(u, v);
+        pixel_color += sanitize_sample(get_ray_color(ray, world, config));
+    }
+    pixel_color * normalization_factor
+}
+
+// number of samples with NaN or infinite components so far, see sanitize_sample
+static NON_FINITE_SAMPLES: AtomicUsize = AtomicUsize::new(0);
+
+// total number of samples of all renders whose NaN or infinite components were
+// replaced by 0, e.g. to debug degenerate geometry or materials
+pub fn non_finite_sample_count() -> usize {
+    NON_FINITE_SAMPLES.load(Ordering::Relaxed)
+}
+
+// a single NaN sample would poison the whole pixel average,
+// hence non finite components are dropped (and counted)
+fn sanitize_sample(color: Color) -> Color {
+    if color.x.is_finite() && color.y.is_finite() && color.z.is_finite() {
+        return color;
+    }
+    NON_FINITE_SAMPLES.fetch_add(1, Ordering::Relaxed);
+    let finite_or_zero = |value: f64| if value.is_finite() { value } else { 0.0 };
+    vec3!(
+        finite_or_zero(color.x),
+        finite_or_zero(color.y),
+        finite_or_zero(color.z)
+    )
+}
+
+// render the image on the current thread, the pixel colors are averaged over all samples.
+// The gamma is applied once the framebuffer is written, e.g. by Framebuffer::write_ppm.
+// progress(rows done, total rows) is called after every completed scanline
+pub fn render(
+    world: &[Box<dyn Collidable>],
+    camera: &Camera,
+    image_width: usize,
+    image_height: usize,
+    samples_per_pixel: usize,
+    config: &RenderConfig,
+    mut progress: impl FnMut(usize, usize),
+) -> Framebuffer {
+    let mut pixels = Vec::with_capacity(image_width * image_height);
+    for scanline in 0..image_height {
+        pixels.extend(render_scanlines(
+            world,
+            camera,
+            image_width,
+            image_height,
+            scanline..scanline + 1,
+            samples_per_pixel,
+            config,
+        ));
+        progress(scanline + 1, image_height);
+    }
+    Framebuffer {
+        width: image_width,
+        height: image_height,
+        pixels,
+    }
+}
+
+// like render but the pixels are written to the caller's buffer, e.g. to reuse one
+// buffer for all frames of an animation. out must hold image_width * image_height
+// pixels
+pub fn render_into(
+    world: &[Box<dyn Collidable>],
+    camera: &Camera,
+    image_width: usize,
+    image_height: usize,
+    samples_per_pixel: usize,
+    config: &RenderConfig,
+    out: &mut [Color],
+) {
+    assert_eq!(
+        out.len(),
+        image_width * image_height,
+        "the buffer must hold image_width * image_height pixels"
+    );
+    render_scanlines_into(
+        world,
+        camera,
+        (image_width, image_height),
+        0..image_height,
+        samples_per_pixel,
+        config,
+        out,
+    );
+}
+
+// like render but every preview_interval scanlines the image rendered so far is
+// written as PPM to a fresh writer of open_output, the rows still to be rendered
+// being black. Hence the output can be viewed during long renders, e.g. with
+// open_output = || Ok(BufWriter::new(File::create(path)?)). The last write holds
+// the complete image
+#[allow(clippy::too_many_arguments)]
+pub fn render_with_preview<W: Write>(
+    world: &[Box<dyn Collidable>],
+    camera: &Camera,
+    image_width: usize,
+    image_height: usize,
+    samples_per_pixel: usize,
+    config: &RenderConfig,
+    post_process: &PostProcess,
+    preview_interval: usize,
+    mut open_output: impl FnMut() -> std::io::Result<W>,
+) -> std::io::Result<Framebuffer> {
+    let mut framebuffer = Framebuffer::new(image_width, image_height);
+    let mut write_output = |framebuffer: &Framebuffer| -> std::io::Result<()> {
+        let mut writer = open_output()?;
+        framebuffer.write_ppm_to(&mut writer, post_process)?;
+        writer.flush()
+    };
+    let preview_interval = preview_interval.max(1);
+    for scanline in 0..image_height {
+        let row = scanline * image_width..(scanline + 1) * image_width;
+        render_scanlines_into(
+            world,
+            camera,
+            (image_width, image_height),
+            scanline..scanline + 1,
+            samples_per_pixel,
+            config,
+            &mut framebuffer.pixels[row],
+        );
+        let rows_done = scanline + 1;
+        if rows_done % preview_interval == 0 && rows_done < image_height {
+            write_output(&framebuffer)?;
+        }
+    }
+    write_output(&framebuffer)?;
+    Ok(framebuffer)
+}
+
+// like render but also returns the RenderStats of the render. The counts of
+// render_parallel and the other renderers are collected by setting RenderConfig::stats
+pub fn render_with_stats(
+    world: &[Box<dyn Collidable>],
+    camera: &Camera,
+    image_width: usize,
+    image_height: usize,
+    samples_per_pixel: usize,
+    config: &RenderConfig,
+) -> (Framebuffer, RenderStats) {
+    let counter = RenderStatsCounter::default();
+    let config = RenderConfig {
+        stats: Some(&counter),
+        ..*config
+    };
+    let framebuffer = render(
+        world,
+        camera,
+        image_width,
+        image_height,
+        samples_per_pixel,
+        &config,
+        |_, _| {},
+    );
+    (framebuffer, counter.stats())
+}
+
+// render at factor times the resolution in both directions and downscale the result
+// again, i.e. supersampling with a box filter
+pub fn render_supersampled(
+    world: &[Box<dyn Collidable>],
+    camera: &Camera,
+    image_width: usize,
+    image_height: usize,
+    samples_per_pixel: usize,
+    config: &RenderConfig,
+    factor: usize,
+) -> Framebuffer {
+    render(
+        world,
+        camera,
+        image_width * factor,
+        image_height * factor,
+        samples_per_pixel,
+        config,
+        |_, _| {},
+    )
+    .downscale(factor)
+}
+
+// split the image into thread_count horizontal tiles which are rendered concurrently.
+// Every tile seeds the rng of its thread with the tile index, hence the output only
+// depends on the number of threads. Returns the pixel colors averaged over all samples
+pub fn render_parallel(
+    world: &[Box<dyn Collidable>],
+    camera: &Camera,
+    image_width: usize,
+    image_height: usize,
+    samples_per_pixel: usize,
+    config: &RenderConfig,
+    thread_count: usize,
+) -> Framebuffer {
+    render_parallel_with_progress(
+        world,
+        camera,
+        image_width,
+        image_height,
+        samples_per_pixel,
+        config,
+        thread_count,
+        |_, _| {},
+    )
+}
+
+// render_parallel calling progress(rows done, total rows) whenever a tile and all
+// tiles above it are completed
+#[allow(clippy::too_many_arguments)]
+pub fn render_parallel_with_progress(
+    world: &[Box<dyn Collidable>],
+    camera: &Camera,
+    image_width: usize,
+    image_height: usize,
+    samples_per_pixel: usize,
+    config: &RenderConfig,
+    thread_count: usize,
+    mut progress: impl FnMut(usize, usize),
+) -> Framebuffer {
+    let thread_count = thread_count.clamp(1, image_height.max(1));
+    let tile_height = image_height.div_ceil(thread_count);
+
+    thread::scope(|scope| {
+        let tiles: Vec<_> = (0..thread_count)
+            .map(|tile| {
+                let scanlines = (tile * tile_height).min(image_height)
+                    ..((tile + 1) * tile_height).min(image_height);
+                scope.spawn(move || {
+                    rng::seed_thread_rng(tile as u64);
+                    render_scanlines(
+                        world,
+                        camera,
+                        image_width,
+                        image_height,
+                        scanlines,
+                        samples_per_pixel,
+                        config,
+                    )
+                })
+            })
+            .collect();
+
+        let mut pixels = Vec::with_capacity(image_width * image_height);
+        for tile in tiles {
+            let tile_pixels = tile.join().expect("render thread panicked");
+            if tile_pixels.is_empty() {
+                continue;
+            }
+            pixels.extend(tile_pixels);
+            progress(pixels.len() / image_width, image_height);
+        }
+        Framebuffer {
+            width: image_width,
+            height: image_height,
+            pixels,
+        }
+    })
+}
+
+// render every pixel as an independent task on the rayon thread pool. Every pixel is
+// seeded from RenderConfig::per_pixel_seed, 0 if unset, hence the output neither
+// depends on the number of threads nor on the order in which the pixels are rendered
+// and matches render with the same per_pixel_seed
+#[cfg(feature = "rayon")]
+pub fn render_rayon(
+    world: &[Box<dyn Collidable>],
+    camera: &Camera,
+    image_width: usize,
+    image_height: usize,
+    samples_per_pixel: usize,
+    config: &RenderConfig,
+) -> Framebuffer {
+    use rayon::prelude::*;
+
+    let mut pixels = vec![vec3!(0.0, 0.0, 0.0); image_width * image_height];
+    pixels
+        .par_iter_mut()
+        .enumerate()
+        .for_each(|(index, pixel)| {
+            let (x, y) = (index % image_width, index / image_width);
+            if !config.renders_pixel(x, y) {
+                return;
+            }
+            let global_seed = config.per_pixel_seed.unwrap_or(0);
+            rng::seed_thread_rng(rng::pixel_seed(x, y, global_seed));
+            *pixel = render_pixel(
+                world,
+                camera,
+                (image_width, image_height),
+                (x, y),
+                samples_per_pixel,
+                config,
+            );
+        });
+    Framebuffer {
+        width: image_width,
+        height: image_height,
+        pixels,
+    }
+}
+
+// auxiliary render passes, the buffers store one value per pixel row by row
+// starting at the top row just like the color framebuffer
+pub struct AovBuffers {
+    pub color: Framebuffer,
+    // distance collision.t of the first hit, infinite if the primary ray misses
+    pub depth: Vec<f64>,
+    // normal of the first hit mapped from [-1, 1] to [0, 1], black on a miss
+    pub normal: Framebuffer,
+}
+
+// render the color framebuffer like render and additionally store the depth and
+// normal of the first hit of the primary ray through the center of every pixel
+pub fn render_aov(
+    world: &[Box<dyn Collidable>],
+    camera: &Camera,
+    image_width: usize,
+    image_height: usize,
+    samples_per_pixel: usize,
+    config: &RenderConfig,
+) -> AovBuffers {
+    let color = render(
+        world,
+        camera,
+        image_width,
+        image_height,
+        samples_per_pixel,
+        config,
+        |_, _| {},
+    );
+
+    let mut depth = Vec::with_capacity(image_width * image_height);
+    let mut normals = Vec::with_capacity(image_width * image_height);
+    for scanline in 0..image_height {
+        let i = image_height - 1 - scanline;
+        for j in 0..image_width {
+            let u = ((j as f64) + 0.5) / ((image_width - 1) as f64);
+            let v = ((i as f64) + 0.5) / ((image_height - 1) as f64);
+            let ray = camera.send_ray_towards(u, v);
+            match get_closest_collision(&ray, world, config.t_min) {
+                Some(collision) => {
+                    depth.push(collision.t);
+                    normals.push(0.5 * (collision.normal + vec3!(1.0, 1.0, 1.0)));
+                }
+                None => {
+                    depth.push(f64::INFINITY);
+                    normals.push(vec3!(0.0, 0.0, 0.0));
+                }
+            }
+        }
+    }
+
+    AovBuffers {
+        color,
+        depth,
+        normal: Framebuffer {
+            width: image_width,
+            height: image_height,
+            pixels: normals,
+        },
+    }
+}
+
+// stopping rule of adaptive_render
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdaptiveSampling {
+    // number of samples taken between two variance estimates, at least 2
+    pub batch_size: usize,
+    // upper bound of the samples of a single pixel
+    pub max_samples: usize,
+    // a pixel is done once the estimated variance of its mean brightness falls below
+    pub variance_threshold: f64,
+}
+
+// render the image on the current thread with as many samples per pixel as needed:
+// pixels are sampled batch by batch until the variance of their mean brightness is
+// below the threshold. Returns the averaged colors together with the number of
+// samples of every pixel (row by row starting at the top row)
+pub fn adaptive_render(
+    world: &[Box<dyn Collidable>],
+    camera: &Camera,
+    image_width: usize,
+    image_height: usize,
+    config: &RenderConfig,
+    sampling: &AdaptiveSampling,
+) -> (Framebuffer, Vec<usize>) {
+    let batch_size = sampling.batch_size.max(2);
+    let mut pixels = Vec::with_capacity(image_width * image_height);
+    let mut sample_counts = Vec::with_capacity(image_width * image_height);
+    for scanline in 0..image_height {
+        let i = image_height - 1 - scanline;
+        for j in 0..image_width {
+            let mut pixel_color: Color = vec3!(0.0, 0.0, 0.0);
+            // sums of the brightness and the squared brightness of the samples
+            let mut brightness_sum = 0.0;
+            let mut brightness_squared_sum = 0.0;
+            let mut sample_count = 0;
+            for (offset_u, offset_v) in config.sample_strategy.offsets(sampling.max_samples) {
+                let u = ((j as f64) + offset_u) / ((image_width - 1) as f64);
+                let v = ((i as f64) + offset_v) / ((image_height - 1) as f64);
+                let ray = camera.send_ray_towards(u, v);
+                let color = sanitize_sample(get_ray_color(ray, world, config));
+                let brightness = (color.x + color.y + color.z) / 3.0;
+                pixel_color += color;
+                brightness_sum += brightness;
+                brightness_squared_sum += brightness * brightness;
+                sample_count += 1;
+
+                if sample_count % batch_size == 0 {
+                    let n = sample_count as f64;
+                    let sample_variance =
+                        (brightness_squared_sum - brightness_sum * brightness_sum / n) / (n - 1.0);
+                    if sample_variance / n < sampling.variance_threshold {
+                        break;
+                    }
+                }
+            }
+            pixels.push(pixel_color / sample_count.max(1) as f64);
+            sample_counts.push(sample_count);
+        }
+    }
+    (
+        Framebuffer {
+            width: image_width,
+            height: image_height,
+            pixels,
+        },
+        sample_counts,
+    )
+}
+
+#[cfg(test)]
+use shape::{Plane, Sphere, XZRect};
+
+#[test]
+fn test_write_batch_color_matches_write_color() {
+    let pixel_colors = [
+        vec3!(0.0, 0.0, 0.0),
+        vec3!(1.0, 0.5, 0.25),
+        vec3!(2.0, 0.1, 0.9),
+        vec3!(0.3, 0.3, 0.3),
+    ];
+    let post_process = PostProcess {
+        exposure: 0.5,
+        tone_map: image::ToneMap::Reinhard,
+        gamma: 2.2,
+    };
+    let mut expected: Vec<u8> = vec![];
+    for pixel_color in pixel_colors.iter() {
+        write_color(&mut expected, *pixel_color, &post_process).unwrap();
+    }
+    let mut actual: Vec<u8> = vec![];
+    write_batch_color(&mut actual, &pixel_colors, &post_process).unwrap();
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn test_material_override_renders_uniform_diffuse_shading() {
+    let clay = Material::Lambertian {
+        albedo: vec3!(0.5, 0.5, 0.5),
+    };
+    let world_of = |materials: [Material; 3]| -> Vec<Box<dyn Collidable>> {
+        let [ground, left, right] = materials;
+        vec![
+            Box::new(Plane {
+                point: vec3!(0.0, -0.5, 0.0),
+                normal: vec3!(0.0, 1.0, 0.0),
+                material: ground,
+            }),
+            Box::new(Sphere {
+                center: vec3!(-0.5, 0.0, -1.0),
+                radius: 0.5,
+                material: left,
+            }),
+            Box::new(Sphere {
+                center: vec3!(0.5, 0.0, -1.0),
+                radius: 0.5,
+                material: right,
+            }),
+        ]
+    };
+    let mixed = world_of([
+        Material::Metal {
+            albedo: vec3!(0.8, 0.6, 0.2),
+            fuzzyness: 0.0,
+            f0: None,
+        },
+        Material::Dialectric {
+            refraction_index: material::WINDOW_GLASS_REFRACTION,
+            attenuation: vec3!(1.0, 1.0, 1.0),
+            absorption: 0.0,
+        },
+        Material::Lambertian {
+            albedo: vec3!(0.9, 0.1, 0.1),
+        },
+    ]);
+    let clay_world = world_of([clay.clone(), clay.clone(), clay.clone()]);
+    let (_, camera) = test_scene();
+    let config = RenderConfig {
+        per_pixel_seed: Some(3),
+        ..Default::default()
+    };
+    let overridden_config = RenderConfig {
+        material_override: Some(&clay),
+        ..config
+    };
+
+    let overridden = render(&mixed, &camera, 8, 6, 4, &overridden_config, |_, _| {});
+    let expected = render(&clay_world, &camera, 8, 6, 4, &config, |_, _| {});
+    assert_eq!(overridden, expected);
+    let original = render(&mixed, &camera, 8, 6, 4, &config, |_, _| {});
+    assert_ne!(original, expected);
+}
+
+#[test]
+fn test_render_config_prints_the_background_by_address() {
+    let sky = SKY;
+    let config = RenderConfig {
+        background: &sky,
+        ..Default::default()
+    };
+    let debug = format!("{:?}", config);
+    assert!(
+        debug.starts_with("RenderConfig { max_depth: 50,"),
+        "{}",
+        debug
+    );
+    let address = format!("{:?}", &sky as *const dyn Background);
+    assert!(
+        debug.contains(&format!("background: {}", address)),
+        "{}",
+        debug
+    );
+}
+
+#[test]
+fn test_solid_background_replaces_the_sky() {
+    let world: Vec<Box<dyn Collidable>> = vec![];
+    let ray = || Ray {
+        origin: vec3!(0.0, 0.0, 0.0),
+        direction: vec3!(0.0, 1.0, -1.0),
+    };
+    let config = RenderConfig::default();
+    let sky = SKY.sample(&ray());
+    assert_eq!(get_ray_color(ray(), &world, &config), sky);
+
+    let black = vec3!(0.0, 0.0, 0.0);
+    let config = RenderConfig {
+        background: &background::SolidBackground(black),
+        ..Default::default()
+    };
+    assert_eq!(get_ray_color(ray(), &world, &config), black);
+}
+
+#[test]
+fn test_ambient_lights_enclosed_points() {
+    // the camera sits inside a closed sphere, the sky is never reached
+    let world: Vec<Box<dyn Collidable>> = vec![Box::new(Sphere {
+        center: vec3!(0.0, 0.0, 0.0),
+        radius: 2.0,
+        material: Material::Lambertian {
+            albedo: vec3!(0.8, 0.8, 0.8),
+        },
+    })];
+    let ray = || Ray {
+        origin: vec3!(0.0, 0.0, 0.0),
+        direction: vec3!(0.3, -0.2, 1.0),
+    };
+    let config = RenderConfig {
+        max_depth: 10,
+        ..Default::default()
+    };
+    let enclosed = get_ray_color(ray(), &world, &config);
+    assert_eq!(enclosed, vec3!(0.0, 0.0, 0.0));
+
+    let ambient = vec3!(0.05, 0.1, 0.2);
+    let config = RenderConfig { ambient, ..config };
+    // added once, not once per bounce
+    assert_eq!(get_ray_color(ray(), &world, &config), ambient);
+    // rays missing the world only see the background
+    let world: Vec<Box<dyn Collidable>> = vec![];
+    let sky = SKY.sample(&ray());
+    assert_eq!(get_ray_color(ray(), &world, &config), sky);
+}
+
+#[test]
+fn test_frame_scene_sees_both_spheres() {
+    let material = Material::Lambertian {
+        albedo: vec3!(0.5, 0.5, 0.5),
+    };
+    let world: Vec<Box<dyn Collidable>> = vec![
+        Box::new(Sphere {
+            center: vec3!(-3.0, 1.0, -2.0),
+            radius: 1.0,
+            material: material.clone(),
+        }),
+        Box::new(Sphere {
+            center: vec3!(4.0, -1.0, 1.0),
+            radius: 0.5,
+            material: material.clone(),
+        }),
+        Box::new(Plane {
+            point: vec3!(0.0, -10.0, 0.0),
+            normal: vec3!(0.0, 1.0, 0.0),
+            material,
+        }),
+    ];
+    let bounds = scene_bounds(&world).unwrap();
+    // the plane is unbounded and skipped
+    assert_eq!(bounds.min, vec3!(-4.0, -1.5, -3.0));
+    assert_eq!(bounds.max, vec3!(4.5, 2.0, 1.5));
+    assert!(scene_bounds(&world[2..]).is_none());
+
+    let camera = camera::Camera::frame_scene(&bounds, 16.0 / 9.0, 40.0).unwrap();
+    let mut hits = [0, 0];
+    for i in 0..64 {
+        for j in 0..36 {
+            let x = (i as f64 + 0.5) / 64.0;
+            let y = (j as f64 + 0.5) / 36.0;
+            let ray = camera.ray_through(x, y);
+            if let Some(collision) = get_closest_collision(&ray, &world[..2], DEFAULT_T_MIN) {
+                let sphere = usize::from(collision.pos.x > 0.0);
+                hits[sphere] += 1;
+            }
+        }
+    }
+    assert!(hits[0] > 0 && hits[1] > 0, "{:?}", hits);
+
+    let error = camera::Camera::frame_scene(&bounds, 1.0, 180.0).unwrap_err();
+    assert_eq!(error, camera::CameraError::FieldOfViewOutOfRange(180.0));
+}
+
+#[test]
+fn test_closest_collision_in_mixed_world() {
+    let sphere_material = Material::Lambertian {
+        albedo: vec3!(0.1, 0.2, 0.5),
+    };
+    let plane_material = Material::Lambertian {
+        albedo: vec3!(0.8, 0.8, 0.0),
+    };
+    let world: Vec<Box<dyn Collidable>> = vec![
+        Box::new(Plane {
+            point: vec3!(0.0, 0.0, -5.0),
+            normal: vec3!(0.0, 0.0, 1.0),
+            material: plane_material.clone(),
+        }),
+        Box::new(Sphere {
+            center: vec3!(0.0, 0.0, -2.0),
+            radius: 0.5,
+            material: sphere_material.clone(),
+        }),
+    ];
+    let ray = Ray {
+        origin: vec3!(),
+        direction: vec3!(0.0, 0.0, -1.0),
+    };
+    let closest = get_closest_collision(&ray, &world, DEFAULT_T_MIN).unwrap();
+    assert_eq!(closest.t, 1.5);
+    assert_eq!(*closest.material, sphere_material);
+
+    // the sphere is off to the side, only the plane remains
+    let ray = Ray {
+        origin: vec3!(3.0, 0.0, 0.0),
+        direction: vec3!(0.0, 0.0, -1.0),
+    };
+    let closest = get_closest_collision(&ray, &world, DEFAULT_T_MIN).unwrap();
+    assert_eq!(closest.t, 5.0);
+    assert_eq!(*closest.material, plane_material);
+}
+
+#[test]
+fn test_collisions_borrow_the_material_of_the_shape() {
+    let pixels = vec![vec3!(0.5, 0.5, 0.5); 64 * 64];
+    let material = Material::Textured {
+        texture: texture::Texture::image(pixels, 64, 64).unwrap(),
+    };
+    // a row of 100 spheres along the x axis
+    let spheres: Vec<Box<Sphere>> = (0..100)
+        .map(|i| {
+            Box::new(Sphere {
+                center: vec3!(i as f64, 0.0, -2.0),
+                radius: 0.4,
+                material: material.clone(),
+            })
+        })
+        .collect();
+    // the spheres stay in place when moved into the world
+    let materials: Vec<*const Material> = spheres
+        .iter()
+        .map(|sphere| &sphere.material as *const Material)
+        .collect();
+    let world: Vec<Box<dyn Collidable>> = spheres
+        .into_iter()
+        .map(|sphere| sphere as Box<dyn Collidable>)
+        .collect();
+
+    for (i, sphere_material) in materials.iter().enumerate() {
+        let ray = Ray {
+            origin: vec3!(i as f64, 0.0, 0.0),
+            direction: vec3!(0.0, 0.0, -1.0),
+        };
+        let collision = get_closest_collision(&ray, &world, DEFAULT_T_MIN).unwrap();
+        assert!(std::ptr::eq(collision.material, *sphere_material));
+    }
+}
+
+#[cfg(test)]
+fn enclosed_light_world(emit: Color) -> Vec<Box<dyn Collidable>> {
+    vec![
+        // the ray origin lies inside the light, hence no ray can reach the sky
+        Box::new(Sphere {
+            center: vec3!(),
+            radius: 10.0,
+            material: Material::DiffuseLight { emit },
+        }),
+        Box::new(Sphere {
+            center: vec3!(0.0, 0.0, -2.0),
+            radius: 0.5,
+            material: Material::Lambertian {
+                albedo: vec3!(0.5, 0.5, 0.5),
+            },
+        }),
+    ]
+}
+
+#[test]
+fn test_diffuse_light_illuminates_scene() {
+    let ray = Ray {
+        origin: vec3!(),
+        direction: vec3!(0.0, 0.0, -1.0),
+    };
+    let color = get_ray_color(
+        ray,
+        &enclosed_light_world(vec3!(4.0, 4.0, 4.0)),
+        &RenderConfig::default(),
+    );
+    assert!(
+        color.x > 0.0 && color.y > 0.0 && color.z > 0.0,
+        "{:?}",
+        color
+    );
+
+    // without emission nothing else contributes any light
+    let ray = Ray {
+        origin: vec3!(),
+        direction: vec3!(0.0, 0.0, -1.0),
+    };
+    let color = get_ray_color(
+        ray,
+        &enclosed_light_world(vec3!()),
+        &RenderConfig::default(),
+    );
+    assert_eq!(color, vec3!());
+}
+
+#[test]
+fn test_ray_hitting_diffuse_light_returns_emission() {
+    let ray = Ray {
+        origin: vec3!(),
+        direction: vec3!(0.0, 1.0, 0.0),
+    };
+    let color = get_ray_color(
+        ray,
+        &enclosed_light_world(vec3!(4.0, 2.0, 1.0)),
+        &RenderConfig::default(),
+    );
+    assert_eq!(color, vec3!(4.0, 2.0, 1.0));
+}
+
+#[test]
+fn test_normal_debug_material_shows_normal_as_color() {
+    let world: Vec<Box<dyn Collidable>> = vec![Box::new(Sphere {
+        center: vec3!(0.0, 0.0, -3.0),
+        radius: 1.0,
+        material: Material::NormalDebug,
+    })];
+    let color_towards = |origin, direction| {
+        get_ray_color(Ray { origin, direction }, &world, &RenderConfig::default())
+    };
+
+    // the normal points along +x, i.e. red
+    let color = color_towards(vec3!(3.0, 0.0, -3.0), vec3!(-1.0, 0.0, 0.0));
+    assert_eq!(color, vec3!(1.0, 0.5, 0.5));
+    // the normal points along +y, i.e. green
+    let color = color_towards(vec3!(0.0, 3.0, -3.0), vec3!(0.0, -1.0, 0.0));
+    assert_eq!(color, vec3!(0.5, 1.0, 0.5));
+}
+
+#[cfg(test)]
+fn test_scene() -> (Vec<Box<dyn Collidable>>, Camera) {
+    let world: Vec<Box<dyn Collidable>> = vec![
+        Box::new(Plane {
+            point: vec3!(0.0, -0.5, 0.0),
+            normal: vec3!(0.0, 1.0, 0.0),
+            material: Material::Lambertian {
+                albedo: vec3!(0.8, 0.8, 0.0),
+            },
+        }),
+        Box::new(Sphere {
+            center: vec3!(0.0, 0.0, -1.0),
+            radius: 0.5,
+            material: Material::Metal {
+                albedo: vec3!(0.8, 0.6, 0.2),
+                fuzzyness: 0.3,
+                f0: None,
+            },
+        }),
+    ];
+    let camera = camera::setup_camera_unchecked(
+        vec3!(0.0, 0.0, 1.0),
+        vec3!(0.0, 0.0, -1.0),
+        vec3!(0.0, 1.0, 0.0),
+        60.0,
+        4.0 / 3.0,
+        0.1,
+        2.0,
+    );
+    (world, camera)
+}
+
+#[test]
+fn test_render_parallel_with_one_thread_matches_sequential_loop() {
+    let (world, camera) = test_scene();
+    let (image_width, image_height, samples_per_pixel) = (8, 6, 4);
+    let config = RenderConfig {
+        max_depth: 10,
+        ..Default::default()
+    };
+    let actual = render_parallel(
+        &world,
+        &camera,
+        image_width,
+        image_height,
+        samples_per_pixel,
+        &config,
+        1,
+    );
+
+    // the render loop of the binaries, seeded like the first tile
+    rng::seed_thread_rng(0);
+    let mut expected = vec![];
+    for i in (0..image_height).rev() {
+        for j in 0..image_width {
+            let mut pixel_color: Color = vec3!(0.0, 0.0, 0.0);
+            for _sample in 0..samples_per_pixel {
+                let u = ((j as f64) + rand_f64(0.0, 0.999)) / ((image_width - 1) as f64);
+                let v = ((i as f64) + rand_f64(0.0, 0.999)) / ((image_height - 1) as f64);
+                let ray = camera.send_ray_towards(u, v);
+                pixel_color += get_ray_color(ray, &world, &config);
+            }
+            expected.push(pixel_color * (1.0 / samples_per_pixel as f64));
+        }
+    }
+
+    assert_eq!(actual.pixels, expected);
+}
+
+#[test]
+fn test_render_parallel_is_deterministic_per_thread_count() {
+    let (world, camera) = test_scene();
+    let config = RenderConfig {
+        max_depth: 10,
+        ..Default::default()
+    };
+    let first = render_parallel(&world, &camera, 8, 6, 4, &config, 3);
+    let second = render_parallel(&world, &camera, 8, 6, 4, &config, 3);
+
+    assert_eq!(first.pixels.len(), 8 * 6);
+    assert_eq!(first, second);
+}
+
+// reference implementation of get_ray_color as it was written recursively
+#[cfg(test)]
+fn get_ray_color_recursive(ray: Ray, world: &[Box<dyn Collidable>], max_depth: usize) -> Color {
     if max_depth == 0 {
         return vec3!();
     }
-    if let Some(collision) = get_closest_collision(&ray, world) {
-        if let Some((scattered_ray, scattered_color)) = collision.material.scatter(
-            &ray,
-            &collision.pos,
-            &collision.normal,
-            collision.ray_is_inside,
-            &collision.material,
-        ) {
-            return scattered_color * get_ray_color(scattered_ray, world, max_depth - 1);
+    if let Some(collision) = get_closest_collision(&ray, world, DEFAULT_T_MIN) {
+        let emitted = collision.material.emitted(&collision);
+        if let Some((scattered_ray, scattered_color)) =
+            collision
+                .material
+                .scatter(&ray, &collision, None, VACUUM_REFRACTION)
+        {
+            return emitted
+                + scattered_color * get_ray_color_recursive(scattered_ray, world, max_depth - 1);
         }
-        return vec3!(0.0, 0.0, 0.0);
+        return emitted;
     }
-    let unit_direction: Vec3 = ray.direction.to_unit_vec();
+    let unit_direction: Vec3 = ray.direction.to_unit_vec_unchecked();
     let t: f64 = (0.5 * (unit_direction.y + 1.0)).clamp(0.0, 1.0);
-    let color = (1.0 - t) * vec3!(1.0, 1.0, 1.0) + t * vec3!(0.5, 0.7, 1.0);
-    return color;
+    (1.0 - t) * vec3!(1.0, 1.0, 1.0) + t * vec3!(0.5, 0.7, 1.0)
+}
+
+#[test]
+fn test_iterative_ray_color_matches_recursive() {
+    let (mut world, camera) = test_scene();
+    world.push(Box::new(Sphere {
+        center: vec3!(-1.0, 0.0, -1.0),
+        radius: 0.5,
+        material: Material::Dialectric {
+            refraction_index: material::WINDOW_GLASS_REFRACTION,
+            attenuation: vec3!(1.0, 1.0, 1.0),
+            absorption: 0.0,
+        },
+    }));
+    world.push(Box::new(Sphere {
+        center: vec3!(1.0, 0.0, -1.0),
+        radius: 0.3,
+        material: Material::DiffuseLight {
+            emit: vec3!(2.0, 2.0, 2.0),
+        },
+    }));
+
+    let config = RenderConfig::default();
+    for k in 0..200 {
+        let (x, y) = ((k % 20) as f64 / 19.0, (k / 20) as f64 / 9.0);
+        rng::seed_thread_rng(k);
+        let actual = get_ray_color(camera.send_ray_towards(x, y), &world, &config);
+        rng::seed_thread_rng(k);
+        let expected =
+            get_ray_color_recursive(camera.send_ray_towards(x, y), &world, config.max_depth);
+
+        // both draw the same random numbers and hence follow the same path, but the
+        // colors are not bitwise equal: the recursion sums e_0 + a_0 * (e_1 + a_1 * ...)
+        // from the last bounce backwards, while get_ray_color accumulates
+        // e_0 + a_0 * e_1 + (a_0 * a_1) * e_2 + ... along the path, and floating-point
+        // multiplication does not distribute exactly over addition
+        let distance = (actual - expected).norm();
+        assert!(
+            distance <= 1e-12 * expected.norm().max(1.0),
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+}
+
+#[test]
+fn test_write_ppm_into_memory() {
+    let (world, camera) = test_scene();
+    let framebuffer = render_parallel(&world, &camera, 4, 4, 2, &RenderConfig::default(), 1);
+
+    let mut buffer: Vec<u8> = vec![];
+    framebuffer
+        .write_ppm_to(&mut buffer, &PostProcess::default())
+        .unwrap();
+    let ppm = String::from_utf8(buffer).unwrap();
+    let mut lines = ppm.lines();
+
+    assert_eq!(lines.next(), Some("P3"));
+    assert_eq!(lines.next(), Some("4 4"));
+    assert_eq!(lines.next(), Some("255"));
+    let [r, g, b] = PostProcess::default().apply(framebuffer.get_pixel(0, 0));
+    assert_eq!(lines.next(), Some(format!("{} {} {}", r, g, b).as_str()));
+    assert_eq!(lines.count(), 15);
+}
+
+#[test]
+fn test_solid_black_background_darkens_missing_rays() {
+    let config = RenderConfig {
+        background: &background::SolidBackground(vec3!()),
+        ..Default::default()
+    };
+    let (world, camera) = test_scene();
+    for _ in 0..100 {
+        let ray = Ray {
+            origin: vec3!(),
+            direction: rng::rand_unit_vec(),
+        };
+        assert_eq!(get_ray_color(ray, &[], &config), vec3!());
+    }
+    // the scene only scatters light coming from the background
+    for k in 0..100 {
+        let (x, y) = ((k % 10) as f64 / 9.0, (k / 10) as f64 / 9.0);
+        let color = get_ray_color(camera.send_ray_towards(x, y), &world, &config);
+        assert_eq!(color, vec3!());
+    }
+}
+
+#[test]
+fn test_direct_light_from_point_light() {
+    let ground = Plane {
+        point: vec3!(),
+        normal: vec3!(0.0, 1.0, 0.0),
+        material: Material::Lambertian {
+            albedo: vec3!(0.5, 0.5, 0.5),
+        },
+    };
+    let lights = [PointLight {
+        position: vec3!(0.0, 1.0, 0.0),
+        intensity: vec3!(2.0, 2.0, 2.0),
+        shape: None,
+        shadow_samples: 0,
+    }];
+    let collision_at = |x: f64| {
+        let ray = Ray {
+            origin: vec3!(x, 1.0, 1.0),
+            direction: vec3!(0.0, -1.0, -1.0),
+        };
+        ground.collide(&ray, 0.001, f64::MAX).unwrap()
+    };
+
+    let world: Vec<Box<dyn Collidable>> = vec![];
+    let above = direct_light(&collision_at(0.0), &world, &lights, DEFAULT_T_MIN);
+    let grazing = direct_light(&collision_at(3.0), &world, &lights, DEFAULT_T_MIN);
+    assert_eq!(above, vec3!(2.0, 2.0, 2.0));
+    // cos = 1 / √10 at a squared distance of 10
+    assert!(
+        (grazing.x - 2.0 / 10f64.powf(1.5)).abs() < 1e-12,
+        "{:?}",
+        grazing
+    );
+    assert!(above.x > grazing.x);
+
+    // a sphere between the ground and the light casts a shadow
+    let world: Vec<Box<dyn Collidable>> = vec![Box::new(Sphere {
+        center: vec3!(0.0, 0.5, 0.0),
+        radius: 0.2,
+        material: Material::Lambertian {
+            albedo: vec3!(0.5, 0.5, 0.5),
+        },
+    })];
+    assert_eq!(
+        direct_light(&collision_at(0.0), &world, &lights, DEFAULT_T_MIN),
+        vec3!()
+    );
+    assert!(direct_light(&collision_at(3.0), &world, &lights, DEFAULT_T_MIN).x > 0.0);
+
+    // without any background light the ground is only lit by the point light
+    let world: Vec<Box<dyn Collidable>> = vec![Box::new(ground.clone())];
+    let ray = Ray {
+        origin: vec3!(0.0, 1.0, 1.0),
+        direction: vec3!(0.0, -1.0, -1.0),
+    };
+    let config = RenderConfig {
+        background: &background::SolidBackground(vec3!()),
+        ..Default::default()
+    };
+    assert_eq!(get_ray_color(ray, &world, &config), vec3!());
+    let ray = Ray {
+        origin: vec3!(0.0, 1.0, 1.0),
+        direction: vec3!(0.0, -1.0, -1.0),
+    };
+    let config = RenderConfig {
+        lights: &lights,
+        ..config
+    };
+    assert_eq!(get_ray_color(ray, &world, &config), vec3!(1.0, 1.0, 1.0));
+}
+
+#[test]
+fn test_spot_light_cone() {
+    let ground = Plane {
+        point: vec3!(),
+        normal: vec3!(0.0, 1.0, 0.0),
+        material: Material::Lambertian {
+            albedo: vec3!(0.5, 0.5, 0.5),
+        },
+    };
+    // pointing straight down from a height of 1, the inner cone reaches x = tan(20°)
+    // on the ground and the outer one x = 1
+    let lights = [SpotLight {
+        position: vec3!(0.0, 1.0, 0.0),
+        direction: vec3!(0.0, -2.0, 0.0),
+        intensity: vec3!(2.0, 2.0, 2.0),
+        inner_angle_deg: 20.0,
+        outer_angle_deg: 45.0,
+    }];
+    let point_light = [PointLight {
+        position: vec3!(0.0, 1.0, 0.0),
+        intensity: vec3!(2.0, 2.0, 2.0),
+        shape: None,
+        shadow_samples: 0,
+    }];
+    // light of the spot light and of a point light at the same position
+    let light_at = |x: f64| {
+        let ray = Ray {
+            origin: vec3!(x, 1.0, 1.0),
+            direction: vec3!(0.0, -1.0, -1.0),
+        };
+        let collision = ground.collide(&ray, 0.001, f64::MAX).unwrap();
+        (
+            direct_spot_light(&collision, &[], &lights, DEFAULT_T_MIN),
+            direct_light(&collision, &[], &point_light, DEFAULT_T_MIN),
+        )
+    };
+
+    // on the axis and within the inner cone it is as bright as the point light
+    assert_eq!(light_at(0.0), (vec3!(2.0, 2.0, 2.0), vec3!(2.0, 2.0, 2.0)));
+    let (spot, point) = light_at(0.35);
+    assert_eq!(spot, point);
+    // just past the outer cone it is dark
+    assert_eq!(light_at(1.001).0, vec3!());
+    let (spot, point) = light_at(0.6);
+    assert!(spot.x > 0.0 && spot.x < point.x);
+}
+
+#[test]
+fn test_spot_light_with_equal_angles_has_hard_edge() {
+    let light = SpotLight {
+        position: vec3!(0.0, 1.0, 0.0),
+        direction: vec3!(0.0, -1.0, 0.0),
+        intensity: vec3!(2.0, 2.0, 2.0),
+        inner_angle_deg: 45.0,
+        outer_angle_deg: 45.0,
+    };
+    assert_eq!(light.falloff(&vec3!(0.0, 0.0, 0.0)), 1.0);
+    assert_eq!(light.falloff(&vec3!(0.99, 0.0, 0.0)), 1.0);
+    assert_eq!(light.falloff(&vec3!(1.01, 0.0, 0.0)), 0.0);
+}
+
+#[test]
+fn test_sampling_area_lights_reduces_variance() {
+    let light = XZRect {
+        x0: -0.25,
+        x1: 0.25,
+        z0: -0.25,
+        z1: 0.25,
+        k: 2.0,
+        material: Material::DiffuseLight {
+            emit: vec3!(20.0, 20.0, 20.0),
+        },
+    };
+    let world: Vec<Box<dyn Collidable>> = vec![
+        Box::new(XZRect {
+            x0: -5.0,
+            x1: 5.0,
+            z0: -5.0,
+            z1: 5.0,
+            k: 0.0,
+            material: Material::Lambertian {
+                albedo: vec3!(0.5, 0.5, 0.5),
+            },
+        }),
+        Box::new(light.clone()),
+    ];
+    let area_lights: [&dyn Emitter; 1] = [&light];
+    let brute_force = RenderConfig {
+        max_depth: 4,
+        background: &background::SolidBackground(vec3!()),
+        ..Default::default()
+    };
+    let light_sampling = RenderConfig {
+        area_lights: &area_lights,
+        ..brute_force
+    };
+
+    // mean and variance of single sample estimates of the lit floor below the light
+    let estimate = |config: &RenderConfig| {
+        let sample_count = 4000;
+        let samples: Vec<f64> = (0..sample_count)
+            .map(|_| {
+                let ray = Ray {
+                    origin: vec3!(0.0, 1.0, 1.0),
+                    direction: vec3!(0.0, -1.0, -1.0),
+                };
+                get_ray_color(ray, &world, config).x
+            })
+            .collect();
+        let mean = samples.iter().sum::<f64>() / sample_count as f64;
+        let variance = samples.iter().map(|s| (s - mean) * (s - mean)).sum::<f64>()
+            / (sample_count - 1) as f64;
+        (mean, variance)
+    };
+    rng::seed_thread_rng(5);
+    let (brute_force_mean, brute_force_variance) = estimate(&brute_force);
+    let (mean, variance) = estimate(&light_sampling);
+
+    // both estimate the same light: 0.5 / pi * 20 * cos^2 / distance^2 * area
+    let expected = 0.5 / std::f64::consts::PI * 20.0 * 0.25 / 4.0;
+    assert!((mean - expected).abs() < 0.05 * expected, "{:?}", mean);
+    assert!(
+        (brute_force_mean - expected).abs() < 0.3 * expected,
+        "{:?}",
+        brute_force_mean
+    );
+    assert!(
+        variance < 0.01 * brute_force_variance,
+        "{:?} vs {:?}",
+        variance,
+        brute_force_variance
+    );
+}
+
+#[test]
+fn test_soft_shadow_factor_in_penumbra() {
+    let light_material = Material::DiffuseLight {
+        emit: vec3!(4.0, 4.0, 4.0),
+    };
+    let light = XZRect {
+        x0: -1.0,
+        x1: 1.0,
+        z0: -1.0,
+        z1: 1.0,
+        k: 2.0,
+        material: light_material.clone(),
+    };
+    // blocks the half x > 0 of the light as seen from the origin
+    let blocker = XZRect {
+        x0: 0.0,
+        x1: 5.0,
+        z0: -5.0,
+        z1: 5.0,
+        k: 1.0,
+        material: Material::Lambertian {
+            albedo: vec3!(0.5, 0.5, 0.5),
+        },
+    };
+    let lit_world: Vec<Box<dyn Collidable>> = vec![Box::new(light.clone())];
+    let shadowed_world: Vec<Box<dyn Collidable>> = vec![Box::new(light.clone()), Box::new(blocker)];
+    let shadow_factor = |point: Vec3, world: &[Box<dyn Collidable>], samples: usize| {
+        soft_shadow_factor(&point, &light, world, samples, DEFAULT_T_MIN)
+    };
+
+    assert_eq!(shadow_factor(vec3!(), &lit_world, 64), 1.0);
+    let penumbra = shadow_factor(vec3!(), &shadowed_world, 1000);
+    assert!(penumbra > 0.4 && penumbra < 0.6, "{:?}", penumbra);
+    let umbra = shadow_factor(vec3!(3.0, 0.0, 0.0), &shadowed_world, 64);
+    assert_eq!(umbra, 0.0);
+}
+
+#[test]
+#[should_panic(expected = "at least one sample")]
+fn test_soft_shadow_factor_without_samples_panics() {
+    let light = XZRect {
+        x0: -1.0,
+        x1: 1.0,
+        z0: -1.0,
+        z1: 1.0,
+        k: 2.0,
+        material: Material::DiffuseLight {
+            emit: vec3!(4.0, 4.0, 4.0),
+        },
+    };
+    soft_shadow_factor(&vec3!(), &light, &[], 0, DEFAULT_T_MIN);
+}
+
+#[test]
+fn test_medium_stack_of_glass_in_water() {
+    let water = Material::Dialectric {
+        refraction_index: material::WATER_20_CELSIUS_REFRACTION,
+        attenuation: vec3!(1.0, 1.0, 1.0),
+        absorption: 0.0,
+    };
+    let glass = Material::Dialectric {
+        refraction_index: material::WINDOW_GLASS_REFRACTION,
+        attenuation: vec3!(1.0, 1.0, 1.0),
+        absorption: 0.0,
+    };
+    let mut media = MediumStack::default();
+    assert_eq!(media.outside(false), None);
+    media.enter(&water);
+    // the glass is entered from and left into the water
+    assert_eq!(media.outside(false), Some(&water));
+    media.enter(&glass);
+    assert_eq!(media.outside(true), Some(&water));
+    media.leave();
+    // leaving the water into the ambient medium
+    assert_eq!(media.outside(true), None);
+    media.leave();
+    // a ray starting inside a body leaves it into the ambient medium
+    assert_eq!(media.outside(true), None);
+    media.leave();
+    assert_eq!(media.outside(false), None);
+}
+
+#[test]
+fn test_point_light_with_shape_casts_soft_shadows() {
+    let light = XZRect {
+        x0: -1.0,
+        x1: 1.0,
+        z0: -1.0,
+        z1: 1.0,
+        k: 2.0,
+        material: Material::DiffuseLight {
+            emit: vec3!(4.0, 4.0, 4.0),
+        },
+    };
+    let lights = [PointLight {
+        position: vec3!(0.0, 2.0, 0.0),
+        intensity: vec3!(4.0, 4.0, 4.0),
+        shape: Some(&light),
+        shadow_samples: 1000,
+    }];
+    let ground = Plane {
+        point: vec3!(),
+        normal: vec3!(0.0, 1.0, 0.0),
+        material: Material::Lambertian {
+            albedo: vec3!(0.5, 0.5, 0.5),
+        },
+    };
+    let ray = || Ray {
+        origin: vec3!(0.0, 0.5, 0.0),
+        direction: vec3!(0.0, -1.0, 0.0),
+    };
+    let collision = ground.collide(&ray(), DEFAULT_T_MIN, f64::MAX).unwrap();
+    // like a point light of intensity 4 at a distance of 2
+    let lit = direct_light(&collision, &[], &lights, DEFAULT_T_MIN);
+    assert_eq!(lit, vec3!(1.0, 1.0, 1.0));
+
+    // blocks the half x > 0 of the light as seen from the origin
+    let blocker: Vec<Box<dyn Collidable>> = vec![Box::new(XZRect {
+        x0: 0.0,
+        x1: 5.0,
+        z0: -5.0,
+        z1: 5.0,
+        k: 1.0,
+        material: Material::Lambertian {
+            albedo: vec3!(0.5, 0.5, 0.5),
+        },
+    })];
+    let penumbra = direct_light(&collision, &blocker, &lights, DEFAULT_T_MIN);
+    assert!(penumbra.x > 0.4 && penumbra.x < 0.6, "{:?}", penumbra);
+    // a point light at the same position is hidden behind the edge of the blocker
+    let point_lights = [PointLight {
+        shape: None,
+        ..lights[0]
+    }];
+    assert_eq!(
+        direct_light(&collision, &blocker, &point_lights, DEFAULT_T_MIN),
+        vec3!(0.0, 0.0, 0.0)
+    );
+
+    // the tracer adds it at diffuse hits
+    let world: Vec<Box<dyn Collidable>> = vec![Box::new(ground.clone())];
+    let config = RenderConfig {
+        max_depth: 1,
+        background: &background::SolidBackground(vec3!()),
+        lights: &lights,
+        ..Default::default()
+    };
+    assert_eq!(get_ray_color(ray(), &world, &config), vec3!(0.5, 0.5, 0.5));
 }
 
+#[test]
+fn test_cropped_render_matches_full_render() {
+    // a pinhole camera, centered samples and an unlit material draw no random numbers,
+    // hence the pixels do not depend on which other pixels are rendered
+    let world: Vec<Box<dyn Collidable>> = vec![Box::new(Sphere {
+        center: vec3!(0.0, 0.0, -1.0),
+        radius: 0.5,
+        material: Material::NormalDebug,
+    })];
+    let camera = camera::setup_camera_unchecked(
+        vec3!(0.0, 0.0, 0.0),
+        vec3!(0.0, 0.0, -1.0),
+        vec3!(0.0, 1.0, 0.0),
+        90.0,
+        1.5,
+        0.0,
+        1.0,
+    );
+    let config = RenderConfig {
+        sample_strategy: SampleStrategy::Uniform,
+        ..Default::default()
+    };
+    let full = render(&world, &camera, 12, 8, 2, &config, |_, _| {});
+    let config = RenderConfig {
+        crop: Some((3, 2, 9, 5)),
+        ..config
+    };
+    let cropped = render(&world, &camera, 12, 8, 2, &config, |_, _| {});
+
+    assert_eq!((cropped.width, cropped.height), (12, 8));
+    for y in 0..8 {
+        for x in 0..12 {
+            if (3..9).contains(&x) && (2..5).contains(&y) {
+                assert_eq!(cropped.get_pixel(x, y), full.get_pixel(x, y));
+                assert_ne!(cropped.get_pixel(x, y), vec3!());
+            } else {
+                assert_eq!(cropped.get_pixel(x, y), vec3!());
+            }
+        }
+    }
+}
+
+#[test]
+fn test_per_pixel_seed_gives_stable_noise() {
+    let (world, camera) = test_scene();
+    let config = RenderConfig {
+        per_pixel_seed: Some(7),
+        ..Default::default()
+    };
+    let ppm_bytes = |framebuffer: &Framebuffer| {
+        let mut bytes = vec![];
+        image::write_ppm_binary(&mut bytes, framebuffer, &PostProcess::default()).unwrap();
+        bytes
+    };
+    let first = render(&world, &camera, 8, 6, 4, &config, |_, _| {});
+    // some unrelated draws in between do not matter
+    rand_f64(0.0, 1.0);
+    let second = render(&world, &camera, 8, 6, 4, &config, |_, _| {});
+    assert_eq!(ppm_bytes(&first), ppm_bytes(&second));
+    // neither does the number of threads nor the set of rendered pixels
+    assert_eq!(render_parallel(&world, &camera, 8, 6, 4, &config, 3), first);
+    let cropped_config = RenderConfig {
+        crop: Some((2, 1, 5, 4)),
+        ..config
+    };
+    let cropped = render(&world, &camera, 8, 6, 4, &cropped_config, |_, _| {});
+    assert_eq!(cropped.get_pixel(3, 2), first.get_pixel(3, 2));
+
+    let other_seed_config = RenderConfig {
+        per_pixel_seed: Some(8),
+        ..config
+    };
+    let other_noise = render(&world, &camera, 8, 6, 4, &other_seed_config, |_, _| {});
+    assert_ne!(ppm_bytes(&other_noise), ppm_bytes(&first));
+}
+
+#[test]
+fn test_depth_limit_policy_between_parallel_mirrors() {
+    // no ray started between the mirrors ever escapes
+    let mirror = |y: f64, normal_y: f64| -> Box<dyn Collidable> {
+        Box::new(Plane {
+            point: vec3!(0.0, y, 0.0),
+            normal: vec3!(0.0, normal_y, 0.0),
+            material: Material::Metal {
+                albedo: vec3!(0.9, 0.9, 0.9),
+                fuzzyness: 0.0,
+                f0: None,
+            },
+        })
+    };
+    let world = vec![mirror(0.0, 1.0), mirror(1.0, -1.0)];
+    let camera = camera::setup_camera_unchecked(
+        vec3!(0.0, 0.5, 0.0),
+        vec3!(0.0, 0.5, -1.0),
+        vec3!(0.0, 1.0, 0.0),
+        90.0,
+        1.0,
+        0.0,
+        1.0,
+    );
+    let mean_brightness = |depth_limit_policy| {
+        let config = RenderConfig {
+            max_depth: 5,
+            depth_limit_policy,
+            ..Default::default()
+        };
+        let framebuffer = render(&world, &camera, 4, 4, 1, &config, |_, _| {});
+        let sum: f64 = framebuffer.pixels.iter().map(|p| p.x + p.y + p.z).sum();
+        sum / 48.0
+    };
+
+    let black = mean_brightness(DepthLimitPolicy::ReturnBlack);
+    let background = mean_brightness(DepthLimitPolicy::ReturnBackground);
+    let attenuation = mean_brightness(DepthLimitPolicy::ReturnAttenuation);
+    assert_eq!(black, 0.0);
+    assert!(background > 0.1, "{:?}", background);
+    assert!(attenuation > background, "{:?}", attenuation);
+}
+
+#[test]
+fn test_fog_blends_distant_surfaces_more_strongly() {
+    let light = |z: f64| -> Box<dyn Collidable> {
+        Box::new(Sphere {
+            center: vec3!(0.0, 0.0, z),
+            radius: 0.5,
+            material: Material::DiffuseLight {
+                emit: vec3!(1.0, 0.0, 0.0),
+            },
+        })
+    };
+    let world = vec![light(-1.5), light(10.5)];
+    let fog_color = vec3!(0.0, 0.0, 1.0);
+    let background = background::SolidBackground(vec3!(0.0, 1.0, 0.0));
+    let config = RenderConfig {
+        background: &background,
+        fog: Some(Fog {
+            color: fog_color,
+            density: 0.2,
+        }),
+        ..Default::default()
+    };
+    let trace = |direction: Vec3| {
+        let ray = Ray {
+            origin: vec3!(),
+            direction,
+        };
+        trace_path(ray, &world, &config).0
+    };
+
+    // both spheres are hit at the distance 1 resp. 10
+    let near = trace(vec3!(0.0, 0.0, -1.0));
+    let far = trace(vec3!(0.0, 0.0, 2.0));
+    let near_transmittance = (-0.2f64).exp();
+    let far_transmittance = (-2.0f64).exp();
+    assert!((near.x - near_transmittance).abs() < 1e-9, "{:?}", near);
+    let near_fog = 1.0 - near_transmittance;
+    assert!((near.z - near_fog).abs() < 1e-9, "{:?}", near);
+    assert!((far.x - far_transmittance).abs() < 1e-9, "{:?}", far);
+    assert!(far.z > near.z && far.x < near.x);
+    // misses are not fogged
+    assert_eq!(trace(vec3!(0.0, 1.0, 0.0)), vec3!(0.0, 1.0, 0.0));
+}
+
+#[test]
+fn test_accumulated_frames_match_render_with_all_samples() {
+    let (world, camera) = test_scene();
+    // with a single rendered pixel the samples draw the same random numbers in
+    // both cases
+    let config = RenderConfig {
+        crop: Some((2, 1, 3, 2)),
+        ..Default::default()
+    };
+    rng::seed_thread_rng(9);
+    let expected = render(&world, &camera, 4, 3, 4, &config, |_, _| {});
+
+    rng::seed_thread_rng(9);
+    let mut accumulator = image::Accumulator::new(12);
+    assert_eq!(accumulator.average(), vec![vec3!(); 12]);
+    for _ in 0..4 {
+        let frame = render(&world, &camera, 4, 3, 1, &config, |_, _| {});
+        accumulator.add_frame(&frame.pixels);
+    }
+    assert_eq!(accumulator.count, 4);
+    assert_eq!(accumulator.average(), expected.pixels);
+    assert_ne!(expected.get_pixel(2, 1), vec3!());
+}
+
+#[test]
+fn test_render_into_reuses_the_buffer() {
+    let (world, camera) = test_scene();
+    let config = RenderConfig {
+        per_pixel_seed: Some(3),
+        ..Default::default()
+    };
+    let other_camera = camera::setup_camera_unchecked(
+        vec3!(0.0, 3.0, 0.0),
+        vec3!(0.0, 0.0, -1.0),
+        vec3!(0.0, 1.0, 0.0),
+        60.0,
+        1.5,
+        0.0,
+        1.0,
+    );
+    let mut frame = vec![vec3!(); 12];
+    render_into(&world, &camera, 4, 3, 2, &config, &mut frame);
+    let first_frame = frame.clone();
+    let expected = render(&world, &camera, 4, 3, 2, &config, |_, _| {});
+    assert_eq!(first_frame, expected.pixels);
+
+    render_into(&world, &other_camera, 4, 3, 2, &config, &mut frame);
+    let expected = render(&world, &other_camera, 4, 3, 2, &config, |_, _| {});
+    assert_eq!(frame, expected.pixels);
+    assert_ne!(frame, first_frame);
+}
+
+#[test]
+#[should_panic(expected = "the buffer must hold image_width * image_height pixels")]
+fn test_render_into_rejects_wrong_buffer_size() {
+    let (world, camera) = test_scene();
+    let config = RenderConfig::default();
+    let mut frame = vec![vec3!(); 11];
+    render_into(&world, &camera, 4, 3, 1, &config, &mut frame);
+}
+
+// writer which counts its flushes and keeps the bytes written by the last writer
+#[cfg(test)]
+struct PreviewWriter<'a> {
+    flushes: &'a std::cell::Cell<usize>,
+    output: &'a std::cell::RefCell<Vec<u8>>,
+}
+
+#[cfg(test)]
+impl Write for PreviewWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.output.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.flushes.set(self.flushes.get() + 1);
+        Ok(())
+    }
+}
+
+#[test]
+fn test_render_with_preview_writes_partial_images() {
+    let (world, camera) = test_scene();
+    let config = RenderConfig {
+        per_pixel_seed: Some(1),
+        ..Default::default()
+    };
+    let post_process = PostProcess::default();
+    let flushes = std::cell::Cell::new(0);
+    let output = std::cell::RefCell::new(vec![]);
+    let mut previews: Vec<Vec<u8>> = vec![];
+    let open_output = || {
+        // the previous write is complete once a new one is opened
+        previews.push(output.take());
+        Ok(PreviewWriter {
+            flushes: &flushes,
+            output: &output,
+        })
+    };
+    let framebuffer = render_with_preview(
+        &world,
+        &camera,
+        4,
+        6,
+        2,
+        &config,
+        &post_process,
+        2,
+        open_output,
+    )
+    .unwrap();
+
+    // after the scanlines 2 and 4 and the final image
+    assert!(flushes.get() >= 3, "{}", flushes.get());
+    let mut expected = vec![];
+    framebuffer
+        .write_ppm_to(&mut expected, &post_process)
+        .unwrap();
+    assert_eq!(output.take(), expected);
+    // the first preview only holds the two top rows
+    let first_preview = String::from_utf8(previews[1].clone()).unwrap();
+    let black_rows = first_preview.lines().skip(3 + 2 * 4);
+    assert!(black_rows.clone().all(|line| line == "0 0 0"));
+    assert_eq!(black_rows.count(), 4 * 4);
+    let sequential = render(&world, &camera, 4, 6, 2, &config, |_, _| {});
+    assert_eq!(framebuffer, sequential);
+}
+
+#[test]
+fn test_render_with_stats_counts_rays_and_intersection_tests() {
+    let (world, camera) = test_scene();
+    let config = RenderConfig {
+        max_depth: 1,
+        ..Default::default()
+    };
+    let (_, stats) = render_with_stats(&world, &camera, 4, 3, 2, &config);
+    assert_eq!(stats.primary_rays, 4 * 3 * 2);
+    // a single nearest hit search per path
+    let expected = stats.primary_rays * world.len();
+    assert_eq!(stats.intersection_tests, expected);
+    assert_eq!(stats.max_depth_reached, 1);
+    assert!(stats.scatter_rays <= stats.primary_rays);
+
+    // the counter is shared by the render threads
+    let counter = RenderStatsCounter::default();
+    let config = RenderConfig {
+        max_depth: 5,
+        stats: Some(&counter),
+        ..Default::default()
+    };
+    render_parallel(&world, &camera, 4, 3, 2, &config, 3);
+    let stats = counter.stats();
+    assert_eq!(stats.primary_rays, 4 * 3 * 2);
+    assert!(stats.intersection_tests >= stats.primary_rays * world.len());
+    assert!(stats.max_depth_reached > 1 && stats.max_depth_reached <= 5);
+}
+
+#[test]
+fn test_nan_samples_do_not_poison_the_pixel() {
+    // the sphere fills the whole view
+    let world: Vec<Box<dyn Collidable>> = vec![Box::new(Sphere {
+        center: vec3!(0.0, 0.0, -1.0),
+        radius: 1.5,
+        material: Material::Lambertian {
+            albedo: vec3!(f64::NAN, 0.5, f64::INFINITY),
+        },
+    })];
+    let (_, camera) = test_scene();
+    let config = RenderConfig::default();
+    let non_finite_before = non_finite_sample_count();
+    let framebuffer = render(&world, &camera, 3, 3, 4, &config, |_, _| {});
+    for pixel in framebuffer.pixels.iter() {
+        assert!(pixel.x.is_finite() && pixel.y.is_finite() && pixel.z.is_finite());
+        assert!(pixel.y > 0.0, "{:?}", pixel);
+    }
+    assert!(non_finite_sample_count() > non_finite_before);
+}
+
+#[test]
+fn test_render_supersampled_downscales_render() {
+    let (world, camera) = test_scene();
+    let config = RenderConfig::default();
+    rng::seed_thread_rng(1);
+    let supersampled = render_supersampled(&world, &camera, 3, 2, 2, &config, 2);
+    rng::seed_thread_rng(1);
+    let expected = render(&world, &camera, 6, 4, 2, &config, |_, _| {}).downscale(2);
+    assert_eq!((supersampled.width, supersampled.height), (3, 2));
+    assert_eq!(supersampled, expected);
+}
+
+#[test]
+fn test_render_one_sphere_scene() {
+    let world: Vec<Box<dyn Collidable>> = vec![Box::new(Sphere {
+        center: vec3!(0.0, 0.0, -1.0),
+        radius: 0.3,
+        material: Material::Lambertian {
+            albedo: vec3!(0.8, 0.1, 0.1),
+        },
+    })];
+    let camera = camera::setup_camera_unchecked(
+        vec3!(0.0, 0.0, 0.0),
+        vec3!(0.0, 0.0, -1.0),
+        vec3!(0.0, 1.0, 0.0),
+        90.0,
+        1.0,
+        0.0,
+        1.0,
+    );
+    let framebuffer = render(
+        &world,
+        &camera,
+        8,
+        8,
+        4,
+        &RenderConfig {
+            // fixes the noise, which would otherwise depend on the rng stream of earlier
+            // draws, e.g. on whether the camera samples its lens
+            per_pixel_seed: Some(0),
+            ..Default::default()
+        },
+        |_, _| {},
+    );
+
+    assert_eq!((framebuffer.width, framebuffer.height), (8, 8));
+    assert_eq!(framebuffer.pixels.len(), 64);
+    assert!(framebuffer.pixels.iter().all(|pixel| pixel.norm() > 0.0));
+    // the red sphere covers the center, the corners only see the sky
+    let center = framebuffer.get_pixel(4, 4);
+    for (x, y) in [(0, 0), (7, 0), (0, 7), (7, 7)] {
+        let corner = framebuffer.get_pixel(x, y);
+        assert!(center.x > center.z && corner.z > corner.x, "{:?}", corner);
+    }
+}
+
+#[test]
+fn test_render_reports_progress_per_scanline() {
+    let (world, camera) = test_scene();
+    let mut calls = vec![];
+    let framebuffer = render(
+        &world,
+        &camera,
+        4,
+        5,
+        1,
+        &RenderConfig::default(),
+        |rows_done, total_rows| calls.push((rows_done, total_rows)),
+    );
+
+    assert_eq!(framebuffer.pixels.len(), 4 * 5);
+    assert_eq!(calls, vec![(1, 5), (2, 5), (3, 5), (4, 5), (5, 5)]);
+}
+
+#[test]
+fn test_render_parallel_reports_progress_per_tile() {
+    let (world, camera) = test_scene();
+    let mut calls = vec![];
+    let config = RenderConfig::default();
+    let framebuffer = render_parallel_with_progress(
+        &world,
+        &camera,
+        4,
+        6,
+        1,
+        &config,
+        4,
+        |rows_done, total_rows| calls.push((rows_done, total_rows)),
+    );
+
+    // tiles of two rows each, the fourth tile is empty
+    assert_eq!(calls, vec![(2, 6), (4, 6), (6, 6)]);
+    assert_eq!(
+        framebuffer,
+        render_parallel(&world, &camera, 4, 6, 1, &config, 4)
+    );
+}
+
+#[test]
+fn test_render_aov_depth_and_normal_of_centered_sphere() {
+    let world: Vec<Box<dyn Collidable>> = vec![Box::new(Sphere {
+        center: vec3!(0.0, 0.0, -1.0),
+        radius: 0.3,
+        material: Material::Lambertian {
+            albedo: vec3!(0.8, 0.1, 0.1),
+        },
+    })];
+    let camera = camera::setup_camera_unchecked(
+        vec3!(0.0, 0.0, 0.0),
+        vec3!(0.0, 0.0, -1.0),
+        vec3!(0.0, 1.0, 0.0),
+        90.0,
+        1.0,
+        0.0,
+        1.0,
+    );
+    let aov = render_aov(&world, &camera, 9, 9, 1, &RenderConfig::default());
+
+    assert_eq!(aov.color.pixels.len(), 81);
+    assert_eq!(aov.depth.len(), 81);
+    assert_eq!(aov.normal.pixels.len(), 81);
+    let center_depth = aov.depth[4 * 9 + 4];
+    assert!(center_depth.is_finite(), "{}", center_depth);
+    for (x, y) in [(0, 0), (8, 0), (0, 8), (8, 8)] {
+        let corner_depth = aov.depth[y * 9 + x];
+        assert!(center_depth < corner_depth, "{}", corner_depth);
+        assert_eq!(aov.normal.get_pixel(x, y), vec3!(0.0, 0.0, 0.0));
+    }
+    // the sphere faces the camera at the center
+    let center_normal = aov.normal.get_pixel(4, 4);
+    assert!(center_normal.z > 0.9, "{:?}", center_normal);
+}
+
+#[test]
+fn test_russian_roulette_keeps_brightness_with_fewer_scatter_calls() {
+    // a closed gray room, every path bounces until it reaches the light
+    let world: Vec<Box<dyn Collidable>> = vec![
+        Box::new(Sphere {
+            center: vec3!(),
+            radius: 5.0,
+            material: Material::Lambertian {
+                albedo: vec3!(0.7, 0.7, 0.7),
+            },
+        }),
+        Box::new(Sphere {
+            center: vec3!(0.0, 0.0, -2.0),
+            radius: 1.0,
+            material: Material::DiffuseLight {
+                emit: vec3!(1.0, 1.0, 1.0),
+            },
+        }),
+    ];
+    let sample_count = 20_000;
+    let mean_brightness = |config: &RenderConfig| {
+        rng::seed_thread_rng(3);
+        let mut brightness = 0.0;
+        let mut scatter_count = 0;
+        for _ in 0..sample_count {
+            let ray = Ray {
+                origin: vec3!(),
+                direction: vec3!(0.0, 1.0, 0.0),
+            };
+            let (color, scatter_calls) = trace_path(ray, &world, config);
+            brightness += color.x;
+            scatter_count += scatter_calls;
+        }
+        (brightness / sample_count as f64, scatter_count)
+    };
+
+    let (full_brightness, full_scatter_count) = mean_brightness(&RenderConfig::default());
+    let (roulette_brightness, roulette_scatter_count) = mean_brightness(&RenderConfig {
+        roulette_min_bounces: Some(2),
+        ..Default::default()
+    });
+
+    let relative_error = (roulette_brightness - full_brightness).abs() / full_brightness;
+    assert!(
+        relative_error < 0.05,
+        "{} vs {}",
+        roulette_brightness,
+        full_brightness
+    );
+    assert!(
+        roulette_scatter_count < full_scatter_count * 3 / 4,
+        "{} vs {}",
+        roulette_scatter_count,
+        full_scatter_count
+    );
+}
+
+#[test]
+fn test_adaptive_render_samples_edges_more_than_flat_regions() {
+    // a light in front of a black background: the only variance is at its silhouette
+    let world: Vec<Box<dyn Collidable>> = vec![Box::new(Sphere {
+        center: vec3!(0.0, 0.0, -1.0),
+        radius: 0.4,
+        material: Material::DiffuseLight {
+            emit: vec3!(1.0, 1.0, 1.0),
+        },
+    })];
+    let camera = camera::setup_camera_unchecked(
+        vec3!(0.0, 0.0, 0.0),
+        vec3!(0.0, 0.0, -1.0),
+        vec3!(0.0, 1.0, 0.0),
+        90.0,
+        1.0,
+        0.0,
+        1.0,
+    );
+    let black = background::SolidBackground(vec3!(0.0, 0.0, 0.0));
+    let config = RenderConfig {
+        background: &black,
+        ..Default::default()
+    };
+    let sampling = AdaptiveSampling {
+        batch_size: 4,
+        max_samples: 64,
+        variance_threshold: 1e-4,
+    };
+    let (framebuffer, sample_counts) = adaptive_render(&world, &camera, 16, 16, &config, &sampling);
+
+    assert_eq!(sample_counts.len(), 16 * 16);
+    // the flat center of the light and the background corners stop after one batch
+    assert_eq!(sample_counts[8 * 16 + 8], 4);
+    assert_eq!(framebuffer.get_pixel(8, 8), vec3!(1.0, 1.0, 1.0));
+    assert_eq!(sample_counts[0], 4);
+    assert_eq!(framebuffer.get_pixel(0, 0), vec3!(0.0, 0.0, 0.0));
+    // pixels on the silhouette see both and keep sampling
+    let mut edge_pixels = 0;
+    for (pixel, sample_count) in framebuffer.pixels.iter().zip(&sample_counts) {
+        if pixel.x > 0.0 && pixel.x < 1.0 {
+            edge_pixels += 1;
+            assert!(*sample_count > 4, "{:?}", sample_count);
+        }
+    }
+    assert!(edge_pixels > 0);
+}
+
+#[test]
+fn test_adaptive_render_drops_non_finite_samples() {
+    // a light whose red channel is NaN in front of a black background
+    let world: Vec<Box<dyn Collidable>> = vec![Box::new(Sphere {
+        center: vec3!(0.0, 0.0, -1.0),
+        radius: 0.4,
+        material: Material::DiffuseLight {
+            emit: vec3!(f64::NAN, 1.0, 1.0),
+        },
+    })];
+    let camera = camera::setup_camera_unchecked(
+        vec3!(0.0, 0.0, 0.0),
+        vec3!(0.0, 0.0, -1.0),
+        vec3!(0.0, 1.0, 0.0),
+        90.0,
+        1.0,
+        0.0,
+        1.0,
+    );
+    let black = background::SolidBackground(vec3!(0.0, 0.0, 0.0));
+    let config = RenderConfig {
+        background: &black,
+        ..Default::default()
+    };
+    let sampling = AdaptiveSampling {
+        batch_size: 4,
+        max_samples: 64,
+        variance_threshold: 1e-4,
+    };
+    let (framebuffer, sample_counts) = adaptive_render(&world, &camera, 16, 16, &config, &sampling);
+
+    // the NaN channel neither poisons the pixel nor its variance
+    assert_eq!(framebuffer.get_pixel(8, 8), vec3!(0.0, 1.0, 1.0));
+    assert_eq!(sample_counts[8 * 16 + 8], 4);
+}
+
+#[test]
+fn test_t_min_scaled_with_tiny_scene_keeps_contact_shadows() {
+    // a sphere of radius 0.5 resting on the ground scaled down 1000 times
+    let world: Vec<Box<dyn Collidable>> = vec![
+        Box::new(Plane {
+            point: vec3!(0.0, 0.0, 0.0),
+            normal: vec3!(0.0, 1.0, 0.0),
+            material: Material::Lambertian {
+                albedo: vec3!(0.5, 0.5, 0.5),
+            },
+        }),
+        Box::new(Sphere {
+            center: vec3!(0.0, 0.0005, 0.0),
+            radius: 0.0005,
+            material: Material::Lambertian {
+                albedo: vec3!(0.5, 0.5, 0.5),
+            },
+        }),
+    ];
+    // the light is hidden behind the sphere when seen from next to its contact point
+    let lights = [PointLight {
+        position: vec3!(-1.0, 1.0, 0.0),
+        intensity: vec3!(1.0, 1.0, 1.0),
+        shape: None,
+        shadow_samples: 0,
+    }];
+    let collision = Collision {
+        pos: vec3!(0.0002, 0.0, 0.0),
+        normal: vec3!(0.0, 1.0, 0.0),
+        ray_is_inside: false,
+        t: 1.0,
+        u: 0.0,
+        v: 0.0,
+        material: &Material::Lambertian {
+            albedo: vec3!(0.5, 0.5, 0.5),
+        },
+    };
+
+    // the whole sphere is closer than the default t_min, hence the shadow is missing
+    assert!(direct_light(&collision, &world, &lights, DEFAULT_T_MIN).x > 0.0);
+    let tuned_t_min = DEFAULT_T_MIN / 1000.0;
+    assert_eq!(
+        direct_light(&collision, &world, &lights, tuned_t_min),
+        vec3!(0.0, 0.0, 0.0)
+    );
+
+    // the same holds for the bounce rays of get_ray_color
+    let ray = Ray {
+        origin: collision.pos,
+        direction: vec3!(-1.0, 1.0, 0.0),
+    };
+    assert!(get_closest_collision(&ray, &world, DEFAULT_T_MIN).is_none());
+    let config = RenderConfig {
+        t_min: tuned_t_min,
+        ..Default::default()
+    };
+    let hit = get_closest_collision(&ray, &world, config.t_min).unwrap();
+    assert!(hit.t < DEFAULT_T_MIN, "{:?}", hit);
+}
+
+#[test]
+fn test_t_min_scaled_with_huge_scene_removes_shadow_acne() {
+    // shadow rays from the earth on its orbit around the sun at the origin towards
+    // the sun 45° above the horizon, with a unit of 1 m and scaled up 1000 times to mm.
+    // The earth is convex, hence every hit of a shadow ray is a self-hit
+    let count_self_hits = |scale: f64, t_min: f64| {
+        let center = vec3!(1.496e11 * scale, 0.0, 0.0);
+        let radius = 6.371e6 * scale;
+        let world: Vec<Box<dyn Collidable>> = vec![Box::new(Sphere {
+            center,
+            radius,
+            material: Material::Lambertian {
+                albedo: vec3!(0.5, 0.5, 0.5),
+            },
+        })];
+        rng::seed_thread_rng(7);
+        let eye = center + vec3!(radius + 2.0 * scale, 0.0, 0.0);
+        (0..100)
+            .filter(|_| {
+                let ground = center
+                    + vec3!(
+                        radius,
+                        rng::rand_f64(-50.0, 50.0) * scale,
+                        rng::rand_f64(-50.0, 50.0) * scale
+                    );
+                let ray = Ray {
+                    origin: eye,
+                    direction: ground - eye,
+                };
+                let collision = get_closest_collision(&ray, &world, t_min).unwrap();
+                let shadow_ray = Ray {
+                    origin: collision.pos,
+                    direction: collision.normal + vec3!(0.0, 1.0, 0.0),
+                };
+                get_closest_collision(&shadow_ray, &world, t_min).is_some()
+            })
+            .count()
+    };
+
+    // the rounding error of the hit points grows with their distance to the origin
+    assert_eq!(count_self_hits(1.0, DEFAULT_T_MIN), 0);
+    assert!(count_self_hits(1000.0, DEFAULT_T_MIN) > 0);
+    assert_eq!(count_self_hits(1000.0, 1000.0 * DEFAULT_T_MIN), 0);
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_render_rayon_matches_sequential_per_pixel_seeded_render() {
+    let (world, camera) = test_scene();
+    let config = RenderConfig {
+        per_pixel_seed: Some(42),
+        ..Default::default()
+    };
+    let expected = render(&world, &camera, 6, 4, 3, &config, |_, _| {});
+
+    let actual = render_rayon(&world, &camera, 6, 4, 3, &config);
+    assert_eq!(actual, expected);
+    // the same on a pool of a different size
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(3)
+        .build()
+        .unwrap();
+    let actual = pool.install(|| render_rayon(&world, &camera, 6, 4, 3, &config));
+    assert_eq!(actual, expected);
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_render_rayon_is_deterministic_without_per_pixel_seed() {
+    let (world, camera) = test_scene();
+    let config = RenderConfig::default();
+    let render_on_pool = |threads: usize| {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap();
+        pool.install(|| render_rayon(&world, &camera, 6, 4, 3, &config))
+    };
+
+    let expected = render_on_pool(1);
+    assert_eq!(render_on_pool(3), expected);
+    // an unset per_pixel_seed seeds every pixel with 0
+    let config = RenderConfig {
+        per_pixel_seed: Some(0),
+        ..Default::default()
+    };
+    assert_eq!(
+        render(&world, &camera, 6, 4, 3, &config, |_, _| {}),
+        expected
+    );
+}
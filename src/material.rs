@@ -1,5 +1,11 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::image::DEFAULT_GAMMA;
 use crate::ray::Ray;
-use crate::rng::{rand_f64, rand_unit_vec};
+use crate::rng::{rand_cosine_direction, rand_f64, rand_unit_vec};
+use crate::shape::Collision;
+use crate::texture::{shared_perlin, Texture};
 use crate::vec::{Color, Vec3};
 use crate::vec3;
 
@@ -13,125 +19,343 @@ pub const WATER_20_CELSIUS_REFRACTION: f64 = 1.333;
 pub const DIAMOND_REFRACTION: f64 = 2.417;
 
 #[allow(dead_code)]
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Material {
     // albedo is a latin word
     // it is a measure for the amount of light reflexion
     Lambertian { albedo: Color },
-    Metal { albedo: Color, fuzzyness: f64 },
+    // diffuse like lambertian but the albedo varies over the surface
+    Textured { texture: Texture },
+    // the reflected color follows the Fresnel equations: it is f0 at normal
+    // incidence and approaches white at grazing angles, f0 defaults to the albedo
+    Metal {
+        albedo: Color,
+        fuzzyness: f64,
+        #[cfg_attr(feature = "serde", serde(default))]
+        f0: Option<Color>,
+    },
+    // brushed metal: the fuzz is an ellipsoid with semi-axis roughness_u along the
+    // tangent (projected onto the surface) and roughness_v along the bitangent
+    // normal x tangent, i.e. highlights are stretched along one surface axis
+    AnisotropicMetal {
+        albedo: Color,
+        roughness_u: f64,
+        roughness_v: f64,
+        tangent: Vec3,
+    },
     // glass, diamond etc
-    Dialectric { refraction_index: f64 },
+    // light travelling a distance d inside the medium keeps the fraction
+    // exp(-sigma * d) of each channel (Beer–Lambert). Rather than the absorption
+    // coefficients sigma per channel the color attenuation left after 1 / absorption
+    // units is given, i.e. sigma = -absorption * ln(attenuation) and the fraction
+    // kept is attenuation^(absorption * d). absorption = 0 gives clear glass
+    Dialectric {
+        refraction_index: f64,
+        attenuation: Color,
+        absorption: f64,
+    },
+    // area light, emits light but does not scatter any
+    DiffuseLight { emit: Color },
+    // phase function of participating media (smoke, fog), scatters
+    // into a uniformly random direction
+    Isotropic { albedo: Color },
+    // debug view of the geometry: emits the normal mapped from [-1, 1] to the
+    // color range [0, 1] and scatters no light
+    NormalDebug,
+    // each scatter picks a with probability ratio and b otherwise, e.g. glossy
+    // plastic mixes a Lambertian with a Metal. Emission and diffuse albedo are
+    // the ratio weighted means of both
+    Mix {
+        a: Box<Material>,
+        b: Box<Material>,
+        ratio: f64,
+    },
+    // normal mapping: base scatters off the normal tilted by strength times the
+    // tangential part of the perlin noise gradient at the hit, which adds bumps
+    // without any geometry. strength = 0 is just the base material
+    BumpWrap { base: Box<Material>, strength: f64 },
 }
 
-fn reflect(v: &Vec3, normal: &Vec3) -> Vec3 {
-    return *v - 2.0 * v.dot(normal) * *normal;
+// diffuse reflection: scatter into a cosine weighted random direction around
+// the normal. The lambertian brdf albedo / pi times cos(theta) divided by the
+// sampling pdf cos(theta) / pi is just the albedo, which hence stays the
+// attenuation of the scattered ray
+fn diffuse_scatter(reflection_point: &Vec3, reflection_normal: &Vec3) -> Ray {
+    Ray {
+        origin: *reflection_point,
+        direction: rand_cosine_direction(reflection_normal),
+    }
 }
 
-fn refract(v: &Vec3, normal: &Vec3, refraction_ratio: f64) -> Vec3 {
-    let cos_theta_1 = (-v.dot(normal)).min(1.0);
-    let cos_theta_2 = (1.0
-        - refraction_ratio * refraction_ratio * (1.0 - cos_theta_1 * cos_theta_1))
-        .max(0.0)
-        .sqrt();
-    return refraction_ratio * *v + (refraction_ratio * cos_theta_1 - cos_theta_2) * *normal;
+impl Material {
+    // albedo at the collision point of diffuse materials, None for all others
+    pub fn diffuse_albedo(&self, collision: &Collision) -> Option<Color> {
+        match self {
+            Material::Lambertian { albedo } => Some(*albedo),
+            Material::Textured { texture } => {
+                Some(texture.value(collision.u, collision.v, &collision.pos))
+            }
+            Material::BumpWrap { base, .. } => base.diffuse_albedo(collision),
+            Material::Mix { a, b, ratio } => {
+                match (a.diffuse_albedo(collision), b.diffuse_albedo(collision)) {
+                    (None, None) => None,
+                    (albedo_a, albedo_b) => Some(
+                        *ratio * albedo_a.unwrap_or(vec3!())
+                            + (1.0 - ratio) * albedo_b.unwrap_or(vec3!()),
+                    ),
+                }
+            }
+            _ => None,
+        }
+    }
 }
 
+// normal tilted away from the gradient of the shared perlin noise at point, the
+// result is a unit vector on the same side of the surface as normal
+pub fn bump_normal(normal: &Vec3, point: &Vec3, strength: f64) -> Vec3 {
+    let gradient = shared_perlin().gradient(point);
+    let tangential = gradient - gradient.dot(normal) * *normal;
+    (*normal - strength * tangential).to_unit_vec_unchecked()
+}
+
+// Schlick approximation of the Fresnel reflectance, r0 being the reflectance at normal incidence
+fn schlick(cos_theta: f64, r0: f64) -> f64 {
+    r0 + (1.0 - r0) * (1.0 - cos_theta).powi(5)
+}
+
+// Schlick approximation for each color channel of the reflectance f0 at normal incidence
+fn schlick_color(cos_theta: f64, f0: &Color) -> Color {
+    vec3!(
+        schlick(cos_theta, f0.x),
+        schlick(cos_theta, f0.y),
+        schlick(cos_theta, f0.z)
+    )
+}
+
+// unit tangent and bitangent of the surface with the (unit) normal, the tangent is
+// the given direction projected onto the surface. Directions parallel to the
+// normal fall back to the tangent of the normal's orthonormal basis
+fn surface_axes(normal: &Vec3, tangent: &Vec3) -> (Vec3, Vec3) {
+    let projected = *tangent - tangent.dot(normal) * *normal;
+    let tangent = projected
+        .to_unit_vec()
+        .unwrap_or_else(|| normal.build_onb().0);
+    (tangent, normal.cross(&tangent))
+}
+
+// scatters the mirror reflection of input_ray perturbed by fuzz, like a fuzzy metal.
+// Perturbations pointing into the surface are mirrored, rays still leaving below
+// the surface are absorbed
+fn fuzzy_reflection(
+    input_ray: &Ray,
+    collision: &Collision,
+    f0: &Color,
+    fuzz: Vec3,
+) -> Option<(Ray, Color)> {
+    let reflection_normal = &collision.normal;
+    // normalized input direction =: v
+    let v = input_ray.direction.to_unit_vec_unchecked();
+    let reflection = v.reflect(reflection_normal);
+
+    let cos_theta = (-v.dot(reflection_normal)).clamp(0.0, 1.0);
+    let fresnel = schlick_color(cos_theta, f0);
+
+    let scatter_direction = if reflection_normal.dot(&fuzz) > 0.0 {
+        // fuzz points from collision outwards
+        reflection + fuzz
+    } else {
+        // fuzz points from collision inwards, hence we subtract to point outwards
+        reflection - fuzz
+    };
+
+    let scattered_ray = Ray {
+        origin: collision.pos,
+        direction: scatter_direction,
+    };
+    if scattered_ray.direction.dot(reflection_normal) > 0.0 {
+        return Some((scattered_ray, fresnel));
+    }
+    None
+}
+
+// rays closer than this to the critical angle are totally reflected, otherwise
+// rounding decides between a refraction grazing the surface and a reflection
+const CRITICAL_ANGLE_EPSILON: f64 = 1e-9;
+
+// total internal reflection: snell's law sin(theta_2) = refraction_ratio * sin(theta_1)
+// has no solution, cos_theta being that of the incidence angle theta_1
+fn cannot_refract(cos_theta: f64, refraction_ratio: f64) -> bool {
+    // 1 = cos(theta)^2 + sin(theta)^2 iff sin(theta) = sqrt(1-cos(theta)^2)
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    refraction_ratio * sin_theta >= 1.0 - CRITICAL_ANGLE_EPSILON
+}
+
+// Fresnel reflectance of a dielectric boundary, refraction_ratio = n_1 / n_2.
+// Ratios which are no physical ones (not positive) reflect everything
 pub fn reflectance(cos_theta: f64, refraction_ratio: f64) -> f64 {
-    // Schlick approximation
-    assert_ne!(refraction_ratio, -1.0);
+    if refraction_ratio.is_nan() || refraction_ratio <= 0.0 {
+        return 1.0;
+    }
     let mut r0 = (1.0 - refraction_ratio) / (1.0 + refraction_ratio);
     r0 *= r0;
-    return r0 + (1.0 - r0) * (1.0 - cos_theta).powi(5);
+    schlick(cos_theta.clamp(0.0, 1.0), r0)
+}
+
+// approximate linear color of a black body at the given temperature, e.g. as the
+// emit of a DiffuseLight: candle light 1900K, daylight 6500K. Temperatures are
+// clamped to [1000K, 12000K]. The fit of the planckian locus by Tanner Helland
+// yields display colors which are converted back to linear ones
+pub fn blackbody_color(kelvin: f64) -> Color {
+    let t = kelvin.clamp(1000.0, 12000.0) / 100.0;
+    let red = if t <= 66.0 {
+        255.0
+    } else {
+        329.698727446 * (t - 60.0).powf(-0.1332047592)
+    };
+    let green = if t <= 66.0 {
+        99.4708025861 * t.ln() - 161.1195681661
+    } else {
+        288.1221695283 * (t - 60.0).powf(-0.0755148492)
+    };
+    let blue = if t >= 66.0 {
+        255.0
+    } else if t <= 19.0 {
+        0.0
+    } else {
+        138.5177312231 * (t - 10.0).ln() - 305.0447927307
+    };
+    let to_linear = |value: f64| (value / 255.0).clamp(0.0, 1.0).powf(DEFAULT_GAMMA);
+    vec3!(to_linear(red), to_linear(green), to_linear(blue))
+}
+
+// refraction index of a medium, None (or a material which is not dielectric) stands
+// for the ambient medium of index ambient_ior
+fn medium_refraction_index(medium: Option<&Material>, ambient_ior: f64) -> f64 {
+    match medium {
+        Some(Material::Dialectric {
+            refraction_index, ..
+        }) => *refraction_index,
+        _ => ambient_ior,
+    }
 }
 
 pub trait Reflectable {
+    // collision is the hit of input_ray with the surface of self, source_material the
+    // medium on the outer side of that surface: the one input_ray travelled through
+    // when entering the body, the one it passes into when leaving it. None is the
+    // ambient medium of index ambient_ior
     fn scatter(
         &self,
         input_ray: &Ray,
-        reflection_point: &Vec3,
-        reflection_normal: &Vec3,
-        ray_is_inside: bool,
-        source_material: &Material,
+        collision: &Collision,
+        source_material: Option<&Material>,
+        ambient_ior: f64,
     ) -> Option<(Ray, Color)>;
+
+    // light emitted by the surface itself at the collision point, black for
+    // non-emissive surfaces
+    fn emitted(&self, _collision: &Collision) -> Color {
+        vec3!(0.0, 0.0, 0.0)
+    }
 }
 
 impl Reflectable for Material {
     fn scatter(
         &self,
         input_ray: &Ray,
-        reflection_point: &Vec3,
-        reflection_normal: &Vec3,
-        ray_is_inside: bool,
-        source_material: &Material,
+        collision: &Collision,
+        source_material: Option<&Material>,
+        ambient_ior: f64,
     ) -> Option<(Ray, Color)> {
+        let reflection_point = &collision.pos;
+        let reflection_normal = &collision.normal;
+        let ray_is_inside = collision.ray_is_inside;
         match self {
-            Material::Lambertian { albedo } => {
+            Material::Lambertian { albedo } => Some((
+                diffuse_scatter(reflection_point, reflection_normal),
+                *albedo,
+            )),
+            Material::Textured { texture } => Some((
+                diffuse_scatter(reflection_point, reflection_normal),
+                texture.value(collision.u, collision.v, reflection_point),
+            )),
+            Material::Metal {
+                albedo,
+                fuzzyness,
+                f0,
+            } => {
+                let fuzzy_random_unit_vec: Vec3 = fuzzyness.min(1.0) * rand_unit_vec();
+                fuzzy_reflection(
+                    input_ray,
+                    collision,
+                    &f0.unwrap_or(*albedo),
+                    fuzzy_random_unit_vec,
+                )
+            }
+            Material::AnisotropicMetal {
+                albedo,
+                roughness_u,
+                roughness_v,
+                tangent,
+            } => {
+                let (tangent, bitangent) = surface_axes(reflection_normal, tangent);
                 let random_unit_vec = rand_unit_vec();
-                let mut scatter_direction = if reflection_normal.dot(&random_unit_vec) > 0.0 {
-                    // unit vec points from collision outwards
-                    *reflection_normal + random_unit_vec
-                } else {
-                    // unit vec points from collision inwards, hence we subtract to point outwards
-                    *reflection_normal - random_unit_vec
-                };
-                if scatter_direction.almost_zero() {
-                    scatter_direction = *reflection_normal;
-                }
-                let scattered_ray = Ray {
-                    origin: *reflection_point,
-                    direction: scatter_direction,
-                };
-                return Some((scattered_ray, albedo.clone()));
+                // the axis along the normal takes the mean roughness
+                let fuzz = roughness_u.min(1.0) * random_unit_vec.dot(&tangent) * tangent
+                    + roughness_v.min(1.0) * random_unit_vec.dot(&bitangent) * bitangent
+                    + 0.5
+                        * (roughness_u.min(1.0) + roughness_v.min(1.0))
+                        * random_unit_vec.dot(reflection_normal)
+                        * *reflection_normal;
+                fuzzy_reflection(input_ray, collision, albedo, fuzz)
             }
-            Material::Metal { albedo, fuzzyness } => {
-                // normalized input direction =: v
-                let v = input_ray.direction.to_unit_vec();
-                let reflection = reflect(&v, &reflection_normal);
-
-                let fuzzy_random_unit_vec: Vec3 = fuzzyness.min(1.0) * rand_unit_vec();
-                let scatter_direction = if reflection_normal.dot(&fuzzy_random_unit_vec) > 0.0 {
-                    // unit vec points from collision outwards
-                    reflection + fuzzy_random_unit_vec
+            Material::Dialectric {
+                refraction_index,
+                attenuation,
+                absorption,
+            } => {
+                let attenuation: Color = if ray_is_inside {
+                    // the ray travelled from the previous hit through the medium,
+                    // which is approximately the distance to this exit hit
+                    let distance = collision.t * input_ray.direction.norm();
+                    let transmittance = |channel: f64| {
+                        if *absorption == 0.0 {
+                            return 1.0;
+                        }
+                        // a black channel absorbs everything instead of yielding NaN
+                        let sigma = -absorption * channel.clamp(f64::MIN_POSITIVE, 1.0).ln();
+                        (-sigma * distance).exp()
+                    };
+                    vec3!(
+                        transmittance(attenuation.x),
+                        transmittance(attenuation.y),
+                        transmittance(attenuation.z)
+                    )
                 } else {
-                    // unit vec points from collision inwards, hence we subtract to point outwards
-                    reflection - fuzzy_random_unit_vec
-                };
-
-                let scattered_ray = Ray {
-                    origin: *reflection_point,
-                    direction: scatter_direction,
+                    vec3!(1.0, 1.0, 1.0)
                 };
-                if scattered_ray.direction.dot(&reflection_normal) > 0.0 {
-                    return Some((scattered_ray, albedo.clone()));
-                }
-                return None;
-            }
-            Material::Dialectric { refraction_index } => {
-                let attenuation: Color = vec3!(1.0, 1.0, 1.0);
-
-                let source_refraction = VACUUM_REFRACTION;
 
+                // leaving the body the ray passes into the medium of source_material,
+                // entering it the ray comes from there
+                let outer_refraction_index = medium_refraction_index(source_material, ambient_ior);
                 let refraction_ratio = if ray_is_inside {
-                    *refraction_index / source_refraction
+                    *refraction_index / outer_refraction_index
                 } else {
-                    source_refraction / refraction_index
+                    outer_refraction_index / refraction_index
                 };
 
-                let unit_direction = input_ray.direction.to_unit_vec();
-                let cos_theta = (-unit_direction.dot(&reflection_normal)).min(1.0);
-                // 1 = cos(theta)^2 + sin(theta)^2 iff sin(theta) = sqrt(1-cos(theta)^2)
-                let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
-
-                let cannot_refract = refraction_ratio * sin_theta > 1.0;
-                let reflection_coefficient = reflectance(cos_theta, refraction_ratio);
+                let unit_direction = input_ray.direction.to_unit_vec_unchecked();
+                // the normal faces the ray, rounding may still push the cosine out of [0, 1]
+                let cos_theta = (-unit_direction.dot(reflection_normal)).clamp(0.0, 1.0);
 
-                let should_reflect = cannot_refract || reflection_coefficient > rand_f64(0.0, 1.0);
-                // let should_reflect = cannot_refract;
+                let should_reflect = cannot_refract(cos_theta, refraction_ratio)
+                    || reflectance(cos_theta, refraction_ratio) > rand_f64(0.0, 1.0);
 
                 let direction = if should_reflect {
-                    reflect(&unit_direction, reflection_normal)
+                    unit_direction.reflect(reflection_normal)
                 } else {
-                    refract(&unit_direction, reflection_normal, refraction_ratio)
+                    unit_direction.refract(reflection_normal, refraction_ratio)
                 };
                 Some((
                     Ray {
@@ -141,6 +365,37 @@ impl Reflectable for Material {
                     attenuation,
                 ))
             }
+            Material::DiffuseLight { .. } | Material::NormalDebug => None,
+            Material::Mix { a, b, ratio } => {
+                let material = if rand_f64(0.0, 1.0) < *ratio { a } else { b };
+                material.scatter(input_ray, collision, source_material, ambient_ior)
+            }
+            Material::BumpWrap { base, strength } => {
+                let bumped = Collision {
+                    normal: bump_normal(reflection_normal, reflection_point, *strength),
+                    ..collision.clone()
+                };
+                base.scatter(input_ray, &bumped, source_material, ambient_ior)
+            }
+            Material::Isotropic { albedo } => Some((
+                Ray {
+                    origin: *reflection_point,
+                    direction: rand_unit_vec(),
+                },
+                *albedo,
+            )),
+        }
+    }
+
+    fn emitted(&self, collision: &Collision) -> Color {
+        match self {
+            Material::DiffuseLight { emit } => *emit,
+            Material::NormalDebug => 0.5 * (collision.normal + vec3!(1.0, 1.0, 1.0)),
+            Material::Mix { a, b, ratio } => {
+                *ratio * a.emitted(collision) + (1.0 - ratio) * b.emitted(collision)
+            }
+            Material::BumpWrap { base, .. } => base.emitted(collision),
+            _ => vec3!(0.0, 0.0, 0.0),
         }
     }
 }
@@ -164,7 +419,7 @@ macro_rules! test_dialectric_refraction_angle {
                 }
 
 
-                let refracted = refract(&v, &n, 1.0/refraction_index);
+                let refracted = v.refract(&n, 1.0/refraction_index);
                 let actual = (refracted.dot(&(-n)).min(1.0).acos() / refracted.norm()).to_degrees();
                 assert!((actual - expected).abs() < 1e-3, "Actual = {:?} != {:?} = Expected", actual, expected);
             }
@@ -177,3 +432,545 @@ test_dialectric_refraction_angle! {
     glass_to_vacuum: (25f64.to_radians(), 1.0/WINDOW_GLASS_REFRACTION, 39.9695),
     vacuum_to_water: (27f64.to_radians(), WATER_20_CELSIUS_REFRACTION, 19.9121),
 }
+
+#[test]
+fn test_reflectance_handles_degenerate_input() {
+    assert_eq!(reflectance(0.5, -1.0), 1.0);
+    assert_eq!(reflectance(0.5, 0.0), 1.0);
+    assert_eq!(reflectance(0.5, f64::NAN), 1.0);
+    // cosines beyond [0, 1] are clamped
+    assert_eq!(reflectance(1.5, 1.5), reflectance(1.0, 1.5));
+    assert_eq!(reflectance(-0.5, 1.5), 1.0);
+    // without a change of the medium nothing is reflected at normal incidence
+    assert_eq!(reflectance(1.0, 1.0), 0.0);
+}
+
+#[test]
+fn test_dialectric_total_internal_reflection_beyond_critical_angle() {
+    let material = Material::Dialectric {
+        refraction_index: WINDOW_GLASS_REFRACTION,
+        attenuation: vec3!(1.0, 1.0, 1.0),
+        absorption: 0.0,
+    };
+    let critical_angle = (1.0 / WINDOW_GLASS_REFRACTION).asin();
+    // normal faces the ray travelling inside the glass
+    let normal = vec3!(0.0, -1.0, 0.0);
+    // fraction of the rays hitting the glass surface from inside at theta which are refracted
+    let refracted_fraction = |theta: f64| {
+        let input_ray = Ray {
+            origin: vec3!(0.0, 0.0, 0.0),
+            direction: vec3!(theta.sin(), theta.cos(), 0.0),
+        };
+        let collision = Collision {
+            pos: vec3!(theta.tan(), 1.0, 0.0),
+            normal,
+            ray_is_inside: true,
+            t: 1.0,
+            u: 0.0,
+            v: 0.0,
+            material: &material,
+        };
+        let sample_count = 200;
+        let refracted = (0..sample_count)
+            .filter(|_| {
+                let (scattered, _) = material
+                    .scatter(&input_ray, &collision, None, VACUUM_REFRACTION)
+                    .unwrap();
+                assert!(scattered.direction.x.is_finite() && scattered.direction.y.is_finite());
+                scattered.direction.dot(&normal) < 0.0
+            })
+            .count();
+        refracted as f64 / sample_count as f64
+    };
+
+    crate::rng::seed_thread_rng(3);
+    for degrees in [0.0, 10.0, 20.0, 30.0, 35.0, 40.0] {
+        let theta: f64 = f64::to_radians(degrees);
+        assert!(theta < critical_angle);
+        assert!(refracted_fraction(theta) > 0.5, "{} degrees", degrees);
+    }
+    // exactly at and beyond the critical angle of about 41.1 degrees every ray is reflected
+    assert_eq!(refracted_fraction(critical_angle), 0.0);
+    for degrees in [41.2, 45.0, 60.0, 80.0, 89.9, 90.0] {
+        let theta: f64 = f64::to_radians(degrees);
+        assert!(theta > critical_angle);
+        assert_eq!(refracted_fraction(theta), 0.0, "{} degrees", degrees);
+    }
+}
+
+#[test]
+fn test_textured_material_scatters_texture_color() {
+    let even = vec3!(1.0, 1.0, 1.0);
+    let odd = vec3!(0.2, 0.3, 0.1);
+    let material = Material::Textured {
+        texture: Texture::Checker {
+            even,
+            odd,
+            scale: std::f64::consts::PI,
+        },
+    };
+    let input_ray = Ray {
+        origin: vec3!(0.5, 2.0, 0.5),
+        direction: vec3!(0.0, -1.0, 0.0),
+    };
+    let normal = vec3!(0.0, 1.0, 0.0);
+
+    let collision_at = |pos| Collision {
+        pos,
+        normal,
+        ray_is_inside: false,
+        t: 1.5,
+        u: 0.0,
+        v: 0.0,
+        material: &material,
+    };
+
+    let (_, even_color) = material
+        .scatter(
+            &input_ray,
+            &collision_at(vec3!(0.5, 0.5, 0.5)),
+            None,
+            VACUUM_REFRACTION,
+        )
+        .unwrap();
+    let (_, odd_color) = material
+        .scatter(
+            &input_ray,
+            &collision_at(vec3!(1.5, 0.5, 0.5)),
+            None,
+            VACUUM_REFRACTION,
+        )
+        .unwrap();
+
+    assert_eq!(even_color, even);
+    assert_eq!(odd_color, odd);
+}
+
+#[cfg(test)]
+use crate::shape::{Collidable, Sphere};
+
+#[test]
+fn test_thick_colored_glass_attenuates_more_than_thin_glass() {
+    let material = Material::Dialectric {
+        refraction_index: WINDOW_GLASS_REFRACTION,
+        attenuation: vec3!(0.9, 0.5, 0.2),
+        absorption: 1.0,
+    };
+    // color of a ray crossing the sphere along its diameter
+    let transmitted_color = |radius: f64| {
+        let sphere = Sphere {
+            center: vec3!(0.0, 0.0, -5.0),
+            radius,
+            material: material.clone(),
+        };
+        let ray = Ray {
+            origin: vec3!(0.0, 0.0, 0.0),
+            direction: vec3!(0.0, 0.0, -1.0),
+        };
+        let entry = sphere.collide(&ray, 0.001, f64::MAX).unwrap();
+        assert!(!entry.ray_is_inside);
+        let (_, entry_color) = material
+            .scatter(&ray, &entry, None, VACUUM_REFRACTION)
+            .unwrap();
+        assert_eq!(entry_color, vec3!(1.0, 1.0, 1.0));
+
+        let inner_ray = Ray {
+            origin: entry.pos,
+            direction: vec3!(0.0, 0.0, -1.0),
+        };
+        let exit = sphere.collide(&inner_ray, 0.001, f64::MAX).unwrap();
+        assert!(exit.ray_is_inside);
+        let (_, exit_color) = material
+            .scatter(&inner_ray, &exit, None, VACUUM_REFRACTION)
+            .unwrap();
+        exit_color
+    };
+
+    let thin = transmitted_color(0.1);
+    let thick = transmitted_color(1.0);
+    // after the diameter d = 2 the ray keeps attenuation^2
+    assert!(
+        (thick - vec3!(0.81, 0.25, 0.04)).norm() < 1e-9,
+        "{:?}",
+        thick
+    );
+    assert!(thick.x < thin.x && thick.y < thin.y && thick.z < thin.z);
+    assert!(thin.x < 1.0 && thin.y < 1.0 && thin.z < 1.0);
+}
+
+#[test]
+fn test_glass_with_black_channel_absorbs_it_fully() {
+    let material = Material::Dialectric {
+        refraction_index: WINDOW_GLASS_REFRACTION,
+        attenuation: vec3!(1.0, 0.0, -0.5),
+        absorption: 1.0,
+    };
+    let sphere = Sphere {
+        center: vec3!(0.0, 0.0, -5.0),
+        radius: 1.0,
+        material: material.clone(),
+    };
+    // from the center to the exit
+    let ray = Ray {
+        origin: vec3!(0.0, 0.0, -5.0),
+        direction: vec3!(0.0, 0.0, -1.0),
+    };
+    let exit = sphere.collide(&ray, 0.001, f64::MAX).unwrap();
+    let (_, exit_color) = material
+        .scatter(&ray, &exit, None, VACUUM_REFRACTION)
+        .unwrap();
+    assert!(
+        (exit_color - vec3!(1.0, 0.0, 0.0)).norm() < 1e-9,
+        "{:?}",
+        exit_color
+    );
+}
+
+#[test]
+fn test_dialectric_refracts_relative_to_ambient_medium() {
+    let material = Material::Dialectric {
+        refraction_index: WINDOW_GLASS_REFRACTION,
+        attenuation: vec3!(1.0, 1.0, 1.0),
+        absorption: 0.0,
+    };
+    let sphere = Sphere {
+        center: vec3!(0.0, 0.0, -5.0),
+        radius: 1.0,
+        material: material.clone(),
+    };
+    // hits the sphere at an incidence angle of 30 degrees
+    let ray = Ray {
+        origin: vec3!(0.5, 0.0, 0.0),
+        direction: vec3!(0.0, 0.0, -1.0),
+    };
+    let collision = sphere.collide(&ray, 0.001, f64::MAX).unwrap();
+    let sin_theta_1 = 0.5;
+
+    // sin(theta_2) of the refracted ray, reflections are skipped by reseeding
+    let sin_theta_2 = |ambient_ior: f64| {
+        for seed in 0.. {
+            crate::rng::seed_thread_rng(seed);
+            let (scattered, _) = material
+                .scatter(&ray, &collision, None, ambient_ior)
+                .unwrap();
+            let direction = scattered.direction.to_unit_vec_unchecked();
+            if direction.dot(&collision.normal) < 0.0 {
+                return direction.cross(&collision.normal).norm();
+            }
+        }
+        unreachable!()
+    };
+
+    let in_vacuum = sin_theta_2(VACUUM_REFRACTION);
+    let in_water = sin_theta_2(WATER_20_CELSIUS_REFRACTION);
+    // snell: n_1 * sin(theta_1) = n_2 * sin(theta_2)
+    let expected_vacuum = VACUUM_REFRACTION / WINDOW_GLASS_REFRACTION * sin_theta_1;
+    let expected_water = WATER_20_CELSIUS_REFRACTION / WINDOW_GLASS_REFRACTION * sin_theta_1;
+    assert!(
+        (in_vacuum - expected_vacuum).abs() < 1e-9,
+        "{:?}",
+        in_vacuum
+    );
+    assert!((in_water - expected_water).abs() < 1e-9, "{:?}", in_water);
+    // glass in water bends the ray less than glass in vacuum
+    assert!(in_water > in_vacuum + 0.05);
+}
+
+#[test]
+fn test_dialectric_refracts_relative_to_source_material() {
+    let glass = Material::Dialectric {
+        refraction_index: WINDOW_GLASS_REFRACTION,
+        attenuation: vec3!(1.0, 1.0, 1.0),
+        absorption: 0.0,
+    };
+    let water = Material::Dialectric {
+        refraction_index: WATER_20_CELSIUS_REFRACTION,
+        attenuation: vec3!(1.0, 1.0, 1.0),
+        absorption: 0.0,
+    };
+    // a ray travelling through water hits the glass at an incidence angle of 30 degrees
+    let sphere = Sphere {
+        center: vec3!(0.0, 0.0, -5.0),
+        radius: 1.0,
+        material: glass.clone(),
+    };
+    let ray = Ray {
+        origin: vec3!(0.5, 0.0, 0.0),
+        direction: vec3!(0.0, 0.0, -1.0),
+    };
+    let collision = sphere.collide(&ray, 0.001, f64::MAX).unwrap();
+    let sin_theta_1 = 0.5;
+
+    let sin_theta_2 = |source_material: Option<&Material>| {
+        for seed in 0.. {
+            crate::rng::seed_thread_rng(seed);
+            let (scattered, _) = glass
+                .scatter(&ray, &collision, source_material, VACUUM_REFRACTION)
+                .unwrap();
+            let direction = scattered.direction.to_unit_vec_unchecked();
+            if direction.dot(&collision.normal) < 0.0 {
+                return direction.cross(&collision.normal).norm();
+            }
+        }
+        unreachable!()
+    };
+
+    let from_water = sin_theta_2(Some(&water));
+    let expected = WATER_20_CELSIUS_REFRACTION / WINDOW_GLASS_REFRACTION * sin_theta_1;
+    assert!((from_water - expected).abs() < 1e-9, "{:?}", from_water);
+    // None stands for the ambient vacuum
+    let from_vacuum = sin_theta_2(None);
+    let expected = VACUUM_REFRACTION / WINDOW_GLASS_REFRACTION * sin_theta_1;
+    assert!((from_vacuum - expected).abs() < 1e-9, "{:?}", from_vacuum);
+    // coming from a body of the same glass the ray does not refract at all
+    let from_glass = sin_theta_2(Some(&glass));
+    assert!((from_glass - sin_theta_1).abs() < 1e-9, "{:?}", from_glass);
+}
+
+#[test]
+fn test_dialectric_leaves_into_source_material() {
+    let glass = Material::Dialectric {
+        refraction_index: WINDOW_GLASS_REFRACTION,
+        attenuation: vec3!(1.0, 1.0, 1.0),
+        absorption: 0.0,
+    };
+    let water = Material::Dialectric {
+        refraction_index: WATER_20_CELSIUS_REFRACTION,
+        attenuation: vec3!(1.0, 1.0, 1.0),
+        absorption: 0.0,
+    };
+    // a ray inside the glass hits its surface at an incidence angle of 30 degrees
+    let sphere = Sphere {
+        center: vec3!(0.0, 0.0, -5.0),
+        radius: 1.0,
+        material: glass.clone(),
+    };
+    let ray = Ray {
+        origin: vec3!(0.5, 0.0, -5.0),
+        direction: vec3!(0.0, 0.0, 1.0),
+    };
+    let collision = sphere.collide(&ray, 0.001, f64::MAX).unwrap();
+    assert!(collision.ray_is_inside);
+
+    let sin_theta_2 = |source_material: Option<&Material>| {
+        for seed in 0.. {
+            crate::rng::seed_thread_rng(seed);
+            let (scattered, _) = glass
+                .scatter(&ray, &collision, source_material, VACUUM_REFRACTION)
+                .unwrap();
+            let direction = scattered.direction.to_unit_vec_unchecked();
+            if direction.dot(&collision.normal) < 0.0 {
+                return direction.cross(&collision.normal).norm();
+            }
+        }
+        unreachable!()
+    };
+
+    let into_water = sin_theta_2(Some(&water));
+    let expected = WINDOW_GLASS_REFRACTION / WATER_20_CELSIUS_REFRACTION * 0.5;
+    assert!((into_water - expected).abs() < 1e-9, "{:?}", into_water);
+    let into_vacuum = sin_theta_2(None);
+    let expected = WINDOW_GLASS_REFRACTION / VACUUM_REFRACTION * 0.5;
+    assert!((into_vacuum - expected).abs() < 1e-9, "{:?}", into_vacuum);
+}
+
+#[test]
+fn test_metal_reflects_more_at_grazing_angles() {
+    let material = Material::Metal {
+        albedo: vec3!(0.8, 0.6, 0.2),
+        fuzzyness: 0.0,
+        f0: None,
+    };
+    let normal = vec3!(0.0, 1.0, 0.0);
+    let collision = Collision {
+        pos: vec3!(),
+        normal,
+        ray_is_inside: false,
+        t: 1.0,
+        u: 0.0,
+        v: 0.0,
+        material: &material,
+    };
+    let reflected_color = |direction: Vec3| {
+        let input_ray = Ray {
+            origin: vec3!() - direction,
+            direction,
+        };
+        let (_, color) = material
+            .scatter(&input_ray, &collision, None, VACUUM_REFRACTION)
+            .unwrap();
+        color
+    };
+
+    let normal_incidence = reflected_color(vec3!(0.0, -1.0, 0.0));
+    let grazing = reflected_color(vec3!(1.0, -0.01, 0.0));
+    assert_eq!(normal_incidence, vec3!(0.8, 0.6, 0.2));
+    assert!(grazing.x > normal_incidence.x, "{:?}", grazing);
+    assert!(grazing.y > normal_incidence.y, "{:?}", grazing);
+    assert!(grazing.z > normal_incidence.z, "{:?}", grazing);
+    assert!(
+        (grazing - vec3!(1.0, 1.0, 1.0)).norm() < 0.1,
+        "{:?}",
+        grazing
+    );
+
+    // an explicit f0 replaces the albedo
+    let material = Material::Metal {
+        albedo: vec3!(0.8, 0.6, 0.2),
+        fuzzyness: 0.0,
+        f0: Some(vec3!(0.9, 0.9, 0.9)),
+    };
+    let input_ray = Ray {
+        origin: vec3!(0.0, 1.0, 0.0),
+        direction: vec3!(0.0, -1.0, 0.0),
+    };
+    let (_, color) = material
+        .scatter(&input_ray, &collision, None, VACUUM_REFRACTION)
+        .unwrap();
+    assert_eq!(color, vec3!(0.9, 0.9, 0.9));
+}
+
+#[test]
+fn test_blackbody_color() {
+    let daylight = blackbody_color(6500.0);
+    assert!(
+        (daylight - vec3!(1.0, 1.0, 1.0)).norm() < 0.05,
+        "{:?}",
+        daylight
+    );
+
+    let warm = blackbody_color(2000.0);
+    assert_eq!(warm.x, 1.0);
+    assert!(warm.x > warm.y && warm.y > warm.z, "{:?}", warm);
+
+    let cold = blackbody_color(12000.0);
+    assert!(cold.z > cold.x, "{:?}", cold);
+    // out of range temperatures are clamped
+    assert_eq!(blackbody_color(100_000.0), cold);
+    assert_eq!(blackbody_color(0.0), blackbody_color(1000.0));
+}
+
+#[test]
+fn test_mix_scatters_sub_materials_by_ratio() {
+    let diffuse_albedo = vec3!(0.9, 0.1, 0.1);
+    let material = Material::Mix {
+        a: Box::new(Material::Lambertian {
+            albedo: diffuse_albedo,
+        }),
+        b: Box::new(Material::Metal {
+            albedo: vec3!(0.8, 0.8, 0.8),
+            fuzzyness: 0.0,
+            f0: None,
+        }),
+        ratio: 0.3,
+    };
+    let collision = Collision {
+        pos: vec3!(),
+        normal: vec3!(0.0, 1.0, 0.0),
+        ray_is_inside: false,
+        t: 1.0,
+        u: 0.0,
+        v: 0.0,
+        material: &material,
+    };
+    let input_ray = Ray {
+        origin: vec3!(-1.0, 1.0, 0.0),
+        direction: vec3!(1.0, -1.0, 0.0),
+    };
+
+    let scatter_count = 10000;
+    let diffuse_count = (0..scatter_count)
+        .filter(|_| {
+            let (_, color) = material
+                .scatter(&input_ray, &collision, None, VACUUM_REFRACTION)
+                .unwrap();
+            color == diffuse_albedo
+        })
+        .count();
+    let diffuse_fraction = diffuse_count as f64 / scatter_count as f64;
+    assert!(
+        (diffuse_fraction - 0.3).abs() < 0.02,
+        "{:?}",
+        diffuse_fraction
+    );
+
+    // only the lambertian part is lit directly
+    assert_eq!(
+        material.diffuse_albedo(&collision),
+        Some(0.3 * diffuse_albedo)
+    );
+}
+
+#[test]
+fn test_bump_wrap_perturbs_normal() {
+    let normal = vec3!(0.0, 1.0, 0.0);
+    let point = vec3!(0.3, 0.0, 0.7);
+    let bumped = bump_normal(&normal, &point, 0.5);
+    assert!((bumped - normal).norm() > 1e-3, "{:?}", bumped);
+    assert!((bumped.norm() - 1.0).abs() < 1e-12);
+    assert!(bumped.dot(&normal) > 0.0);
+
+    let base = Material::Metal {
+        albedo: vec3!(0.8, 0.8, 0.8),
+        fuzzyness: 0.3,
+        f0: None,
+    };
+    let material = Material::BumpWrap {
+        base: Box::new(base.clone()),
+        strength: 0.0,
+    };
+    let collision = Collision {
+        pos: point,
+        normal,
+        ray_is_inside: false,
+        t: 1.0,
+        u: 0.0,
+        v: 0.0,
+        material: &material,
+    };
+    let input_ray = Ray {
+        origin: vec3!(-1.0, 1.0, 0.0),
+        direction: vec3!(1.0, -1.0, 0.7),
+    };
+    crate::rng::seed_thread_rng(5);
+    let bumped_scatter = material.scatter(&input_ray, &collision, None, VACUUM_REFRACTION);
+    crate::rng::seed_thread_rng(5);
+    let base_scatter = base.scatter(&input_ray, &collision, None, VACUUM_REFRACTION);
+    assert!(base_scatter.is_some());
+    assert_eq!(bumped_scatter, base_scatter);
+}
+
+#[test]
+fn test_anisotropic_metal_spreads_along_tangent() {
+    let material = Material::AnisotropicMetal {
+        albedo: vec3!(0.9, 0.9, 0.9),
+        roughness_u: 0.5,
+        roughness_v: 0.05,
+        // not perpendicular to the normal, only its projection (1, 0, 0) counts
+        tangent: vec3!(1.0, 1.0, 0.0),
+    };
+    let collision = Collision {
+        pos: vec3!(),
+        normal: vec3!(0.0, 1.0, 0.0),
+        ray_is_inside: false,
+        t: 1.0,
+        u: 0.0,
+        v: 0.0,
+        material: &material,
+    };
+    let input_ray = Ray {
+        origin: vec3!(0.0, 1.0, 0.0),
+        direction: vec3!(0.0, -1.0, 0.0),
+    };
+
+    let (mut spread_u, mut spread_v) = (0.0, 0.0);
+    for _ in 0..2000 {
+        let (ray, _) = material
+            .scatter(&input_ray, &collision, None, VACUUM_REFRACTION)
+            .unwrap();
+        let direction = ray.direction.to_unit_vec_unchecked();
+        spread_u += direction.x * direction.x;
+        spread_v += direction.z * direction.z;
+    }
+    assert!(spread_u > 10.0 * spread_v, "{:?} {:?}", spread_u, spread_v);
+}
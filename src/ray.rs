@@ -1,6 +1,10 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
 use crate::vec::Vec3;
 
 #[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Ray {
     pub origin: Vec3,
     pub direction: Vec3,
@@ -11,8 +15,8 @@ pub struct Ray {
 macro_rules! ray {
     () => {
         Ray {
-            origin: crate::vec3!(),
-            direction: crate::vec3!(1.0, 0.0, 0.0),
+            origin: $crate::vec3!(),
+            direction: $crate::vec3!(1.0, 0.0, 0.0),
         }
     };
 }
@@ -1,36 +1,128 @@
-use crate::vec::{ZERO, Vec3};
+use std::cell::RefCell;
 
-static mut RNG_STATE: i32 = 44;
-const RNG_A: i32 = 8121;
-const RNG_C: i32 = 28411;
-const RNG_M: i32 = 134456;
+use crate::vec::{Vec3, ZERO};
 
-pub fn rand_f64(t_min: f64, t_max: f64) -> f64 {
-    // unsafe is fine as we don't rely on a deterministic random number
-    debug_assert!(t_min <= t_max);
-    let mut t = 0.0;
-    unsafe {
-        RNG_STATE = (RNG_A * RNG_STATE + RNG_C) % RNG_M;
-        t += (RNG_STATE as f64) / ((RNG_M - 1) as f64);
+const RNG_DEFAULT_SEED: u64 = 44;
+const PCG_MULTIPLIER: u64 = 6364136223846793005;
+const PCG_INCREMENT: u64 = 1442695040888963407;
+
+// PCG-XSH-RR (O'Neill 2014): a 64 bit linear congruential generator with period 2^64
+// whose state is permuted into 32 bit outputs. The same seed always yields the same
+// sequence
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        let mut rng = Rng { state: 0 };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+        rng
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old_state = self.state;
+        self.state = old_state
+            .wrapping_mul(PCG_MULTIPLIER)
+            .wrapping_add(PCG_INCREMENT);
+        let xor_shifted = (((old_state >> 18) ^ old_state) >> 27) as u32;
+        let rotation = (old_state >> 59) as u32;
+        xor_shifted.rotate_right(rotation)
+    }
+
+    pub fn next_f64(&mut self, t_min: f64, t_max: f64) -> f64 {
+        debug_assert!(t_min <= t_max);
+        let t = self.next_u32() as f64 / u32::MAX as f64;
+        (t_max - t_min) * t + t_min
+    }
+
+    pub fn vec(&mut self, min_val: f64, max_val: f64) -> Vec3 {
+        Vec3 {
+            x: self.next_f64(min_val, max_val),
+            y: self.next_f64(min_val, max_val),
+            z: self.next_f64(min_val, max_val),
+        }
     }
-    (t_max - t_min) * t + t_min * t
+
+    // uniformly distributed point of the unit disk in the xy plane, by rejection
+    // sampling of the enclosing square
+    pub fn in_unit_disk(&mut self) -> Vec3 {
+        loop {
+            let p = Vec3 {
+                x: self.next_f64(-1.0, 1.0),
+                y: self.next_f64(-1.0, 1.0),
+                z: 0.0,
+            };
+            if p.norm_squared() < 1.0 {
+                return p;
+            }
+        }
+    }
+
+    // uniformly distributed direction: normalizing points of the cube would
+    // favor its corners, so only points inside the unit ball are accepted
+    pub fn unit_vec(&mut self) -> Vec3 {
+        loop {
+            let v = self.vec(-1.0, 1.0);
+            let norm_squared = v.norm_squared();
+            if norm_squared < 1.0 && v != ZERO {
+                return v.to_unit_vec_unchecked();
+            }
+        }
+    }
+}
+
+thread_local! {
+    // default generator behind the free functions below
+    static THREAD_RNG: RefCell<Rng> = RefCell::new(Rng::new(RNG_DEFAULT_SEED));
+}
+
+// reset the generator of the current thread to make its sequence reproducible
+pub fn seed_thread_rng(seed: u64) {
+    THREAD_RNG.with(|rng| *rng.borrow_mut() = Rng::new(seed));
+}
+
+// seed of the pixel (x, y) derived from base_seed, neighbouring pixels get
+// unrelated seeds (splitmix64 finalizer)
+pub fn pixel_seed(x: usize, y: usize, base_seed: u64) -> u64 {
+    let mut z = base_seed
+        .wrapping_add((x as u64).wrapping_mul(0x9e3779b97f4a7c15))
+        .wrapping_add((y as u64).wrapping_mul(0xc2b2ae3d27d4eb4f));
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+// run f with the generator of the current thread, e.g. to call code taking an Rng
+pub fn with_thread_rng<T>(f: impl FnOnce(&mut Rng) -> T) -> T {
+    THREAD_RNG.with(|rng| f(&mut rng.borrow_mut()))
+}
+
+pub fn rand_f64(t_min: f64, t_max: f64) -> f64 {
+    THREAD_RNG.with(|rng| rng.borrow_mut().next_f64(t_min, t_max))
 }
 
 pub fn rand_vec(min_val: f64, max_val: f64) -> Vec3 {
-    Vec3 {
-        x: rand_f64(min_val, max_val),
-        y: rand_f64(min_val, max_val),
-        z: rand_f64(min_val, max_val),
-    }
+    THREAD_RNG.with(|rng| rng.borrow_mut().vec(min_val, max_val))
 }
 
 pub fn rand_unit_vec() -> Vec3 {
-    loop {
-        let v = rand_vec(-10.0, 10.0);
-        if v != ZERO {
-            return v.to_unit_vec();
-        }
-    }
+    THREAD_RNG.with(|rng| rng.borrow_mut().unit_vec())
+}
+
+pub fn rand_in_unit_disk() -> Vec3 {
+    THREAD_RNG.with(|rng| rng.borrow_mut().in_unit_disk())
+}
+
+// random direction on the hemisphere around the (unit) normal with a density
+// proportional to cos(theta) = normal·direction, i.e. pdf = cos(theta) / pi.
+// Shifting a uniformly distributed point of the unit sphere by the normal
+// yields exactly this distribution
+pub fn rand_cosine_direction(normal: &Vec3) -> Vec3 {
+    (*normal + rand_unit_vec()).to_unit_vec().unwrap_or(*normal)
 }
 
 #[test]
@@ -40,5 +132,190 @@ fn test_rand_unit_vec_has_norm_one() {
     let expected = 1.0;
 
     assert!(f64::abs(actual - expected) < f64::EPSILON);
-    assert_eq!(actual, expected);
+}
+
+#[test]
+fn test_rand_in_unit_disk_fills_the_disk() {
+    // the fraction of uniform points of the unit disk within radius r is r^2
+    let sample_count = 10000;
+    let mut ring_counts = [0usize; 4];
+    for _ in 0..sample_count {
+        let p = rand_in_unit_disk();
+        assert_eq!(p.z, 0.0);
+        let norm = p.norm();
+        assert!(norm < 1.0);
+        ring_counts[(norm * 4.0) as usize] += 1;
+    }
+    for (ring, count) in ring_counts.iter().enumerate() {
+        let (inner, outer) = (ring as f64 / 4.0, (ring + 1) as f64 / 4.0);
+        let expected = (outer * outer - inner * inner) * sample_count as f64;
+        assert!(
+            (*count as f64 - expected).abs() < 0.1 * expected,
+            "ring {:?}: {:?} != {:?}",
+            ring,
+            count,
+            expected
+        );
+    }
+}
+
+#[test]
+fn test_rng_with_same_seed_yields_same_sequence() {
+    let mut rng_1 = Rng::new(42);
+    let mut rng_2 = Rng::new(42);
+    for _ in 0..1000 {
+        assert_eq!(rng_1.next_f64(-1.0, 1.0), rng_2.next_f64(-1.0, 1.0));
+    }
+    assert_eq!(rng_1.unit_vec(), rng_2.unit_vec());
+    assert_eq!(rng_1.vec(0.0, 1.0), rng_2.vec(0.0, 1.0));
+}
+
+#[test]
+fn test_rng_with_different_seed_yields_different_sequence() {
+    let mut rng_1 = Rng::new(42);
+    let mut rng_2 = Rng::new(43);
+    let draws_1: Vec<f64> = (0..10).map(|_| rng_1.next_f64(0.0, 1.0)).collect();
+    let draws_2: Vec<f64> = (0..10).map(|_| rng_2.next_f64(0.0, 1.0)).collect();
+    assert_ne!(draws_1, draws_2);
+}
+
+#[test]
+fn test_rand_f64_spans_whole_range() {
+    let mut rng = Rng::new(7);
+    let samples: Vec<f64> = (0..100_000).map(|_| rng.next_f64(-1.0, 1.0)).collect();
+    let min = samples.iter().cloned().fold(f64::MAX, f64::min);
+    let max = samples.iter().cloned().fold(f64::MIN, f64::max);
+    let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+
+    assert!((-1.0..-0.999).contains(&min), "min = {:?}", min);
+    assert!((0.999..=1.0).contains(&max), "max = {:?}", max);
+    assert!(mean.abs() < 0.01, "mean = {:?}", mean);
+}
+
+#[test]
+fn test_rng_is_uniform_by_chi_squared() {
+    let mut rng = Rng::new(3);
+    let bucket_count = 100;
+    let sample_count = 1_000_000;
+    let mut buckets = vec![0usize; bucket_count];
+    for _ in 0..sample_count {
+        let t = rng.next_f64(0.0, 1.0);
+        buckets[((t * bucket_count as f64) as usize).min(bucket_count - 1)] += 1;
+    }
+    let expected = sample_count as f64 / bucket_count as f64;
+    let chi_squared: f64 = buckets
+        .iter()
+        .map(|count| (*count as f64 - expected).powi(2) / expected)
+        .sum();
+    // 99 degrees of freedom: mean 99, standard deviation 14
+    assert!(chi_squared < 160.0, "chi² = {:?}", chi_squared);
+}
+
+#[test]
+fn test_rng_does_not_repeat_within_a_million_draws() {
+    let mut rng = Rng::new(RNG_DEFAULT_SEED);
+    let mut states = std::collections::HashSet::new();
+    for _ in 0..1_000_000 {
+        assert!(states.insert(rng.state), "state repeated");
+        rng.next_u32();
+    }
+    // the old 17 bit generator repeated its output after at most 134456 draws
+    let mut rng = Rng::new(RNG_DEFAULT_SEED);
+    let first: Vec<u32> = (0..8).map(|_| rng.next_u32()).collect();
+    let mut window: Vec<u32> = first.clone();
+    for _ in 0..1_000_000 {
+        window.remove(0);
+        window.push(rng.next_u32());
+        assert_ne!(window, first);
+    }
+}
+
+#[test]
+fn test_rand_f64_offsets_by_t_min() {
+    let mut rng = Rng::new(7);
+    for _ in 0..1000 {
+        let t = rng.next_f64(2.0, 3.0);
+        assert!((2.0..=3.0).contains(&t), "t = {:?}", t);
+    }
+}
+
+#[test]
+fn test_rand_unit_vec_is_uniform_over_octants() {
+    let mut rng = Rng::new(11);
+    let sample_count = 80_000;
+    let mut octants = [0usize; 8];
+    let mut abs_z_sum = 0.0;
+    for _ in 0..sample_count {
+        let v = rng.unit_vec();
+        abs_z_sum += v.z.abs();
+        let octant = (v.x > 0.0) as usize + 2 * (v.y > 0.0) as usize + 4 * (v.z > 0.0) as usize;
+        octants[octant] += 1;
+    }
+    let expected = sample_count as f64 / 8.0;
+    for count in octants {
+        let deviation = (count as f64 - expected).abs() / expected;
+        assert!(deviation < 0.05, "octants = {:?}", octants);
+    }
+    // on the unit sphere |z| is uniformly distributed in [0, 1] (Archimedes)
+    let abs_z_mean = abs_z_sum / sample_count as f64;
+    assert!(
+        (abs_z_mean - 0.5).abs() < 0.01,
+        "mean |z| = {:?}",
+        abs_z_mean
+    );
+}
+
+#[test]
+fn test_rand_cosine_direction_is_cosine_weighted() {
+    seed_thread_rng(11);
+    let normal = crate::vec3!(0.0, 0.0, 1.0);
+    let sample_count = 100_000;
+    let mut z_sum = 0.0;
+    for _ in 0..sample_count {
+        let direction = rand_cosine_direction(&normal);
+        assert!(direction.z >= 0.0, "{:?}", direction);
+        assert!((direction.norm() - 1.0).abs() < 1e-9);
+        z_sum += direction.z;
+    }
+    // the expected cos(theta) is the integral of cos(theta)^2 / pi over the hemisphere
+    let mean_z = z_sum / sample_count as f64;
+    assert!((mean_z - 2.0 / 3.0).abs() < 0.01, "mean z = {:?}", mean_z);
+}
+
+#[test]
+fn test_seeded_thread_rngs_are_independent_and_reproducible() {
+    let draw = |seed: u64| {
+        seed_thread_rng(seed);
+        (0..1000).map(|_| rand_f64(0.0, 1.0)).collect::<Vec<f64>>()
+    };
+    let threads: Vec<_> = (0..4)
+        .map(|seed| std::thread::spawn(move || draw(seed)))
+        .collect();
+    let draws: Vec<Vec<f64>> = threads
+        .into_iter()
+        .map(|thread| thread.join().unwrap())
+        .collect();
+
+    // every thread yields the same sequence as a single generator with its seed,
+    // regardless of the other threads drawing concurrently
+    for (seed, thread_draws) in draws.iter().enumerate() {
+        let mut rng = Rng::new(seed as u64);
+        let expected: Vec<f64> = (0..1000).map(|_| rng.next_f64(0.0, 1.0)).collect();
+        assert_eq!(thread_draws, &expected);
+    }
+    // and the sequences differ between seeds
+    assert_ne!(draws[1], draws[2]);
+}
+
+#[test]
+fn test_pixel_seeds_differ_between_pixels() {
+    let mut seeds: Vec<u64> = (0..16)
+        .flat_map(|y| (0..16).map(move |x| pixel_seed(x, y, 7)))
+        .collect();
+    assert_eq!(pixel_seed(3, 5, 7), pixel_seed(3, 5, 7));
+    assert_ne!(pixel_seed(3, 5, 7), pixel_seed(5, 3, 7));
+    assert_ne!(pixel_seed(3, 5, 7), pixel_seed(3, 5, 8));
+    seeds.sort();
+    seeds.dedup();
+    assert_eq!(seeds.len(), 16 * 16);
 }
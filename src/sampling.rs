@@ -0,0 +1,182 @@
+use crate::rng::rand_f64;
+
+// where the samples of a pixel are placed within the pixel
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SampleStrategy {
+    // independent random offsets
+    #[default]
+    Random,
+    // split the pixel into a sqrt_samples x sqrt_samples grid and jitter within
+    // every cell. This needs sqrt_samples^2 samples per pixel, further samples
+    // start over at the first cell
+    Stratified {
+        sqrt_samples: usize,
+    },
+    // every sample at the center of the pixel, i.e. no anti-aliasing
+    Uniform,
+    // correlated multi-jittered samples (Kensler 2013): stratified in the
+    // sqrt_samples x sqrt_samples grid and in sqrt_samples^2 rows and columns.
+    // Like Stratified this needs sqrt_samples^2 samples per pixel
+    CorrelatedMultiJitter {
+        sqrt_samples: usize,
+    },
+}
+
+impl SampleStrategy {
+    // offset (u, v) in [0, 1) x [0, 1) of the sample-th sample of a pixel
+    pub fn offset(&self, sample: usize) -> (f64, f64) {
+        self.pattern_offset(sample, 0)
+    }
+
+    // offset of the sample-th sample, the pattern selects one of the
+    // correlated multi-jittered sample sets and is ignored otherwise
+    fn pattern_offset(&self, sample: usize, pattern: u32) -> (f64, f64) {
+        match self {
+            SampleStrategy::Random => (rand_f64(0.0, 0.999), rand_f64(0.0, 0.999)),
+            SampleStrategy::Stratified { sqrt_samples } => {
+                let n = (*sqrt_samples).max(1);
+                let cell = sample % (n * n);
+                let cell_size = 1.0 / n as f64;
+                let u = ((cell % n) as f64 + rand_f64(0.0, 0.999)) * cell_size;
+                let v = ((cell / n) as f64 + rand_f64(0.0, 0.999)) * cell_size;
+                (u, v)
+            }
+            SampleStrategy::Uniform => (0.5, 0.5),
+            SampleStrategy::CorrelatedMultiJitter { sqrt_samples } => {
+                let n = (*sqrt_samples).max(1) as u32;
+                let sample = (sample % (n * n) as usize) as u32;
+                correlated_multi_jitter(sample, n, pattern)
+            }
+        }
+    }
+
+    // offsets of the first samples_per_pixel samples of a pixel, the random
+    // numbers are only drawn while iterating. Every call uses a new
+    // correlated multi-jittered pattern, otherwise all pixels would share one
+    pub fn offsets(self, samples_per_pixel: usize) -> impl Iterator<Item = (f64, f64)> {
+        let pattern = match self {
+            SampleStrategy::CorrelatedMultiJitter { .. } => {
+                (rand_f64(0.0, 1.0) * u32::MAX as f64) as u32
+            }
+            _ => 0,
+        };
+        (0..samples_per_pixel).map(move |sample| self.pattern_offset(sample, pattern))
+    }
+}
+
+// pseudo random permutation of 0..length selected by pattern, i.e. a hash
+// which maps distinct i < length to distinct values
+fn permute(i: u32, length: u32, pattern: u32) -> u32 {
+    // all bits below the highest bit of length - 1, the loop rejects values >= length
+    let mut w = length - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    let mut i = i;
+    loop {
+        i ^= pattern;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= pattern >> 16;
+        i ^= (i & w) >> 4;
+        i ^= pattern >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= pattern >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | pattern >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < length {
+            return i.wrapping_add(pattern) % length;
+        }
+    }
+}
+
+// hash of (i, pattern) in [0, 1)
+fn hash_f64(i: u32, pattern: u32) -> f64 {
+    let mut i = i ^ pattern;
+    i ^= i >> 17;
+    i ^= i >> 10;
+    i = i.wrapping_mul(0xb36534e5);
+    i ^= i >> 12;
+    i ^= i >> 21;
+    i = i.wrapping_mul(0x93fc4795);
+    i ^= 0xdf6e307f;
+    i ^= i >> 17;
+    i = i.wrapping_mul(1 | pattern >> 18);
+    i as f64 / (u32::MAX as f64 + 1.0)
+}
+
+// sample-th of the n^2 samples: the grid cell (sample % n, sample / n) is split into
+// n x n sub cells and the sample is placed in a shuffled sub cell such that every
+// one of the n^2 columns and rows of sub cells holds exactly one sample
+fn correlated_multi_jitter(sample: u32, n: u32, pattern: u32) -> (f64, f64) {
+    let sample = permute(sample, n * n, pattern.wrapping_mul(0x51633e2d));
+    let sub_cell_x = permute(sample % n, n, pattern.wrapping_mul(0xa511e9b3));
+    let sub_cell_y = permute(sample / n, n, pattern.wrapping_mul(0x63d83595));
+    let jitter_x = hash_f64(sample, pattern.wrapping_mul(0xa399d265));
+    let jitter_y = hash_f64(sample, pattern.wrapping_mul(0x711ad6a5));
+    let n = n as f64;
+    let u = ((sample as f64 % n) + (sub_cell_y as f64 + jitter_x) / n) / n;
+    let v = ((sample as f64 / n).floor() + (sub_cell_x as f64 + jitter_y) / n) / n;
+    (u, v)
+}
+
+#[test]
+fn test_stratified_offsets_cover_every_quadrant() {
+    let strategy = SampleStrategy::Stratified { sqrt_samples: 2 };
+    for _ in 0..100 {
+        let mut quadrants: Vec<(bool, bool)> = strategy
+            .offsets(4)
+            .map(|(u, v)| {
+                assert!((0.0..1.0).contains(&u) && (0.0..1.0).contains(&v));
+                (u >= 0.5, v >= 0.5)
+            })
+            .collect();
+        quadrants.sort();
+        assert_eq!(
+            quadrants,
+            vec![(false, false), (false, true), (true, false), (true, true)]
+        );
+    }
+}
+
+#[test]
+fn test_uniform_offsets_are_pixel_centers() {
+    let offsets: Vec<(f64, f64)> = SampleStrategy::Uniform.offsets(3).collect();
+    assert_eq!(offsets, vec![(0.5, 0.5); 3]);
+}
+
+#[test]
+fn test_correlated_multi_jitter_offsets_are_stratified() {
+    let strategy = SampleStrategy::CorrelatedMultiJitter { sqrt_samples: 3 };
+    let mut sample_sets: Vec<Vec<(f64, f64)>> =
+        (0..50).map(|_| strategy.offsets(9).collect()).collect();
+    sample_sets.push((0..9).map(|sample| strategy.offset(sample)).collect());
+    for offsets in sample_sets {
+        let mut cells = vec![];
+        let mut columns = vec![];
+        let mut rows = vec![];
+        for (u, v) in offsets {
+            assert!((0.0..1.0).contains(&u) && (0.0..1.0).contains(&v));
+            cells.push(((u * 3.0) as usize, (v * 3.0) as usize));
+            columns.push((u * 9.0) as usize);
+            rows.push((v * 9.0) as usize);
+        }
+        cells.sort();
+        columns.sort();
+        rows.sort();
+        cells.dedup();
+        assert_eq!(cells.len(), 9, "{:?}", cells);
+        assert_eq!(columns, (0..9).collect::<Vec<usize>>());
+        assert_eq!(rows, (0..9).collect::<Vec<usize>>());
+    }
+}
@@ -0,0 +1,263 @@
+use std::{
+    fs::File,
+    io::{BufReader, Error, ErrorKind, Read},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::camera::{setup_camera, setup_orthographic_camera, Camera, CameraError};
+use crate::image::Framebuffer;
+use crate::shape::{
+    Capsule, Collidable, Cone, Cylinder, Disk, Ellipsoid, Plane, Quad, SmoothTriangle, Sphere,
+    Torus, Triangle, XYRect, XZRect, YZRect,
+};
+use crate::vec::Vec3;
+use crate::{render_parallel, RenderConfig};
+
+// parameters of setup_camera, the aspect ratio follows from the image size
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CameraSettings {
+    pub look_from: Vec3,
+    pub look_at: Vec3,
+    pub up: Vec3,
+    pub field_of_view: f64,
+    pub aperture: f64,
+    pub focus_distance: f64,
+    // perspective if missing from the scene file
+    #[serde(default)]
+    pub projection: ProjectionSettings,
+}
+
+// projection of the scene camera, see camera::Projection
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum ProjectionSettings {
+    // setup_camera with the field of view, aperture and focus distance
+    #[default]
+    Perspective,
+    // setup_orthographic_camera, the field of view, aperture and focus distance are
+    // ignored
+    Orthographic {
+        viewport_height: f64,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImageSettings {
+    pub width: usize,
+    pub height: usize,
+    pub samples_per_pixel: usize,
+    pub max_depth: usize,
+}
+
+// the shapes a scene file can describe
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Shape {
+    Sphere(Sphere),
+    Plane(Plane),
+    Triangle(Triangle),
+    SmoothTriangle(SmoothTriangle),
+    Cylinder(Cylinder),
+    Disk(Disk),
+    Cone(Cone),
+    Ellipsoid(Ellipsoid),
+    Capsule(Capsule),
+    Quad(Quad),
+    Torus(Torus),
+    XYRect(XYRect),
+    XZRect(XZRect),
+    YZRect(YZRect),
+}
+
+impl Shape {
+    pub fn to_collidable(&self) -> Box<dyn Collidable> {
+        match self {
+            Shape::Sphere(sphere) => Box::new(sphere.clone()),
+            Shape::Plane(plane) => Box::new(plane.clone()),
+            Shape::Triangle(triangle) => Box::new(triangle.clone()),
+            Shape::SmoothTriangle(triangle) => Box::new(triangle.clone()),
+            Shape::Cylinder(cylinder) => Box::new(cylinder.clone()),
+            Shape::Disk(disk) => Box::new(disk.clone()),
+            Shape::Cone(cone) => Box::new(cone.clone()),
+            Shape::Ellipsoid(ellipsoid) => Box::new(ellipsoid.clone()),
+            Shape::Capsule(capsule) => Box::new(capsule.clone()),
+            Shape::Quad(quad) => Box::new(quad.clone()),
+            Shape::Torus(torus) => Box::new(torus.clone()),
+            Shape::XYRect(rect) => Box::new(rect.clone()),
+            Shape::XZRect(rect) => Box::new(rect.clone()),
+            Shape::YZRect(rect) => Box::new(rect.clone()),
+        }
+    }
+}
+
+// everything needed to render an image, e.g. loaded from a json file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Scene {
+    pub camera: CameraSettings,
+    pub image: ImageSettings,
+    pub shapes: Vec<Shape>,
+}
+
+impl Scene {
+    pub fn read_json<R: Read>(reader: R) -> std::io::Result<Scene> {
+        serde_json::from_reader(reader).map_err(|error| Error::new(ErrorKind::InvalidData, error))
+    }
+
+    pub fn from_json(path: &str) -> std::io::Result<Scene> {
+        Scene::read_json(BufReader::new(File::open(path)?))
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("scenes only consist of serializable values")
+    }
+
+    pub fn setup_camera(&self) -> Result<Camera, CameraError> {
+        let camera = &self.camera;
+        let aspect_ratio = self.image.width as f64 / self.image.height as f64;
+        match camera.projection {
+            ProjectionSettings::Perspective => setup_camera(
+                camera.look_from,
+                camera.look_at,
+                camera.up,
+                camera.field_of_view,
+                aspect_ratio,
+                camera.aperture,
+                camera.focus_distance,
+            ),
+            ProjectionSettings::Orthographic { viewport_height } => setup_orthographic_camera(
+                camera.look_from,
+                camera.look_at,
+                camera.up,
+                viewport_height,
+                aspect_ratio,
+            ),
+        }
+    }
+
+    pub fn world(&self) -> Vec<Box<dyn Collidable>> {
+        self.shapes.iter().map(Shape::to_collidable).collect()
+    }
+
+    // render the scene with render_parallel, the pixels are averaged over all samples.
+    // Fails for camera settings which would not yield a valid camera
+    pub fn render(&self, thread_count: usize) -> Result<Framebuffer, CameraError> {
+        let config = RenderConfig {
+            max_depth: self.image.max_depth,
+            ..Default::default()
+        };
+        Ok(render_parallel(
+            &self.world(),
+            &self.setup_camera()?,
+            self.image.width,
+            self.image.height,
+            self.image.samples_per_pixel,
+            &config,
+            thread_count,
+        ))
+    }
+}
+
+#[cfg(test)]
+const TWO_SPHERES_JSON: &str = r#"{
+    "camera": {
+        "look_from": {"x": 0.0, "y": 0.0, "z": 1.0},
+        "look_at": {"x": 0.0, "y": 0.0, "z": -1.0},
+        "up": {"x": 0.0, "y": 1.0, "z": 0.0},
+        "field_of_view": 60.0,
+        "aperture": 0.0,
+        "focus_distance": 2.0
+    },
+    "image": {
+        "width": 8,
+        "height": 6,
+        "samples_per_pixel": 2,
+        "max_depth": 10
+    },
+    "shapes": [
+        {"Sphere": {
+            "center": {"x": 0.0, "y": 0.0, "z": -1.0},
+            "radius": 0.5,
+            "material": {"Lambertian": {"albedo": {"x": 0.1, "y": 0.2, "z": 0.5}}}
+        }},
+        {"Sphere": {
+            "center": {"x": 0.0, "y": -100.5, "z": -1.0},
+            "radius": 100.0,
+            "material": {"Metal": {"albedo": {"x": 0.8, "y": 0.8, "z": 0.0}, "fuzzyness": 0.3}}
+        }}
+    ]
+}"#;
+
+#[cfg(test)]
+use crate::camera::Projection;
+#[cfg(test)]
+use crate::material::Material;
+#[cfg(test)]
+use crate::vec3;
+
+#[test]
+fn test_read_two_spheres_scene() {
+    let scene = Scene::read_json(TWO_SPHERES_JSON.as_bytes()).unwrap();
+
+    assert_eq!(scene.image.width, 8);
+    assert_eq!(scene.shapes.len(), 2);
+    let expected = Shape::Sphere(Sphere {
+        center: vec3!(0.0, 0.0, -1.0),
+        radius: 0.5,
+        material: Material::Lambertian {
+            albedo: vec3!(0.1, 0.2, 0.5),
+        },
+    });
+    assert_eq!(scene.shapes[0], expected);
+
+    let framebuffer = scene.render(2).unwrap();
+    assert_eq!((framebuffer.width, framebuffer.height), (8, 6));
+}
+
+#[test]
+fn test_scene_with_invalid_camera_fails_to_render() {
+    let mut scene = Scene::read_json(TWO_SPHERES_JSON.as_bytes()).unwrap();
+    scene.camera.look_at = scene.camera.look_from;
+    assert_eq!(scene.render(1), Err(CameraError::ZeroViewDirection));
+}
+
+#[test]
+fn test_scene_with_orthographic_camera() {
+    let mut scene = Scene::read_json(TWO_SPHERES_JSON.as_bytes()).unwrap();
+    assert_eq!(scene.camera.projection, ProjectionSettings::Perspective);
+    let camera = scene.setup_camera().unwrap();
+    assert!(matches!(camera.projection, Projection::Perspective { .. }));
+
+    scene.camera.projection = ProjectionSettings::Orthographic {
+        viewport_height: 3.0,
+    };
+    let camera = scene.setup_camera().unwrap();
+    assert_eq!(camera.projection, Projection::Orthographic);
+    assert_eq!(camera.vertical, vec3!(0.0, 3.0, 0.0));
+    let round_tripped = Scene::read_json(scene.to_json().as_bytes()).unwrap();
+    assert_eq!(round_tripped, scene);
+
+    scene.camera.projection = ProjectionSettings::Orthographic {
+        viewport_height: 0.0,
+    };
+    assert_eq!(
+        scene.setup_camera().unwrap_err(),
+        CameraError::NonPositiveViewportHeight(0.0)
+    );
+    scene.camera.up = vec3!(0.0, 0.0, 1.0);
+    assert_eq!(
+        scene.setup_camera().unwrap_err(),
+        CameraError::UpParallelToViewDirection
+    );
+}
+
+#[test]
+fn test_scene_json_round_trip() {
+    let scene = Scene::read_json(TWO_SPHERES_JSON.as_bytes()).unwrap();
+    let round_tripped = Scene::read_json(scene.to_json().as_bytes()).unwrap();
+    assert_eq!(round_tripped, scene);
+}
+
+#[test]
+fn test_read_invalid_scene_fails() {
+    let error = Scene::read_json("{\"camera\": 1}".as_bytes()).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidData);
+}
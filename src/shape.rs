@@ -1,26 +1,144 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Error, ErrorKind},
+};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
 use crate::material::Material;
 use crate::ray::Ray;
-use crate::vec::{Vec3, ZERO};
+use crate::rng::{rand_f64, with_thread_rng, Rng};
+use crate::vec::Vec3;
+#[cfg(test)]
+use crate::vec::ZERO;
 use crate::vec3;
 
-#[derive(Debug, PartialEq)]
-pub struct Collision {
+#[derive(Debug, Clone, PartialEq)]
+pub struct Collision<'a> {
     // position of shape that intersects ray
     pub pos: Vec3,
     pub normal: Vec3,
     pub ray_is_inside: bool,
     // scalar value for which ray R(t):= R.origin + t*R.direction = pos
     pub t: f64,
-    pub material: Material,
+    // texture coordinates of pos in [0, 1] x [0, 1], (0, 0) for shapes without
+    // a parametrization of their surface
+    pub u: f64,
+    pub v: f64,
+    // borrowed from the shape that was hit, materials are not copied per hit
+    pub material: &'a Material,
 }
 
-pub trait Collidable {
+// Sync so that a world can be shared between render threads
+pub trait Collidable: Sync {
     // return scalar value t (if any) at which ray.origin + t*ray.direction
     // first intersects collidable body
-    fn collide(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Collision>;
+    fn collide(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Collision<'_>>;
+
+    // collide which adds the number of ray-shape intersection tests it performs to
+    // tests, see RenderStats::intersection_tests. A single one by default, hierarchies
+    // such as BvhNode count the shapes they actually test
+    fn collide_counted(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        tests: &mut usize,
+    ) -> Option<Collision<'_>> {
+        *tests += 1;
+        self.collide(ray, t_min, t_max)
+    }
+
+    // smallest box enclosing the body, None for unbounded bodies such as planes
+    fn bounding_box(&self) -> Option<Aabb>;
+
+    // span (entry t, exit t) of the ray within the body clipped to [t_min, t_max],
+    // None if the ray misses it or the body cannot compute the span directly
+    fn collide_interval(&self, _ray: &Ray, _t_min: f64, _t_max: f64) -> Option<(f64, f64)> {
+        None
+    }
+
+    // every crossing of the surface in [t_min, t_max] ordered by t, e.g. entry and
+    // exit of a sphere. By default the ray is followed from one collision to the next
+    fn collide_all(&self, ray: &Ray, t_min: f64, t_max: f64) -> Vec<Collision<'_>> {
+        let mut collisions = vec![];
+        let mut t_start = t_min;
+        while collisions.len() < MAX_SURFACE_CROSSINGS {
+            match self.collide(ray, t_start, t_max) {
+                Some(collision) => {
+                    t_start = collision.t + SURFACE_CROSSING_EPSILON;
+                    collisions.push(collision);
+                }
+                None => break,
+            }
+        }
+        collisions
+    }
+}
+
+// surfaces which can be sampled directly, e.g. area lights for next event estimation
+pub trait Emitter: Collidable {
+    // density (w.r.t. solid angle) with which random_point(origin) yields a point
+    // in the given direction, 0 if the direction misses self
+    fn pdf_value(&self, origin: &Vec3, direction: &Vec3) -> f64;
+
+    // random point on the surface as seen from origin
+    fn random_point(&self, origin: &Vec3) -> Vec3;
+}
+
+// distance behind a crossing from which Collidable::collide_all searches for the next one
+const SURFACE_CROSSING_EPSILON: f64 = 1e-9;
+// guards collide_all against shapes that report the same crossing over and over
+const MAX_SURFACE_CROSSINGS: usize = 64;
+
+// spherical coordinates of a point on the unit sphere: u is the azimuth around the
+// y axis starting at -x (0 and 1) via +z (0.25) and +x (0.5), v runs from the top (0)
+// to the bottom (1). An equirectangular image wraps around the sphere this way
+fn sphere_uv(direction: &Vec3) -> (f64, f64) {
+    let u = 0.5 + (-direction.z).atan2(direction.x) / (2.0 * std::f64::consts::PI);
+    let v = 0.5 - direction.y.clamp(-1.0, 1.0).asin() / std::f64::consts::PI;
+    (u, v)
+}
+
+// angle of direction around the (unit) axis as a fraction of a full turn in [0, 1],
+// starting from the tangent of axis.build_onb(). Used as u by the round shapes
+fn turn_around_axis(axis: &Vec3, direction: &Vec3) -> f64 {
+    let (tangent, bitangent, _) = axis.build_onb();
+    0.5 + direction.dot(&bitangent).atan2(direction.dot(&tangent)) / (2.0 * std::f64::consts::PI)
+}
+
+// coordinates of offset along the tangent and bitangent of the (unit) normal
+fn planar_uv(normal: &Vec3, offset: &Vec3) -> (f64, f64) {
+    let (tangent, bitangent, _) = normal.build_onb();
+    (offset.dot(&tangent), offset.dot(&bitangent))
+}
+
+#[test]
+fn test_sphere_uv_of_poles() {
+    let (u, v) = sphere_uv(&vec3!(0.0, 1.0, 0.0));
+    assert_eq!(v, 0.0);
+    assert_eq!(u, 0.5);
+    assert_eq!(sphere_uv(&vec3!(0.0, -1.0, 0.0)).1, 1.0);
+    // the seam where u wraps from 1 to 0 lies at -x
+    let (u, v) = sphere_uv(&vec3!(-1.0, 0.0, 0.0));
+    assert!(u == 0.0 || u == 1.0, "u = {:?}", u);
+    assert_eq!(v, 0.5);
+    // just beside the seam
+    assert!(sphere_uv(&vec3!(-1.0, 0.0, -1e-3)).0 > 0.999);
+    assert!(sphere_uv(&vec3!(-1.0, 0.0, 1e-3)).0 < 0.001);
+    // u = 0.5 + atan2(-z, x) / 2π is 0.5 at +x since atan2(0, 1) = 0, hence +x lies
+    // in the middle of the image and not on the seam at u = 0 or 1
+    assert_eq!(sphere_uv(&vec3!(1.0, 0.0, 0.0)), (0.5, 0.5));
+    assert_eq!(sphere_uv(&vec3!(0.0, 0.0, 1.0)), (0.25, 0.5));
 }
 
-#[derive(Debug, PartialEq)]
+// a negative radius turns the sphere inside out: it has the same surface as
+// for |radius| but its outward normal points towards the center. Placed inside a
+// glass sphere this models a hollow glass bubble, rays entering the bubble are
+// then treated as leaving the glass
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Sphere {
     pub center: Vec3,
     pub radius: f64,
@@ -32,32 +150,24 @@ pub struct Sphere {
 macro_rules! sphere {
     () => {
         Sphere {
-            center: crate::vec3!(),
+            center: $crate::vec3!(),
             radius: 1f64,
             material: Material::Lambertian {
-                albedo: crate::vec3!(),
+                albedo: $crate::vec3!(),
             },
         }
     };
 }
 
 impl Collidable for Sphere {
-    fn collide(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Collision> {
-        let delta: Vec3 = ray.origin - self.center;
-        let a = ray.direction.norm_squared();
-        let half_b = delta.dot(&ray.direction);
-        let c = delta.norm_squared() - self.radius * self.radius;
-        let discriminant = half_b * half_b - a * c;
-        if discriminant < 0.0 {
-            return None;
-        }
+    fn collide(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Collision<'_>> {
+        let (near, far) = sphere_roots(&self.center, self.radius, ray)?;
 
         // find the nearest root t within an acceptable range
         // s.t. ray(t) intersect sphere != empty
-        let discriminant_root = discriminant.sqrt();
-        let mut root = (-half_b - discriminant_root) / a;
+        let mut root = near;
         if root < t_min || root > t_max {
-            root = (-half_b + discriminant_root) / a;
+            root = far;
             if root < t_min || root > t_max {
                 return None;
             }
@@ -66,7 +176,11 @@ impl Collidable for Sphere {
 
         // compute the angle between ray and intersection point
         // to compute a normal that always points towards the ray
-        let outward_normal: Vec3 = (ray.at(root) - self.center) * (1.0 / self.radius);
+        let mut outward_normal: Vec3 = (ray.at(root) - self.center) / self.radius.abs();
+        let (u, v) = sphere_uv(&outward_normal);
+        if self.radius < 0.0 {
+            outward_normal = -outward_normal;
+        }
         let ray_is_inside_sphere: bool = ray.direction.dot(&outward_normal) >= 0.0;
         // let the normal point towards the ray
         let normal = match ray_is_inside_sphere {
@@ -80,13 +194,212 @@ impl Collidable for Sphere {
             }
         };
 
-        return Some(Collision {
+        Some(Collision {
             pos: ray.at(root),
             normal,
             ray_is_inside: ray_is_inside_sphere,
             t: root,
-            material: self.material,
-        });
+            u,
+            v,
+            material: &self.material,
+        })
+    }
+
+    // both roots of the quadratic, a tangent ray enters and exits at the same t
+    fn collide_interval(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<(f64, f64)> {
+        let (near, far) = sphere_roots(&self.center, self.radius, ray)?;
+        let entry = near.max(t_min);
+        let exit = far.min(t_max);
+        if entry > exit {
+            return None;
+        }
+        Some((entry, exit))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // negative radii model hollow spheres but span the same volume
+        let radius = self.radius.abs();
+        let extent = vec3!(radius, radius, radius);
+        Some(Aabb {
+            min: self.center - extent,
+            max: self.center + extent,
+        })
+    }
+}
+
+// both roots t (ascending) of |ray.at(t) - center|² = radius², None if the ray
+// misses the sphere. Computing half_b² - a*c directly cancels catastrophically
+// for a small sphere far away from the ray origin, hence the discriminant is
+// taken from the distance of the center to the line of the ray and the roots
+// from the numerically stable form of the quadratic formula
+fn sphere_roots(center: &Vec3, radius: f64, ray: &Ray) -> Option<(f64, f64)> {
+    let delta: Vec3 = ray.origin - *center;
+    let a = ray.direction.norm_squared();
+    if a == 0.0 {
+        return None;
+    }
+    let half_b = delta.dot(&ray.direction);
+    let c = delta.norm_squared() - radius * radius;
+    // delta minus its component along the ray, i.e. the closest approach
+    let perpendicular = delta - (half_b / a) * ray.direction;
+    let discriminant = a * (radius * radius - perpendicular.norm_squared());
+    if discriminant < 0.0 {
+        return None;
+    }
+    let q = -half_b - half_b.signum() * discriminant.sqrt();
+    if q == 0.0 {
+        // the ray starts on the sphere and grazes it
+        return Some((0.0, 0.0));
+    }
+    let (t_0, t_1) = (q / a, c / q);
+    Some((t_0.min(t_1), t_0.max(t_1)))
+}
+
+impl Sphere {
+    pub fn area(&self) -> f64 {
+        4.0 * std::f64::consts::PI * self.radius * self.radius
+    }
+
+    // cosine of the half opening angle of the cone of directions from origin to the
+    // sphere, None if origin lies inside the sphere
+    fn cos_theta_max(&self, origin: &Vec3) -> Option<f64> {
+        let radius_squared = self.radius * self.radius;
+        let distance_squared = (self.center - *origin).norm_squared();
+        if distance_squared <= radius_squared {
+            return None;
+        }
+        Some((1.0 - radius_squared / distance_squared).sqrt())
+    }
+
+    // random point of the sphere seen from origin, its direction from origin being
+    // uniformly distributed within the cone subtended by the sphere. From inside the
+    // sphere the points are uniformly distributed over the whole surface
+    pub fn random_point_toward(&self, origin: &Vec3, rng: &mut Rng) -> Vec3 {
+        let radius = self.radius.abs();
+        let cos_theta_max = match self.cos_theta_max(origin) {
+            Some(cos_theta_max) => cos_theta_max,
+            None => return self.center + radius * rng.unit_vec(),
+        };
+        let to_center = self.center - *origin;
+        let (tangent, bitangent, axis) = to_center.to_unit_vec_unchecked().build_onb();
+        let phi = 2.0 * std::f64::consts::PI * rng.next_f64(0.0, 1.0);
+        let cos_theta = 1.0 + rng.next_f64(0.0, 1.0) * (cos_theta_max - 1.0);
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let direction =
+            sin_theta * phi.cos() * tangent + sin_theta * phi.sin() * bitangent + cos_theta * axis;
+        // nearest crossing of the ray origin + t * direction, a ray grazing the
+        // sphere may miss it by rounding and takes the point closest to the center
+        let t_center = direction.dot(&to_center);
+        let half_chord_squared = radius * radius - to_center.norm_squared() + t_center * t_center;
+        *origin + (t_center - half_chord_squared.max(0.0).sqrt()) * direction
+    }
+}
+
+impl Emitter for Sphere {
+    fn pdf_value(&self, origin: &Vec3, direction: &Vec3) -> f64 {
+        match self.cos_theta_max(origin) {
+            Some(cos_theta_max) => {
+                let cos_theta = (self.center - *origin).angle_between(direction).cos();
+                if cos_theta < cos_theta_max {
+                    return 0.0;
+                }
+                let solid_angle = 2.0 * std::f64::consts::PI * (1.0 - cos_theta_max);
+                1.0 / solid_angle
+            }
+            None => {
+                let ray = Ray {
+                    origin: *origin,
+                    direction: *direction,
+                };
+                match self.collide(&ray, f64::EPSILON, f64::INFINITY) {
+                    // the area density of the uniform surface points per solid angle
+                    Some(collision) => {
+                        let distance_squared = (collision.pos - *origin).norm_squared();
+                        let cos_theta = collision.normal.dot(direction).abs() / direction.norm();
+                        distance_squared / (cos_theta * self.area())
+                    }
+                    None => 0.0,
+                }
+            }
+        }
+    }
+
+    fn random_point(&self, origin: &Vec3) -> Vec3 {
+        with_thread_rng(|rng| self.random_point_toward(origin, rng))
+    }
+}
+
+#[test]
+fn test_sphere_light_sampling_stays_in_the_cone() {
+    let sphere = Sphere {
+        center: vec3!(1.0, 2.0, -4.0),
+        radius: 0.5,
+        material: Material::DiffuseLight {
+            emit: vec3!(4.0, 4.0, 4.0),
+        },
+    };
+    let origin = vec3!(0.0, 0.0, 0.0);
+    let to_center = sphere.center - origin;
+    let cos_theta_max = (1.0 - 0.25 / to_center.norm_squared()).sqrt();
+    let mut rng = Rng::new(3);
+    for _ in 0..1000 {
+        let point = sphere.random_point_toward(&origin, &mut rng);
+        assert!(
+            ((point - sphere.center).norm() - 0.5).abs() < 1e-9,
+            "{:?}",
+            point
+        );
+        let direction = point - origin;
+        let cos_theta = to_center.angle_between(&direction).cos();
+        assert!(cos_theta >= cos_theta_max - 1e-9, "{:?}", direction);
+        // the visible side of the sphere
+        assert!((point - sphere.center).dot(&direction) <= 1e-9);
+        assert!(sphere.pdf_value(&origin, &direction) > 0.0);
+    }
+    assert_eq!(sphere.pdf_value(&origin, &vec3!(0.0, 0.0, 1.0)), 0.0);
+
+    // the pdf integrates to 1 over all directions: E[pdf(d)] = 1 / (4 pi) for uniform d
+    let integral = |origin: &Vec3, rng: &mut Rng| {
+        let sample_count = 200_000;
+        let sum: f64 = (0..sample_count)
+            .map(|_| sphere.pdf_value(origin, &rng.unit_vec()))
+            .sum();
+        4.0 * std::f64::consts::PI * sum / sample_count as f64
+    };
+    let outside = integral(&origin, &mut rng);
+    assert!((outside - 1.0).abs() < 0.05, "{:?}", outside);
+    // from inside the points cover the whole sphere
+    let inside_origin = sphere.center + vec3!(0.2, 0.0, 0.1);
+    let inside = integral(&inside_origin, &mut rng);
+    assert!((inside - 1.0).abs() < 0.05, "{:?}", inside);
+    for _ in 0..100 {
+        let point = sphere.random_point_toward(&inside_origin, &mut rng);
+        assert!(((point - sphere.center).norm() - 0.5).abs() < 1e-9);
+    }
+}
+
+#[test]
+fn test_tiny_distant_sphere_collides_precisely() {
+    let distance = 1e5;
+    let radius = 1e-3;
+    let sphere = Sphere {
+        center: vec3!(0.0, 0.0, -distance),
+        radius,
+        material: Material::NormalDebug,
+    };
+    for offset in [0.0, 5e-4, 9e-4] {
+        let ray = Ray {
+            origin: vec3!(offset, 0.0, 0.0),
+            direction: vec3!(0.0, 0.0, -1.0),
+        };
+        let collision = sphere.collide(&ray, 0.001, f64::MAX).unwrap();
+        let expected = distance - (radius * radius - offset * offset).sqrt();
+        assert!(
+            (collision.t - expected).abs() < 1e-9,
+            "t = {:?} != {:?}",
+            collision.t,
+            expected
+        );
     }
 }
 
@@ -103,15 +416,35 @@ fn test_sphere_macro() {
     assert_eq!(actual, expected);
 }
 
+#[cfg(feature = "serde")]
+#[test]
+fn test_sphere_json_round_trip() {
+    let sphere = Sphere {
+        center: vec3!(0.0, -1.5, 2.0),
+        radius: 0.5,
+        material: Material::Lambertian {
+            albedo: vec3!(0.1, 0.2, 0.5),
+        },
+    };
+    let json = serde_json::to_string(&sphere).unwrap();
+    assert_eq!(
+        json,
+        r#"{"center":[0.0,-1.5,2.0],"radius":0.5,"material":{"Lambertian":{"albedo":[0.1,0.2,0.5]}}}"#
+    );
+    assert_eq!(serde_json::from_str::<Sphere>(&json).unwrap(), sphere);
+}
+
 #[test]
 fn test_ray_collides_sphere() {
     let material = Material::Dialectric {
         refraction_index: 1.0,
+        attenuation: vec3!(1.0, 1.0, 1.0),
+        absorption: 0.0,
     };
     let sphere = Sphere {
         center: vec3!(0.0, 0.0, -2.0),
         radius: 1.0,
-        material,
+        material: material.clone(),
     };
     let ray = Ray {
         origin: ZERO,
@@ -124,7 +457,9 @@ fn test_ray_collides_sphere() {
         normal: vec3!(0.0, 0.0, 1.0),
         ray_is_inside: false,
         t: 1.0,
-        material,
+        u: 0.25,
+        v: 0.5,
+        material: &material,
     });
 
     assert_eq!(actual, expected);
@@ -134,11 +469,13 @@ fn test_ray_collides_sphere() {
 fn test_ray_collides_inside_sphere() {
     let material = Material::Dialectric {
         refraction_index: 1.0,
+        attenuation: vec3!(1.0, 1.0, 1.0),
+        absorption: 0.0,
     };
     let sphere = Sphere {
         center: vec3!(0.0, 0.0, -2.0),
         radius: 1.0,
-        material,
+        material: material.clone(),
     };
 
     let inside_sphere_pos: Vec3 = vec3!(0.0, 0.0, -1.5);
@@ -153,21 +490,136 @@ fn test_ray_collides_inside_sphere() {
         normal: vec3!(0.0, 0.0, 1.0),
         ray_is_inside: true,
         t: 1.5,
-        material,
+        u: 0.75,
+        v: 0.5,
+        material: &material,
     });
 
     assert_eq!(actual, expected);
 }
 
+#[test]
+fn test_negative_radius_sphere_is_hollow() {
+    let glass = Material::Dialectric {
+        refraction_index: 1.5,
+        attenuation: vec3!(1.0, 1.0, 1.0),
+        absorption: 0.0,
+    };
+    let outer = Sphere {
+        center: vec3!(0.0, 0.0, -1.0),
+        radius: 0.5,
+        material: glass.clone(),
+    };
+    let bubble = Sphere {
+        center: vec3!(0.0, 0.0, -1.0),
+        radius: -0.45,
+        material: glass,
+    };
+    let ray = Ray {
+        origin: ZERO,
+        direction: vec3!(0.0, 0.0, -1.0),
+    };
+
+    // the ray enters the glass at the outer sphere
+    let collision = outer.collide(&ray, 0.001, 10.0).unwrap();
+    assert!(!collision.ray_is_inside);
+    assert_eq!(collision.normal, vec3!(0.0, 0.0, 1.0));
+
+    // and leaves the glass into the bubble, the outward normal of the
+    // bubble points towards its center
+    let collision = bubble.collide(&ray, 0.001, 10.0).unwrap();
+    assert!((collision.t - 0.55).abs() < 1e-9);
+    assert!(collision.ray_is_inside);
+    assert!((collision.normal - vec3!(0.0, 0.0, 1.0)).norm() < 1e-9);
+
+    // on the far side of the bubble the ray enters the glass again
+    let collision = bubble.collide(&ray, 0.6, 10.0).unwrap();
+    assert!((collision.t - 1.45).abs() < 1e-9);
+    assert!(!collision.ray_is_inside);
+    assert!((collision.normal - vec3!(0.0, 0.0, 1.0)).norm() < 1e-9);
+}
+
+#[test]
+fn test_positive_radius_sphere_normal_points_outwards() {
+    let sphere = Sphere {
+        center: vec3!(0.0, 0.0, -1.0),
+        radius: 0.45,
+        material: Material::Lambertian {
+            albedo: vec3!(0.5, 0.5, 0.5),
+        },
+    };
+    let ray = Ray {
+        origin: ZERO,
+        direction: vec3!(0.0, 0.0, -1.0),
+    };
+
+    let collision = sphere.collide(&ray, 0.001, 10.0).unwrap();
+    assert!((collision.t - 0.55).abs() < 1e-9);
+    assert!(!collision.ray_is_inside);
+    assert!((collision.normal - vec3!(0.0, 0.0, 1.0)).norm() < 1e-9);
+
+    let collision = sphere.collide(&ray, 0.6, 10.0).unwrap();
+    assert!((collision.t - 1.45).abs() < 1e-9);
+    assert!(collision.ray_is_inside);
+    assert!((collision.normal - vec3!(0.0, 0.0, 1.0)).norm() < 1e-9);
+}
+
+#[test]
+fn test_sphere_collide_interval() {
+    let sphere = Sphere {
+        center: vec3!(0.0, 0.0, -3.0),
+        radius: 1.0,
+        material: Material::Lambertian {
+            albedo: vec3!(0.5, 0.5, 0.5),
+        },
+    };
+    let through = Ray {
+        origin: ZERO,
+        direction: vec3!(0.0, 0.0, -1.0),
+    };
+    assert_eq!(
+        sphere.collide_interval(&through, 0.001, 10.0),
+        Some((2.0, 4.0))
+    );
+    // clipped to [t_min, t_max]
+    assert_eq!(
+        sphere.collide_interval(&through, 3.0, 3.5),
+        Some((3.0, 3.5))
+    );
+    assert_eq!(sphere.collide_interval(&through, 0.001, 1.0), None);
+
+    let tangent = Ray {
+        origin: vec3!(1.0, 0.0, 0.0),
+        direction: vec3!(0.0, 0.0, -1.0),
+    };
+    assert_eq!(
+        sphere.collide_interval(&tangent, 0.001, 10.0),
+        Some((3.0, 3.0))
+    );
+
+    let miss = Ray {
+        origin: vec3!(1.001, 0.0, 0.0),
+        direction: vec3!(0.0, 0.0, -1.0),
+    };
+    assert_eq!(sphere.collide_interval(&miss, 0.001, 10.0), None);
+    // bodies without closed form keep the default
+    assert_eq!(
+        test_triangle().collide_interval(&through, 0.001, 10.0),
+        None
+    );
+}
+
 #[test]
 fn test_ray_starting_on_boundary_collides_sphere() {
     let material = Material::Dialectric {
         refraction_index: 1.0,
+        attenuation: vec3!(1.0, 1.0, 1.0),
+        absorption: 0.0,
     };
     let sphere = Sphere {
         center: vec3!(0.0, 0.0, -2.0),
         radius: 1.0,
-        material,
+        material: material.clone(),
     };
 
     let sphere_boundary: Vec3 = vec3!(0.0, 0.0, -1.0);
@@ -183,8 +635,2936 @@ fn test_ray_starting_on_boundary_collides_sphere() {
         normal: vec3!(0.0, 0.0, 1.0),
         ray_is_inside: true,
         t: 2.0,
-        material,
+        u: 0.75,
+        v: 0.5,
+        material: &material,
+    });
+
+    assert_eq!(actual, expected);
+}
+
+// bounding boxes carry no material of their own, collisions with
+// them are reported with a neutral grey diffuse material instead
+pub const AABB_MATERIAL: Material = Material::Lambertian {
+    albedo: vec3!(0.5, 0.5, 0.5),
+};
+
+// axis-aligned bounding box spanned by the two corners min and max
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+fn axis_unit_vec(axis: usize, sign: f64) -> Vec3 {
+    match axis {
+        0 => vec3!(sign, 0.0, 0.0),
+        1 => vec3!(0.0, sign, 0.0),
+        _ => vec3!(0.0, 0.0, sign),
+    }
+}
+
+impl Aabb {
+    // slab method: intersect the intervals in which the ray lies between the
+    // two planes of every axis. Returns the scalar values t at which the ray
+    // enters and exits the box, each together with the axis of the crossed face
+    fn slab_interval(&self, ray: &Ray) -> Option<((f64, usize), (f64, usize))> {
+        let mut enter = (f64::NEG_INFINITY, 0);
+        let mut exit = (f64::INFINITY, 0);
+        for axis in 0..3 {
+            if ray.direction[axis] == 0.0 {
+                // the ray runs parallel to the slab, i.e. 1/direction is infinite
+                // and (min - origin) * inf may be NaN. Either the ray lies inside
+                // the slab for every t or for none
+                if ray.origin[axis] < self.min[axis] || ray.origin[axis] > self.max[axis] {
+                    return None;
+                }
+                continue;
+            }
+            let inverse_direction = 1.0 / ray.direction[axis];
+            let mut t0 = (self.min[axis] - ray.origin[axis]) * inverse_direction;
+            let mut t1 = (self.max[axis] - ray.origin[axis]) * inverse_direction;
+            if inverse_direction < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            if t0 > enter.0 {
+                enter = (t0, axis);
+            }
+            if t1 < exit.0 {
+                exit = (t1, axis);
+            }
+        }
+        if enter.0 > exit.0 {
+            return None;
+        }
+        Some((enter, exit))
+    }
+
+    // smallest box enclosing both boxes
+    pub fn surrounding(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: self.min.min_components(&other.min),
+            max: self.max.max_components(&other.max),
+        }
+    }
+
+    pub fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> bool {
+        match self.slab_interval(ray) {
+            Some(((t_enter, _), (t_exit, _))) => t_enter.max(t_min) <= t_exit.min(t_max),
+            None => false,
+        }
+    }
+}
+
+impl Collidable for Aabb {
+    fn collide(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Collision<'_>> {
+        let ((t_enter, enter_axis), (t_exit, exit_axis)) = self.slab_interval(ray)?;
+
+        // if the entry point lies behind t_min the ray starts inside the box
+        // and we report the face through which it leaves instead
+        let (t, axis, ray_is_inside) = if t_min <= t_enter && t_enter <= t_max {
+            (t_enter, enter_axis, false)
+        } else if t_min <= t_exit && t_exit <= t_max {
+            (t_exit, exit_axis, true)
+        } else {
+            return None;
+        };
+
+        // in both cases the face normal pointing towards the ray
+        // is the one opposing the ray direction on the crossed axis
+        let normal = axis_unit_vec(axis, -ray.direction[axis].signum());
+
+        // (u, v) run from 0 to 1 across the face along the next two coordinate axes
+        let pos = ray.at(t);
+        let face_coordinate = |i: usize| {
+            let extent = self.max[i] - self.min[i];
+            if extent > 0.0 {
+                ((pos[i] - self.min[i]) / extent).clamp(0.0, 1.0)
+            } else {
+                0.0
+            }
+        };
+        Some(Collision {
+            pos,
+            normal,
+            ray_is_inside,
+            t,
+            u: face_coordinate((axis + 1) % 3),
+            v: face_coordinate((axis + 2) % 3),
+            material: &AABB_MATERIAL,
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(*self)
+    }
+}
+
+macro_rules! test_ray_collides_aabb_face {
+    ($($name: ident: $value:expr,)*) => {
+        $(
+            #[test]
+            fn $name(){
+                let (origin, direction, expected_pos, expected_normal) = $value;
+                let aabb = Aabb {
+                    min: vec3!(-1.0, -1.0, -1.0),
+                    max: vec3!(1.0, 1.0, 1.0),
+                };
+                let ray = Ray { origin, direction };
+                let actual = aabb.collide(&ray, 0.001, 10.0);
+
+                let expected = Some(Collision {
+                    pos: expected_pos,
+                    normal: expected_normal,
+                    ray_is_inside: false,
+                    t: 4.0,
+                    u: 0.75,
+                    v: 0.5,
+                    material: &AABB_MATERIAL,
+                });
+                assert_eq!(actual, expected);
+                assert!(aabb.hit(&ray, 0.001, 10.0));
+            }
+        )*
+    }
+}
+
+test_ray_collides_aabb_face! {
+    test_ray_collides_aabb_min_x_face: (
+        vec3!(-5.0, 0.5, 0.0),
+        vec3!(1.0, 0.0, 0.0),
+        vec3!(-1.0, 0.5, 0.0),
+        vec3!(-1.0, 0.0, 0.0),
+    ),
+    test_ray_collides_aabb_max_x_face: (
+        vec3!(5.0, 0.5, 0.0),
+        vec3!(-1.0, 0.0, 0.0),
+        vec3!(1.0, 0.5, 0.0),
+        vec3!(1.0, 0.0, 0.0),
+    ),
+    test_ray_collides_aabb_min_y_face: (
+        vec3!(0.0, -5.0, 0.5),
+        vec3!(0.0, 1.0, 0.0),
+        vec3!(0.0, -1.0, 0.5),
+        vec3!(0.0, -1.0, 0.0),
+    ),
+    test_ray_collides_aabb_max_y_face: (
+        vec3!(0.0, 5.0, 0.5),
+        vec3!(0.0, -1.0, 0.0),
+        vec3!(0.0, 1.0, 0.5),
+        vec3!(0.0, 1.0, 0.0),
+    ),
+    test_ray_collides_aabb_min_z_face: (
+        vec3!(0.5, 0.0, -5.0),
+        vec3!(0.0, 0.0, 1.0),
+        vec3!(0.5, 0.0, -1.0),
+        vec3!(0.0, 0.0, -1.0),
+    ),
+    test_ray_collides_aabb_max_z_face: (
+        vec3!(0.5, 0.0, 5.0),
+        vec3!(0.0, 0.0, -1.0),
+        vec3!(0.5, 0.0, 1.0),
+        vec3!(0.0, 0.0, 1.0),
+    ),
+}
+
+#[test]
+fn test_ray_collides_inside_aabb() {
+    let aabb = Aabb {
+        min: vec3!(-1.0, -1.0, -1.0),
+        max: vec3!(1.0, 1.0, 1.0),
+    };
+    let ray = Ray {
+        origin: ZERO,
+        direction: vec3!(0.0, 0.0, -1.0),
+    };
+    let actual = aabb.collide(&ray, 0.001, 10.0);
+
+    let expected = Some(Collision {
+        pos: vec3!(0.0, 0.0, -1.0),
+        normal: vec3!(0.0, 0.0, 1.0),
+        ray_is_inside: true,
+        t: 1.0,
+        u: 0.5,
+        v: 0.5,
+        material: &AABB_MATERIAL,
+    });
+
+    assert_eq!(actual, expected);
+    assert!(aabb.hit(&ray, 0.001, 10.0));
+}
+
+#[test]
+fn test_parallel_ray_outside_slab_misses_aabb() {
+    let aabb = Aabb {
+        min: vec3!(-1.0, -1.0, -1.0),
+        max: vec3!(1.0, 1.0, 1.0),
+    };
+    // direction.x == 0 while origin.x lies outside of the x slab
+    let ray = Ray {
+        origin: vec3!(2.0, 0.0, 5.0),
+        direction: vec3!(0.0, 0.0, -1.0),
+    };
+
+    assert!(!aabb.hit(&ray, 0.001, 10.0));
+    assert_eq!(aabb.collide(&ray, 0.001, 10.0), None);
+}
+
+// determinants below this threshold are treated as a ray parallel
+// to the triangle plane or a degenerate (zero-area) triangle
+const TRIANGLE_DETERMINANT_EPSILON: f64 = 1e-12;
+
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Triangle {
+    pub a: Vec3,
+    pub b: Vec3,
+    pub c: Vec3,
+    pub material: Material,
+}
+
+// Möller–Trumbore: solve ray.origin + t*ray.direction = a + u*(b-a) + v*(c-a)
+// for (t, u, v) via Cramer's rule, where (u, v) are barycentric coordinates
+fn collide_triangle(
+    a: &Vec3,
+    b: &Vec3,
+    c: &Vec3,
+    ray: &Ray,
+    t_min: f64,
+    t_max: f64,
+) -> Option<(f64, f64, f64)> {
+    let edge_1 = *b - *a;
+    let edge_2 = *c - *a;
+    let p = ray.direction.cross(&edge_2);
+    let determinant = edge_1.dot(&p);
+    if determinant.abs() < TRIANGLE_DETERMINANT_EPSILON {
+        return None;
+    }
+    let inverse_determinant = 1.0 / determinant;
+
+    let delta = ray.origin - *a;
+    let u = delta.dot(&p) * inverse_determinant;
+    if u < 0.0 {
+        return None;
+    }
+    let q = delta.cross(&edge_1);
+    let v = ray.direction.dot(&q) * inverse_determinant;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let t = edge_2.dot(&q) * inverse_determinant;
+    if t < t_min || t > t_max {
+        return None;
+    }
+    Some((t, u, v))
+}
+
+fn triangle_bounding_box(a: &Vec3, b: &Vec3, c: &Vec3) -> Aabb {
+    Aabb {
+        min: a.min_components(b).min_components(c),
+        max: a.max_components(b).max_components(c),
+    }
+}
+
+impl Collidable for Triangle {
+    fn collide(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Collision<'_>> {
+        let (t, u, v) = collide_triangle(&self.a, &self.b, &self.c, ray, t_min, t_max)?;
+
+        // same convention as for spheres: the reported normal always points towards the ray
+        // the edges are not parallel, otherwise the determinant would vanish
+        let outward_normal = (self.b - self.a)
+            .cross(&(self.c - self.a))
+            .to_unit_vec_unchecked();
+        let ray_is_inside = ray.direction.dot(&outward_normal) >= 0.0;
+        let normal = if ray_is_inside {
+            -outward_normal
+        } else {
+            outward_normal
+        };
+
+        Some(Collision {
+            pos: ray.at(t),
+            normal,
+            ray_is_inside,
+            t,
+            u,
+            v,
+            material: &self.material,
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(triangle_bounding_box(&self.a, &self.b, &self.c))
+    }
+}
+
+#[cfg(test)]
+fn test_triangle() -> Triangle {
+    Triangle {
+        a: vec3!(-1.0, -1.0, -1.0),
+        b: vec3!(1.0, -1.0, -1.0),
+        c: vec3!(0.0, 1.0, -1.0),
+        material: Material::Lambertian {
+            albedo: vec3!(0.5, 0.5, 0.5),
+        },
+    }
+}
+
+#[test]
+fn test_ray_collides_triangle() {
+    let triangle = test_triangle();
+    let ray = Ray {
+        origin: ZERO,
+        direction: vec3!(0.0, 0.0, -1.0),
+    };
+    let actual = triangle.collide(&ray, 0.001, 10.0);
+
+    let expected = Some(Collision {
+        pos: vec3!(0.0, 0.0, -1.0),
+        normal: vec3!(0.0, 0.0, 1.0),
+        ray_is_inside: false,
+        t: 1.0,
+        u: 0.25,
+        v: 0.5,
+        material: &triangle.material,
     });
 
     assert_eq!(actual, expected);
 }
+
+#[test]
+fn test_ray_grazing_triangle_edge_misses() {
+    let triangle = test_triangle();
+    // the edge b -> c crosses y = 0 at x = 0.5
+    let just_outside = Ray {
+        origin: vec3!(0.5 + 1e-9, 0.0, 0.0),
+        direction: vec3!(0.0, 0.0, -1.0),
+    };
+    let just_inside = Ray {
+        origin: vec3!(0.5 - 1e-9, 0.0, 0.0),
+        direction: vec3!(0.0, 0.0, -1.0),
+    };
+
+    assert_eq!(triangle.collide(&just_outside, 0.001, 10.0), None);
+    assert!(triangle.collide(&just_inside, 0.001, 10.0).is_some());
+}
+
+#[test]
+fn test_ray_collides_triangle_back_face() {
+    let triangle = test_triangle();
+    let ray = Ray {
+        origin: vec3!(0.0, 0.0, -2.0),
+        direction: vec3!(0.0, 0.0, 1.0),
+    };
+    let actual = triangle.collide(&ray, 0.001, 10.0);
+
+    let expected = Some(Collision {
+        pos: vec3!(0.0, 0.0, -1.0),
+        normal: vec3!(0.0, 0.0, -1.0),
+        ray_is_inside: true,
+        t: 1.0,
+        u: 0.25,
+        v: 0.5,
+        material: &triangle.material,
+    });
+
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn test_degenerate_triangle_never_collides() {
+    let triangle = Triangle {
+        a: vec3!(-1.0, 0.0, -1.0),
+        b: vec3!(0.0, 0.0, -1.0),
+        c: vec3!(1.0, 0.0, -1.0),
+        material: Material::Lambertian {
+            albedo: vec3!(0.5, 0.5, 0.5),
+        },
+    };
+    let ray = Ray {
+        origin: ZERO,
+        direction: vec3!(0.0, 0.0, -1.0),
+    };
+
+    assert_eq!(triangle.collide(&ray, 0.001, 10.0), None);
+}
+
+// triangle whose shading normal is interpolated between the normals given at its
+// vertices by the barycentric coordinates of the hit, which hides the facets of a
+// mesh approximating a curved surface. The outside is that of the geometric normal
+// (b - a) x (c - a) like for Triangle, vertex normals may point to either side
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SmoothTriangle {
+    pub a: Vec3,
+    pub b: Vec3,
+    pub c: Vec3,
+    pub normal_a: Vec3,
+    pub normal_b: Vec3,
+    pub normal_c: Vec3,
+    pub material: Material,
+}
+
+impl Collidable for SmoothTriangle {
+    fn collide(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Collision<'_>> {
+        let (t, u, v) = collide_triangle(&self.a, &self.b, &self.c, ray, t_min, t_max)?;
+
+        let geometric_normal = (self.b - self.a).cross(&(self.c - self.a));
+        let ray_is_inside = ray.direction.dot(&geometric_normal) >= 0.0;
+        let interpolated = (1.0 - u - v) * self.normal_a + u * self.normal_b + v * self.normal_c;
+        let mut outward_normal = interpolated
+            .to_unit_vec()
+            .unwrap_or_else(|| geometric_normal.to_unit_vec_unchecked());
+        if outward_normal.dot(&geometric_normal) < 0.0 {
+            outward_normal = -outward_normal;
+        }
+        let normal = if ray_is_inside {
+            -outward_normal
+        } else {
+            outward_normal
+        };
+
+        Some(Collision {
+            pos: ray.at(t),
+            normal,
+            ray_is_inside,
+            t,
+            u,
+            v,
+            material: &self.material,
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(triangle_bounding_box(&self.a, &self.b, &self.c))
+    }
+}
+
+#[test]
+fn test_smooth_triangle_interpolates_normals() {
+    let tilted = vec3!(1.0, 0.0, 1.0).to_unit_vec_unchecked();
+    let triangle = SmoothTriangle {
+        a: vec3!(-1.0, -1.0, -1.0),
+        b: vec3!(1.0, -1.0, -1.0),
+        c: vec3!(0.0, 1.0, -1.0),
+        normal_a: vec3!(0.0, 0.0, 1.0),
+        normal_b: tilted,
+        normal_c: vec3!(0.0, 0.0, 1.0),
+        material: Material::Lambertian {
+            albedo: vec3!(0.5, 0.5, 0.5),
+        },
+    };
+    let flat = Triangle {
+        a: triangle.a,
+        b: triangle.b,
+        c: triangle.c,
+        material: triangle.material.clone(),
+    };
+    let collide_at = |x: f64, shape: &dyn Collidable| {
+        let ray = Ray {
+            origin: vec3!(x, -0.5, 0.0),
+            direction: vec3!(0.0, 0.0, -1.0),
+        };
+        let collision = shape.collide(&ray, 0.001, 10.0).unwrap();
+        (collision.normal, collision.ray_is_inside)
+    };
+
+    // the normal turns towards normal_b the closer the hit is to b
+    let mut previous_normal = collide_at(-0.5, &triangle).0;
+    for step in 1..=10 {
+        let x = -0.5 + step as f64 * 0.1;
+        let (normal, ray_is_inside) = collide_at(x, &triangle);
+        assert!(!ray_is_inside);
+        assert!((normal.norm() - 1.0).abs() < 1e-12);
+        assert!(normal.x > previous_normal.x, "{:?}", normal);
+        // small steps across the face change the normal only a little
+        assert!((normal - previous_normal).norm() < 0.1);
+        previous_normal = normal;
+
+        assert_eq!(collide_at(x, &flat).0, vec3!(0.0, 0.0, 1.0));
+    }
+
+    // seen from behind the normal is flipped towards the ray
+    let ray = Ray {
+        origin: vec3!(0.3, -0.5, -2.0),
+        direction: vec3!(0.0, 0.0, 1.0),
+    };
+    let front = collide_at(0.3, &triangle).0;
+    let back = triangle.collide(&ray, 0.001, 10.0).unwrap();
+    assert!(back.ray_is_inside);
+    assert_eq!(back.normal, -front);
+}
+
+fn obj_error(line_number: usize, message: String) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("line {}: {}", line_number, message),
+    )
+}
+
+// resolve an OBJ vertex reference such as "3", "-1" or "3/1/2" into a
+// 0-based index. OBJ indices are 1-based, negative indices are relative
+// to the vertices read so far (-1 being the most recent one)
+fn obj_vertex_index(
+    token: &str,
+    vertex_count: usize,
+    line_number: usize,
+) -> std::io::Result<usize> {
+    let index_token = token.split('/').next().unwrap_or(token);
+    obj_index(index_token, vertex_count, line_number, "vertex")
+}
+
+// resolve the normal reference of a vertex reference "3//2" or "3/1/2" like
+// obj_vertex_index, None if the vertex has no normal
+fn obj_normal_index(
+    token: &str,
+    normal_count: usize,
+    line_number: usize,
+) -> std::io::Result<Option<usize>> {
+    match token.split('/').nth(2) {
+        Some(index_token) if !index_token.is_empty() => {
+            obj_index(index_token, normal_count, line_number, "normal").map(Some)
+        }
+        _ => Ok(None),
+    }
+}
+
+fn obj_index(
+    index_token: &str,
+    count: usize,
+    line_number: usize,
+    kind: &str,
+) -> std::io::Result<usize> {
+    let index: i64 = index_token.parse().map_err(|_| {
+        obj_error(
+            line_number,
+            format!("invalid {} index '{}'", kind, index_token),
+        )
+    })?;
+    let resolved = if index < 0 {
+        count as i64 + index
+    } else {
+        index - 1
+    };
+    if resolved < 0 || resolved >= count as i64 {
+        return Err(obj_error(
+            line_number,
+            format!(
+                "{} index {} out of range ({} {}s defined)",
+                kind, index, count, kind
+            ),
+        ));
+    }
+    Ok(resolved as usize)
+}
+
+// the 3 coordinates following the statement keyword of a "v" or "vn" line
+fn obj_coordinates<'a>(
+    mut tokens: impl Iterator<Item = &'a str>,
+    line_number: usize,
+    kind: &str,
+) -> std::io::Result<Vec3> {
+    let mut coordinates = [0.0; 3];
+    for coordinate in coordinates.iter_mut() {
+        let token = tokens
+            .next()
+            .ok_or_else(|| obj_error(line_number, format!("{} needs 3 coordinates", kind)))?;
+        *coordinate = token.parse().map_err(|_| {
+            obj_error(
+                line_number,
+                format!("invalid {} coordinate '{}'", kind, token),
+            )
+        })?;
+    }
+    Ok(vec3!(coordinates[0], coordinates[1], coordinates[2]))
+}
+
+// parse the triangles of a Wavefront OBJ mesh; only "v", "vn" and "f" statements are
+// interpreted, polygons with more than three vertices are split up into a fan
+pub fn read_obj<R: BufRead>(reader: R, material: Material) -> std::io::Result<Vec<Triangle>> {
+    let triangles = parse_obj(reader, false)?
+        .into_iter()
+        .map(|[a, b, c]| Triangle {
+            a: a.0,
+            b: b.0,
+            c: c.0,
+            material: material.clone(),
+        })
+        .collect();
+    Ok(triangles)
+}
+
+pub fn load_obj(path: &str, material: Material) -> std::io::Result<Vec<Triangle>> {
+    read_obj(BufReader::new(File::open(path)?), material)
+}
+
+// like read_obj but the "vn" normals referenced by the faces ("f 1//1 2//2 3//3")
+// are interpolated, see SmoothTriangle. Vertices without a normal take the
+// geometric normal of their triangle
+pub fn read_smooth_obj<R: BufRead>(
+    reader: R,
+    material: Material,
+) -> std::io::Result<Vec<SmoothTriangle>> {
+    let triangles = parse_obj(reader, true)?
+        .into_iter()
+        .map(|[a, b, c]| {
+            // a degenerate triangle gets NaN normals but is never hit anyway
+            let geometric_normal = (b.0 - a.0).cross(&(c.0 - a.0)).to_unit_vec_unchecked();
+            SmoothTriangle {
+                a: a.0,
+                b: b.0,
+                c: c.0,
+                normal_a: a.1.unwrap_or(geometric_normal),
+                normal_b: b.1.unwrap_or(geometric_normal),
+                normal_c: c.1.unwrap_or(geometric_normal),
+                material: material.clone(),
+            }
+        })
+        .collect();
+    Ok(triangles)
+}
+
+pub fn load_smooth_obj(path: &str, material: Material) -> std::io::Result<Vec<SmoothTriangle>> {
+    read_smooth_obj(BufReader::new(File::open(path)?), material)
+}
+
+// position and, if given, normal of a face vertex
+type ObjCorner = (Vec3, Option<Vec3>);
+
+// the triangles of an OBJ mesh by their corners, the normal references of the faces
+// are only resolved (and checked) with_normals
+fn parse_obj<R: BufRead>(reader: R, with_normals: bool) -> std::io::Result<Vec<[ObjCorner; 3]>> {
+    let mut vertices: Vec<Vec3> = vec![];
+    let mut normals: Vec<Vec3> = vec![];
+    let mut triangles = vec![];
+
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line_number = i + 1;
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            Some("v") => vertices.push(obj_coordinates(tokens, line_number, "vertex")?),
+            Some("vn") => normals.push(obj_coordinates(tokens, line_number, "normal")?),
+            Some("f") => {
+                let corners = tokens
+                    .map(|token| {
+                        let vertex = obj_vertex_index(token, vertices.len(), line_number)?;
+                        let normal = if with_normals {
+                            obj_normal_index(token, normals.len(), line_number)?
+                        } else {
+                            None
+                        };
+                        Ok((vertices[vertex], normal.map(|normal| normals[normal])))
+                    })
+                    .collect::<std::io::Result<Vec<ObjCorner>>>()?;
+                if corners.len() < 3 {
+                    return Err(obj_error(
+                        line_number,
+                        "face needs at least 3 vertices".to_string(),
+                    ));
+                }
+                for k in 1..corners.len() - 1 {
+                    triangles.push([corners[0], corners[k], corners[k + 1]]);
+                }
+            }
+            // comments, texture coordinates, groups etc. are ignored
+            _ => {}
+        }
+    }
+    Ok(triangles)
+}
+
+#[cfg(test)]
+const TETRAHEDRON_OBJ: &str = "# tetrahedron
+v 0.0 0.0 0.0
+v 1.0 0.0 0.0
+v 0.0 1.0 0.0
+v 0.0 0.0 1.0
+vn 0.0 0.0 -1.0
+vt 0.0 0.0
+f 1 3 2
+f 1/1 2/1 4/1
+f -4//1 -1//1 -2//1
+f 2 3 4
+";
+
+#[test]
+fn test_read_obj_tetrahedron() {
+    let material = Material::Lambertian {
+        albedo: vec3!(0.5, 0.5, 0.5),
+    };
+    let triangles = read_obj(TETRAHEDRON_OBJ.as_bytes(), material.clone()).unwrap();
+
+    assert_eq!(triangles.len(), 4);
+    // negative indices are relative to the last vertex defined
+    let expected = Triangle {
+        a: vec3!(0.0, 0.0, 0.0),
+        b: vec3!(0.0, 0.0, 1.0),
+        c: vec3!(0.0, 1.0, 0.0),
+        material,
+    };
+    assert_eq!(triangles[2], expected);
+}
+
+#[test]
+fn test_read_smooth_obj_reads_vertex_normals() {
+    let material = Material::Lambertian {
+        albedo: vec3!(0.5, 0.5, 0.5),
+    };
+    let triangles = read_smooth_obj(TETRAHEDRON_OBJ.as_bytes(), material).unwrap();
+
+    assert_eq!(triangles.len(), 4);
+    // f -4//1 -1//1 -2//1
+    assert_eq!(triangles[2].c, vec3!(0.0, 1.0, 0.0));
+    assert_eq!(triangles[2].normal_a, vec3!(0.0, 0.0, -1.0));
+    assert_eq!(triangles[2].normal_c, vec3!(0.0, 0.0, -1.0));
+    // f 1 3 2 has no normals, the geometric one (0, 0, -1) is used
+    assert_eq!(triangles[0].normal_b, vec3!(0.0, 0.0, -1.0));
+    // which is normalized for larger triangles as well
+    let obj = "v 0 0 0\nv 4 0 0\nv 0 4 0\nf 1 2 3\n";
+    let triangles = read_smooth_obj(obj.as_bytes(), Material::NormalDebug).unwrap();
+    assert_eq!(triangles[0].normal_a, vec3!(0.0, 0.0, 1.0));
+
+    let obj = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvn 0 0 1\nf 1//1 2//1 3//2\n";
+    let error = read_smooth_obj(obj.as_bytes(), Material::NormalDebug).unwrap_err();
+    assert!(
+        error.to_string().starts_with("line 5: normal index 2"),
+        "{}",
+        error
+    );
+    // read_obj does not use the normals and hence does not resolve them
+    assert_eq!(
+        read_obj(obj.as_bytes(), Material::NormalDebug)
+            .unwrap()
+            .len(),
+        1
+    );
+}
+
+#[test]
+fn test_read_obj_triangulates_quad_as_fan() {
+    let material = Material::Lambertian {
+        albedo: vec3!(0.5, 0.5, 0.5),
+    };
+    let obj = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3 4\n";
+    let triangles = read_obj(obj.as_bytes(), material).unwrap();
+
+    assert_eq!(triangles.len(), 2);
+    assert_eq!(triangles[1].a, vec3!(0.0, 0.0, 0.0));
+    assert_eq!(triangles[1].b, vec3!(1.0, 1.0, 0.0));
+    assert_eq!(triangles[1].c, vec3!(0.0, 1.0, 0.0));
+}
+
+#[test]
+fn test_read_obj_out_of_range_index_reports_line() {
+    let material = Material::Lambertian {
+        albedo: vec3!(0.5, 0.5, 0.5),
+    };
+    let obj = "v 0 0 0\nv 1 0 0\nv 0 1 0\n\nf 1 2 4\n";
+    let error = read_obj(obj.as_bytes(), material).unwrap_err();
+
+    assert_eq!(error.kind(), ErrorKind::InvalidData);
+    assert!(error.to_string().starts_with("line 5:"), "{}", error);
+}
+
+// rectangle in the plane {p: p[k_axis] = k} bounded by [a0, a1] x [b0, b1]
+// on the two remaining axes. Its outward normal points along +k_axis
+#[allow(clippy::too_many_arguments)]
+fn collide_axis_aligned_rect<'a>(
+    ray: &Ray,
+    t_min: f64,
+    t_max: f64,
+    (a_axis, b_axis, k_axis): (usize, usize, usize),
+    (a0, a1): (f64, f64),
+    (b0, b1): (f64, f64),
+    k: f64,
+    material: &'a Material,
+) -> Option<Collision<'a>> {
+    // a ray parallel to the plane yields an infinite or NaN t which is rejected below
+    let t = (k - ray.origin[k_axis]) / ray.direction[k_axis];
+    if t.is_nan() || t < t_min || t > t_max {
+        return None;
+    }
+    let a = ray.origin[a_axis] + t * ray.direction[a_axis];
+    let b = ray.origin[b_axis] + t * ray.direction[b_axis];
+    if a < a0 || a > a1 || b < b0 || b > b1 {
+        return None;
+    }
+
+    let outward_normal = axis_unit_vec(k_axis, 1.0);
+    let ray_is_inside = ray.direction.dot(&outward_normal) >= 0.0;
+    let normal = if ray_is_inside {
+        -outward_normal
+    } else {
+        outward_normal
+    };
+
+    Some(Collision {
+        pos: ray.at(t),
+        normal,
+        ray_is_inside,
+        t,
+        u: (a - a0) / (a1 - a0),
+        v: (b - b0) / (b1 - b0),
+        material,
+    })
+}
+
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct XYRect {
+    pub x0: f64,
+    pub x1: f64,
+    pub y0: f64,
+    pub y1: f64,
+    pub k: f64,
+    pub material: Material,
+}
+
+impl Collidable for XYRect {
+    fn collide(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Collision<'_>> {
+        collide_axis_aligned_rect(
+            ray,
+            t_min,
+            t_max,
+            (0, 1, 2),
+            (self.x0, self.x1),
+            (self.y0, self.y1),
+            self.k,
+            &self.material,
+        )
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb {
+            min: vec3!(self.x0, self.y0, self.k),
+            max: vec3!(self.x1, self.y1, self.k),
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct XZRect {
+    pub x0: f64,
+    pub x1: f64,
+    pub z0: f64,
+    pub z1: f64,
+    pub k: f64,
+    pub material: Material,
+}
+
+impl XZRect {
+    pub fn area(&self) -> f64 {
+        (self.x1 - self.x0) * (self.z1 - self.z0)
+    }
+}
+
+impl Collidable for XZRect {
+    fn collide(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Collision<'_>> {
+        collide_axis_aligned_rect(
+            ray,
+            t_min,
+            t_max,
+            (0, 2, 1),
+            (self.x0, self.x1),
+            (self.z0, self.z1),
+            self.k,
+            &self.material,
+        )
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb {
+            min: vec3!(self.x0, self.k, self.z0),
+            max: vec3!(self.x1, self.k, self.z1),
+        })
+    }
+}
+
+// points are sampled uniformly on the rect
+impl Emitter for XZRect {
+    fn pdf_value(&self, origin: &Vec3, direction: &Vec3) -> f64 {
+        let ray = Ray {
+            origin: *origin,
+            direction: *direction,
+        };
+        match self.collide(&ray, f64::EPSILON, f64::INFINITY) {
+            Some(collision) => {
+                // convert the area density 1 / area into one per solid angle
+                let distance_squared = (collision.pos - *origin).norm_squared();
+                let cos_theta = direction.y.abs() / direction.norm();
+                distance_squared / (cos_theta * self.area())
+            }
+            None => 0.0,
+        }
+    }
+
+    fn random_point(&self, _origin: &Vec3) -> Vec3 {
+        vec3!(
+            rand_f64(self.x0, self.x1),
+            self.k,
+            rand_f64(self.z0, self.z1)
+        )
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct YZRect {
+    pub y0: f64,
+    pub y1: f64,
+    pub z0: f64,
+    pub z1: f64,
+    pub k: f64,
+    pub material: Material,
+}
+
+impl Collidable for YZRect {
+    fn collide(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Collision<'_>> {
+        collide_axis_aligned_rect(
+            ray,
+            t_min,
+            t_max,
+            (1, 2, 0),
+            (self.y0, self.y1),
+            (self.z0, self.z1),
+            self.k,
+            &self.material,
+        )
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb {
+            min: vec3!(self.k, self.y0, self.z0),
+            max: vec3!(self.k, self.y1, self.z1),
+        })
+    }
+}
+
+macro_rules! test_axis_aligned_rect {
+    ($($name: ident: $value:expr,)*) => {
+        $(
+            #[test]
+            fn $name(){
+                let (rect, direction, center, corner, expected_normal) = $value;
+                // start two units in front of the rect and travel towards it
+                let centered_ray = Ray {
+                    origin: center - 2.0 * direction,
+                    direction,
+                };
+                let actual = rect.collide(&centered_ray, 0.001, 10.0);
+                let expected = Some(Collision {
+                    pos: center,
+                    normal: expected_normal,
+                    ray_is_inside: false,
+                    t: 2.0,
+                    // the center of the rect
+                    u: 0.5,
+                    v: 0.5,
+                    material: &rect.material,
+                });
+                assert_eq!(actual, expected);
+
+                // aim slightly beyond the corner of the rect
+                let corner_ray = Ray {
+                    origin: corner - 2.0 * direction,
+                    direction,
+                };
+                assert_eq!(rect.collide(&corner_ray, 0.001, 10.0), None);
+            }
+        )*
+    }
+}
+
+test_axis_aligned_rect! {
+    test_ray_collides_xy_rect: (
+        XYRect {
+            x0: -1.0, x1: 1.0, y0: -1.0, y1: 1.0, k: -1.0,
+            material: Material::Lambertian { albedo: vec3!(0.5, 0.5, 0.5) },
+        },
+        vec3!(0.0, 0.0, -1.0),
+        vec3!(0.0, 0.0, -1.0),
+        vec3!(1.01, 1.01, -1.0),
+        vec3!(0.0, 0.0, 1.0),
+    ),
+    test_ray_collides_xz_rect: (
+        XZRect {
+            x0: -1.0, x1: 1.0, z0: -1.0, z1: 1.0, k: -1.0,
+            material: Material::Lambertian { albedo: vec3!(0.5, 0.5, 0.5) },
+        },
+        vec3!(0.0, -1.0, 0.0),
+        vec3!(0.0, -1.0, 0.0),
+        vec3!(-1.01, -1.0, 1.01),
+        vec3!(0.0, 1.0, 0.0),
+    ),
+    test_ray_collides_yz_rect: (
+        YZRect {
+            y0: -1.0, y1: 1.0, z0: -1.0, z1: 1.0, k: 1.0,
+            material: Material::Lambertian { albedo: vec3!(0.5, 0.5, 0.5) },
+        },
+        vec3!(-1.0, 0.0, 0.0),
+        vec3!(1.0, 0.0, 0.0),
+        vec3!(1.0, -1.01, -1.01),
+        vec3!(1.0, 0.0, 0.0),
+    ),
+}
+
+// parallelogram origin + a*u + b*v for a, b in [0, 1], its outward normal is u x v
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Quad {
+    pub origin: Vec3,
+    pub u: Vec3,
+    pub v: Vec3,
+    pub material: Material,
+}
+
+impl Quad {
+    pub fn area(&self) -> f64 {
+        self.u.cross(&self.v).norm()
+    }
+}
+
+impl Collidable for Quad {
+    fn collide(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Collision<'_>> {
+        let n = self.u.cross(&self.v);
+        let n_dot_n = n.dot(&n);
+        let denominator = n.dot(&ray.direction);
+        // degenerate quad or ray parallel to its plane
+        if n_dot_n < TRIANGLE_DETERMINANT_EPSILON
+            || denominator.abs() < TRIANGLE_DETERMINANT_EPSILON
+        {
+            return None;
+        }
+        let t = n.dot(&(self.origin - ray.origin)) / denominator;
+        if t < t_min || t > t_max {
+            return None;
+        }
+
+        // coordinates of the hit point in the (u, v) basis of the plane: with w = n / (n.n)
+        // the dual basis vectors are v x w and w x u
+        let pos = ray.at(t);
+        let hit_vec = pos - self.origin;
+        let w = n / n_dot_n;
+        let a = w.dot(&hit_vec.cross(&self.v));
+        let b = w.dot(&self.u.cross(&hit_vec));
+        if !(0.0..=1.0).contains(&a) || !(0.0..=1.0).contains(&b) {
+            return None;
+        }
+
+        let outward_normal = n.to_unit_vec_unchecked();
+        let ray_is_inside = ray.direction.dot(&outward_normal) >= 0.0;
+        let normal = if ray_is_inside {
+            -outward_normal
+        } else {
+            outward_normal
+        };
+
+        Some(Collision {
+            pos,
+            normal,
+            ray_is_inside,
+            t,
+            u: a,
+            v: b,
+            material: &self.material,
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let corner = |p: Vec3| Aabb { min: p, max: p };
+        Some(
+            corner(self.origin)
+                .surrounding(&corner(self.origin + self.u))
+                .surrounding(&corner(self.origin + self.v))
+                .surrounding(&corner(self.origin + self.u + self.v)),
+        )
+    }
+}
+
+// uniform over the parallelogram like XZRect
+impl Emitter for Quad {
+    fn pdf_value(&self, origin: &Vec3, direction: &Vec3) -> f64 {
+        let ray = Ray {
+            origin: *origin,
+            direction: *direction,
+        };
+        match self.collide(&ray, f64::EPSILON, f64::INFINITY) {
+            Some(collision) => {
+                let distance_squared = (collision.pos - *origin).norm_squared();
+                let cos_theta = collision.normal.dot(direction).abs() / direction.norm();
+                distance_squared / (cos_theta * self.area())
+            }
+            None => 0.0,
+        }
+    }
+
+    fn random_point(&self, _origin: &Vec3) -> Vec3 {
+        self.origin + rand_f64(0.0, 1.0) * self.u + rand_f64(0.0, 1.0) * self.v
+    }
+}
+
+#[cfg(test)]
+fn test_quad() -> Quad {
+    // tilted by 45 degrees around the x axis, centered at (0, 0, -2)
+    Quad {
+        origin: vec3!(-1.0, -1.0, -1.0),
+        u: vec3!(2.0, 0.0, 0.0),
+        v: vec3!(0.0, 2.0, -2.0),
+        material: Material::Lambertian {
+            albedo: vec3!(0.5, 0.5, 0.5),
+        },
+    }
+}
+
+#[test]
+fn test_ray_collides_quad_center() {
+    let quad = test_quad();
+    let ray = Ray {
+        origin: ZERO,
+        direction: vec3!(0.0, 0.0, -1.0),
+    };
+    let collision = quad.collide(&ray, 0.001, 10.0).unwrap();
+
+    assert_eq!(collision.pos, vec3!(0.0, 0.0, -2.0));
+    assert_eq!(collision.t, 2.0);
+    assert_eq!((collision.u, collision.v), (0.5, 0.5));
+    let sqrt_half = 0.5_f64.sqrt();
+    assert!((collision.normal - vec3!(0.0, sqrt_half, sqrt_half)).norm() < 1e-12);
+    assert!(!collision.ray_is_inside);
+
+    // from behind the normal is flipped towards the ray
+    let ray = Ray {
+        origin: vec3!(0.0, -2.0, -4.0),
+        direction: vec3!(0.0, 1.0, 1.0),
+    };
+    let collision = quad.collide(&ray, 0.001, 10.0).unwrap();
+    assert!((collision.pos - vec3!(0.0, 0.0, -2.0)).norm() < 1e-12);
+    assert!((collision.normal - vec3!(0.0, -sqrt_half, -sqrt_half)).norm() < 1e-12);
+    assert!(collision.ray_is_inside);
+}
+
+#[test]
+fn test_ray_just_outside_quad_edge_misses() {
+    let quad = test_quad();
+    // the edge a = 1 lies at x = 1
+    let just_outside = Ray {
+        origin: vec3!(1.0 + 1e-9, 0.0, 0.0),
+        direction: vec3!(0.0, 0.0, -1.0),
+    };
+    let just_inside = Ray {
+        origin: vec3!(1.0 - 1e-9, 0.0, 0.0),
+        direction: vec3!(0.0, 0.0, -1.0),
+    };
+
+    assert_eq!(quad.collide(&just_outside, 0.001, 10.0), None);
+    assert!(quad.collide(&just_inside, 0.001, 10.0).is_some());
+}
+
+#[test]
+fn test_quad_bounding_box() {
+    let bounding_box = test_quad().bounding_box().unwrap();
+    assert_eq!(bounding_box.min, vec3!(-1.0, -1.0, -3.0));
+    assert_eq!(bounding_box.max, vec3!(1.0, 1.0, -1.0));
+}
+
+// infinite plane through point with the given (unit) normal
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Plane {
+    pub point: Vec3,
+    pub normal: Vec3,
+    pub material: Material,
+}
+
+// t within [t_min, t_max] at which the ray crosses the plane through point with the given normal
+fn collide_plane(point: &Vec3, normal: &Vec3, ray: &Ray, t_min: f64, t_max: f64) -> Option<f64> {
+    let denominator = ray.direction.dot(normal);
+    if denominator.abs() < f64::EPSILON {
+        // the ray runs parallel to the plane
+        return None;
+    }
+    let t = (*point - ray.origin).dot(normal) / denominator;
+    if t < t_min || t > t_max {
+        return None;
+    }
+    Some(t)
+}
+
+impl Collidable for Plane {
+    fn collide(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Collision<'_>> {
+        let t = collide_plane(&self.point, &self.normal, ray, t_min, t_max)?;
+
+        let ray_is_inside = ray.direction.dot(&self.normal) >= 0.0;
+        let normal = if ray_is_inside {
+            -self.normal
+        } else {
+            self.normal
+        };
+
+        // planar coordinates relative to point, an image texture repeats every unit length
+        let pos = ray.at(t);
+        let (u, v) = planar_uv(&self.normal, &(pos - self.point));
+        Some(Collision {
+            pos,
+            normal,
+            ray_is_inside,
+            t,
+            u: u.rem_euclid(1.0),
+            v: v.rem_euclid(1.0),
+            material: &self.material,
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        None
+    }
+}
+
+#[test]
+fn test_ray_collides_plane() {
+    let material = Material::Lambertian {
+        albedo: vec3!(0.5, 0.5, 0.5),
+    };
+    let plane = Plane {
+        point: vec3!(0.0, -1.0, 0.0),
+        normal: vec3!(0.0, 1.0, 0.0),
+        material: material.clone(),
+    };
+    let ray = Ray {
+        origin: ZERO,
+        direction: vec3!(0.0, -1.0, 0.0),
+    };
+    let actual = plane.collide(&ray, 0.001, 10.0);
+
+    let expected = Some(Collision {
+        pos: vec3!(0.0, -1.0, 0.0),
+        normal: vec3!(0.0, 1.0, 0.0),
+        ray_is_inside: false,
+        t: 1.0,
+        u: 0.0,
+        v: 0.0,
+        material: &material,
+    });
+
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn test_plane_uv_repeats_every_unit_length() {
+    let plane = Plane {
+        point: vec3!(0.0, -1.0, 0.0),
+        normal: vec3!(0.0, 0.0, 1.0),
+        material: Material::Lambertian {
+            albedo: vec3!(0.5, 0.5, 0.5),
+        },
+    };
+    let uv_at = |x: f64, y: f64| {
+        let ray = Ray {
+            origin: vec3!(x, y, 1.0),
+            direction: vec3!(0.0, 0.0, -1.0),
+        };
+        let collision = plane.collide(&ray, 0.001, 10.0).unwrap();
+        (collision.u, collision.v)
+    };
+    // the tangent and bitangent of +z are +x and +y
+    assert_eq!(uv_at(0.25, -0.5), (0.25, 0.5));
+    assert_eq!(uv_at(3.25, 1.5), (0.25, 0.5));
+    assert_eq!(uv_at(-0.75, -1.5), (0.25, 0.5));
+}
+
+#[test]
+fn test_parallel_ray_misses_plane() {
+    let plane = Plane {
+        point: vec3!(0.0, -1.0, 0.0),
+        normal: vec3!(0.0, 1.0, 0.0),
+        material: Material::Lambertian {
+            albedo: vec3!(0.5, 0.5, 0.5),
+        },
+    };
+    let ray = Ray {
+        origin: ZERO,
+        direction: vec3!(1.0, 0.0, 0.0),
+    };
+
+    assert_eq!(plane.collide(&ray, 0.001, 10.0), None);
+}
+
+#[test]
+fn test_sphere_bounding_box() {
+    let sphere = Sphere {
+        center: vec3!(1.0, 2.0, 3.0),
+        radius: -0.5,
+        material: Material::Lambertian {
+            albedo: vec3!(0.5, 0.5, 0.5),
+        },
+    };
+    let expected = Some(Aabb {
+        min: vec3!(0.5, 1.5, 2.5),
+        max: vec3!(1.5, 2.5, 3.5),
+    });
+    assert_eq!(sphere.bounding_box(), expected);
+}
+
+#[test]
+fn test_aabb_surrounding() {
+    let a = Aabb {
+        min: vec3!(0.0, -1.0, 0.0),
+        max: vec3!(1.0, 1.0, 1.0),
+    };
+    let b = Aabb {
+        min: vec3!(-2.0, 0.0, 0.5),
+        max: vec3!(0.5, 3.0, 0.7),
+    };
+    let expected = Aabb {
+        min: vec3!(-2.0, -1.0, 0.0),
+        max: vec3!(1.0, 3.0, 1.0),
+    };
+    assert_eq!(a.surrounding(&b), expected);
+}
+
+// the axis of e.g. a Cylinder has to be a unit vector, scenes may write any non-zero direction
+#[cfg(feature = "serde")]
+fn deserialize_axis<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Vec3, D::Error> {
+    Vec3::deserialize(deserializer)?
+        .to_unit_vec()
+        .ok_or_else(|| serde::de::Error::custom("axis must not be zero"))
+}
+
+fn is_unit_axis(axis: &Vec3) -> bool {
+    (axis.norm_squared() - 1.0).abs() < 1e-9
+}
+
+// finite cylinder around the (unit) axis starting at base, closed by two disks
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Cylinder {
+    pub base: Vec3,
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "deserialize_axis"))]
+    pub axis: Vec3,
+    pub radius: f64,
+    pub height: f64,
+    pub material: Material,
+}
+
+// nearest t within [t_min, t_max] on the curved side of the cylinder around the (unit)
+// axis starting at base together with the outward normal
+fn collide_cylinder_side(
+    base: &Vec3,
+    axis: &Vec3,
+    radius: f64,
+    height: f64,
+    ray: &Ray,
+    t_min: f64,
+    t_max: f64,
+) -> Option<(f64, Vec3)> {
+    // only the components perpendicular to the axis matter for the distance to it
+    let delta = ray.origin - *base;
+    let direction_perp = ray.direction - ray.direction.dot(axis) * *axis;
+    let delta_perp = delta - delta.dot(axis) * *axis;
+
+    let a = direction_perp.norm_squared();
+    if a < f64::EPSILON {
+        // the ray runs parallel to the axis and can only hit the caps
+        return None;
+    }
+    let half_b = direction_perp.dot(&delta_perp);
+    let c = delta_perp.norm_squared() - radius * radius;
+    let discriminant = half_b * half_b - a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    let discriminant_root = discriminant.sqrt();
+    for root in [
+        (-half_b - discriminant_root) / a,
+        (-half_b + discriminant_root) / a,
+    ] {
+        if root < t_min || root > t_max {
+            continue;
+        }
+        let root_height = (ray.at(root) - *base).dot(axis);
+        if (0.0..=height).contains(&root_height) {
+            let outward_normal = (ray.at(root) - *base - root_height * *axis) / radius;
+            return Some((root, outward_normal));
+        }
+    }
+    None
+}
+
+impl Cylinder {
+    // nearest t within [t_min, t_max] on one of the two disks together with the outward normal
+    fn collide_caps(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<(f64, Vec3)> {
+        let denominator = ray.direction.dot(&self.axis);
+        if denominator.abs() < f64::EPSILON {
+            return None;
+        }
+        let caps = [
+            (self.base, -self.axis),
+            (self.base + self.height * self.axis, self.axis),
+        ];
+        let mut closest: Option<(f64, Vec3)> = None;
+        for (center, outward_normal) in caps {
+            let t = (center - ray.origin).dot(&self.axis) / denominator;
+            if t < t_min || t > t_max || closest.is_some_and(|(closest_t, _)| closest_t <= t) {
+                continue;
+            }
+            if (ray.at(t) - center).norm_squared() <= self.radius * self.radius {
+                closest = Some((t, outward_normal));
+            }
+        }
+        closest
+    }
+}
+
+impl Collidable for Cylinder {
+    fn collide(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Collision<'_>> {
+        debug_assert!(is_unit_axis(&self.axis), "cylinder axis must be unit");
+        let side = collide_cylinder_side(
+            &self.base,
+            &self.axis,
+            self.radius,
+            self.height,
+            ray,
+            t_min,
+            t_max,
+        );
+        let closest = side.map_or(t_max, |(t, _)| t);
+        let (t, outward_normal) = self.collide_caps(ray, t_min, closest).or(side)?;
+
+        let ray_is_inside = ray.direction.dot(&outward_normal) >= 0.0;
+        let normal = if ray_is_inside {
+            -outward_normal
+        } else {
+            outward_normal
+        };
+
+        // u is the angle around the axis, v the height from 0 at base (and the base cap)
+        // to 1 at the top cap
+        let pos = ray.at(t);
+        let to_pos = pos - self.base;
+        Some(Collision {
+            pos,
+            normal,
+            ray_is_inside,
+            t,
+            u: turn_around_axis(&self.axis, &to_pos),
+            v: (to_pos.dot(&self.axis) / self.height).clamp(0.0, 1.0),
+            material: &self.material,
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // a disk perpendicular to the axis extends radius * sin(angle between axis and e_i)
+        // along the i-th coordinate axis
+        let mut extent = vec3!();
+        for i in 0..3 {
+            extent[i] = self.radius * (1.0 - self.axis[i] * self.axis[i]).max(0.0).sqrt();
+        }
+        let top = self.base + self.height * self.axis;
+        let base_box = Aabb {
+            min: self.base - extent,
+            max: self.base + extent,
+        };
+        let top_box = Aabb {
+            min: top - extent,
+            max: top + extent,
+        };
+        Some(base_box.surrounding(&top_box))
+    }
+}
+
+#[cfg(test)]
+fn test_cylinder() -> Cylinder {
+    Cylinder {
+        base: vec3!(0.0, 0.0, -3.0),
+        axis: vec3!(0.0, 1.0, 0.0),
+        radius: 1.0,
+        height: 2.0,
+        material: Material::Lambertian {
+            albedo: vec3!(0.5, 0.5, 0.5),
+        },
+    }
+}
+
+#[test]
+fn test_ray_collides_cylinder_side() {
+    let cylinder = test_cylinder();
+    let ray = Ray {
+        origin: vec3!(0.0, 1.0, 0.0),
+        direction: vec3!(0.0, 0.0, -1.0),
+    };
+    let actual = cylinder.collide(&ray, 0.001, 10.0);
+
+    let expected = Some(Collision {
+        pos: vec3!(0.0, 1.0, -2.0),
+        normal: vec3!(0.0, 0.0, 1.0),
+        ray_is_inside: false,
+        t: 2.0,
+        u: 0.25,
+        v: 0.5,
+        material: &cylinder.material,
+    });
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn test_ray_collides_cylinder_cap() {
+    let cylinder = test_cylinder();
+    let ray = Ray {
+        origin: vec3!(0.5, 5.0, -3.0),
+        direction: vec3!(0.0, -1.0, 0.0),
+    };
+    let actual = cylinder.collide(&ray, 0.001, 10.0);
+
+    let expected = Some(Collision {
+        pos: vec3!(0.5, 2.0, -3.0),
+        normal: vec3!(0.0, 1.0, 0.0),
+        ray_is_inside: false,
+        t: 3.0,
+        u: 0.5,
+        v: 1.0,
+        material: &cylinder.material,
+    });
+    assert_eq!(actual, expected);
+
+    // from inside the cylinder the ray leaves through the bottom cap
+    let ray = Ray {
+        origin: vec3!(0.5, 1.0, -3.0),
+        direction: vec3!(0.0, -1.0, 0.0),
+    };
+    let collision = cylinder.collide(&ray, 0.001, 10.0).unwrap();
+    assert_eq!(collision.pos, vec3!(0.5, 0.0, -3.0));
+    assert_eq!(collision.normal, vec3!(0.0, 1.0, 0.0));
+    assert!(collision.ray_is_inside);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_cylinder_json_normalizes_axis() {
+    let json = r#"{"base":[0.0,0.0,-3.0],"axis":[0.0,4.0,0.0],"radius":1.0,"height":2.0,"material":{"Lambertian":{"albedo":[0.5,0.5,0.5]}}}"#;
+    assert_eq!(
+        serde_json::from_str::<Cylinder>(json).unwrap(),
+        test_cylinder()
+    );
+    let json = json.replace("[0.0,4.0,0.0]", "[0.0,0.0,0.0]");
+    assert!(serde_json::from_str::<Cylinder>(&json).is_err());
+}
+
+#[test]
+fn test_ray_misses_cylinder() {
+    let cylinder = test_cylinder();
+    // just outside the radius
+    let ray = Ray {
+        origin: vec3!(1.001, 1.0, 0.0),
+        direction: vec3!(0.0, 0.0, -1.0),
+    };
+    assert_eq!(cylinder.collide(&ray, 0.001, 10.0), None);
+
+    // above the top cap the infinite cylinder would have been hit
+    let ray = Ray {
+        origin: vec3!(0.0, 2.5, 0.0),
+        direction: vec3!(0.0, 0.0, -1.0),
+    };
+    assert_eq!(cylinder.collide(&ray, 0.001, 10.0), None);
+}
+
+#[test]
+fn test_cylinder_bounding_box() {
+    let expected = Some(Aabb {
+        min: vec3!(-1.0, 0.0, -4.0),
+        max: vec3!(1.0, 2.0, -2.0),
+    });
+    assert_eq!(test_cylinder().bounding_box(), expected);
+}
+
+// volume of constant density inside a closed boundary, e.g. smoke. A ray
+// passing through is scattered with a probability growing with the distance
+// travelled inside: after a distance d it has hit a particle with probability
+// 1 - exp(-density * d). The particles scatter with the phase_function, usually
+// Material::Isotropic
+pub struct ConstantMedium {
+    pub boundary: Box<dyn Collidable>,
+    pub density: f64,
+    pub phase_function: Material,
+}
+
+impl Collidable for ConstantMedium {
+    fn collide(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Collision<'_>> {
+        // a medium without particles is empty, a negative (or -0) density would
+        // scatter rays before they enter it
+        if self.density.is_nan() || self.density <= 0.0 {
+            return None;
+        }
+        // the span in which the ray is inside the boundary, even if it started inside
+        let (t_min_all, t_max_all) = (f64::NEG_INFINITY, f64::INFINITY);
+        let (entry_t, exit_t) = match self.boundary.collide_interval(ray, t_min_all, t_max_all) {
+            Some(interval) => interval,
+            None => {
+                let entry = self.boundary.collide(ray, t_min_all, t_max_all)?;
+                let exit = self.boundary.collide(ray, entry.t + 0.0001, t_max_all)?;
+                (entry.t, exit.t)
+            }
+        };
+        let t_enter = entry_t.max(t_min).max(0.0);
+        let t_exit = exit_t.min(t_max);
+        if t_enter >= t_exit {
+            return None;
+        }
+
+        let ray_length = ray.direction.norm();
+        let distance_inside_boundary = (t_exit - t_enter) * ray_length;
+        let hit_distance = -(1.0 / self.density) * rand_f64(0.0, 1.0).ln();
+        if hit_distance > distance_inside_boundary {
+            return None;
+        }
+
+        let t = t_enter + hit_distance / ray_length;
+        Some(Collision {
+            pos: ray.at(t),
+            // isotropic scattering does not depend on the normal
+            normal: vec3!(1.0, 0.0, 0.0),
+            ray_is_inside: true,
+            t,
+            u: 0.0,
+            v: 0.0,
+            material: &self.phase_function,
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.boundary.bounding_box()
+    }
+}
+
+#[test]
+fn test_denser_medium_scatters_more_rays() {
+    let medium = |density: f64| ConstantMedium {
+        boundary: Box::new(Sphere {
+            center: vec3!(0.0, 0.0, -3.0),
+            radius: 1.0,
+            material: Material::Dialectric {
+                refraction_index: 1.0,
+                attenuation: vec3!(1.0, 1.0, 1.0),
+                absorption: 0.0,
+            },
+        }),
+        density,
+        phase_function: Material::Isotropic {
+            albedo: vec3!(0.5, 0.5, 0.5),
+        },
+    };
+    // count the rays through the center which scatter inside the medium
+    let scatter_count = |medium: &ConstantMedium| {
+        crate::rng::seed_thread_rng(5);
+        let ray = Ray {
+            origin: ZERO,
+            direction: vec3!(0.0, 0.0, -1.0),
+        };
+        let mut count = 0;
+        for _ in 0..2000 {
+            if let Some(collision) = medium.collide(&ray, 0.001, f64::MAX) {
+                assert!((2.0..=4.0).contains(&collision.t), "{:?}", collision.t);
+                assert_eq!(
+                    *collision.material,
+                    Material::Isotropic {
+                        albedo: vec3!(0.5, 0.5, 0.5)
+                    }
+                );
+                count += 1;
+            }
+        }
+        count
+    };
+
+    // the diameter is 2, i.e. rays scatter with probability 1 - exp(-2 * density)
+    let sparse = scatter_count(&medium(0.1));
+    let dense = scatter_count(&medium(2.0));
+    assert!((250..470).contains(&sparse), "sparse = {:?}", sparse);
+    assert!(dense > 1900, "dense = {:?}", dense);
+    for density in [0.0, -0.0, -1.0, f64::NAN] {
+        assert_eq!(scatter_count(&medium(density)), 0, "{:?}", density);
+    }
+}
+
+// object moved by offset without rebuilding it
+pub struct Translate {
+    pub object: Box<dyn Collidable>,
+    pub offset: Vec3,
+}
+
+impl Collidable for Translate {
+    fn collide(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Collision<'_>> {
+        // moving the object by offset is the same as moving the ray by -offset
+        let moved_ray = Ray {
+            origin: ray.origin - self.offset,
+            direction: ray.direction,
+        };
+        let collision = self.object.collide(&moved_ray, t_min, t_max)?;
+        Some(Collision {
+            pos: collision.pos + self.offset,
+            ..collision
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let bounding_box = self.object.bounding_box()?;
+        Some(Aabb {
+            min: bounding_box.min + self.offset,
+            max: bounding_box.max + self.offset,
+        })
+    }
+}
+
+// object rotated counterclockwise by angle_deg degrees around the y axis
+pub struct RotateY {
+    pub object: Box<dyn Collidable>,
+    pub angle_deg: f64,
+}
+
+const Y_AXIS: Vec3 = vec3!(0.0, 1.0, 0.0);
+
+impl Collidable for RotateY {
+    fn collide(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Collision<'_>> {
+        // rotate the ray into object space and the collision back to world space
+        let theta = self.angle_deg.to_radians();
+        let rotated_ray = Ray {
+            origin: ray.origin.rotate(&Y_AXIS, -theta),
+            direction: ray.direction.rotate(&Y_AXIS, -theta),
+        };
+        let collision = self.object.collide(&rotated_ray, t_min, t_max)?;
+        Some(Collision {
+            pos: collision.pos.rotate(&Y_AXIS, theta),
+            normal: collision.normal.rotate(&Y_AXIS, theta),
+            ..collision
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // smallest box enclosing the rotated corners of the original box
+        let bounding_box = self.object.bounding_box()?;
+        let theta = self.angle_deg.to_radians();
+        let mut min = vec3!(f64::INFINITY, f64::INFINITY, f64::INFINITY);
+        let mut max = vec3!(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY);
+        for corner in 0..8 {
+            let mut point = bounding_box.min;
+            for axis in 0..3 {
+                if corner & (1 << axis) != 0 {
+                    point[axis] = bounding_box.max[axis];
+                }
+            }
+            let rotated = point.rotate(&Y_AXIS, theta);
+            for axis in 0..3 {
+                min[axis] = min[axis].min(rotated[axis]);
+                max[axis] = max[axis].max(rotated[axis]);
+            }
+        }
+        Some(Aabb { min, max })
+    }
+}
+
+#[test]
+fn test_ray_collides_translated_sphere() {
+    let material = Material::Lambertian {
+        albedo: vec3!(0.5, 0.5, 0.5),
+    };
+    let translated = Translate {
+        object: Box::new(Sphere {
+            center: ZERO,
+            radius: 1.0,
+            material: material.clone(),
+        }),
+        offset: vec3!(0.0, 0.0, -3.0),
+    };
+    let ray = Ray {
+        origin: ZERO,
+        direction: vec3!(0.0, 0.0, -1.0),
+    };
+    let actual = translated.collide(&ray, 0.001, 10.0);
+
+    let expected = Some(Collision {
+        pos: vec3!(0.0, 0.0, -2.0),
+        normal: vec3!(0.0, 0.0, 1.0),
+        ray_is_inside: false,
+        t: 2.0,
+        u: 0.25,
+        v: 0.5,
+        material: &material,
+    });
+    assert_eq!(actual, expected);
+    assert_eq!(
+        translated.bounding_box(),
+        Some(Aabb {
+            min: vec3!(-1.0, -1.0, -4.0),
+            max: vec3!(1.0, 1.0, -2.0),
+        })
+    );
+}
+
+#[test]
+fn test_ray_collides_rotated_box() {
+    let rotated = RotateY {
+        object: Box::new(Aabb {
+            min: vec3!(-1.0, -1.0, -1.0),
+            max: vec3!(1.0, 1.0, 1.0),
+        }),
+        angle_deg: 45.0,
+    };
+    let ray = Ray {
+        origin: vec3!(0.5, 0.0, 5.0),
+        direction: vec3!(0.0, 0.0, -1.0),
+    };
+    let collision = rotated.collide(&ray, 0.001, 10.0).unwrap();
+
+    // seen from above the rotated box is a square with corners (±√2, 0) and (0, ±√2),
+    // the ray hits its edge x + z = √2
+    let sqrt_2 = 2f64.sqrt();
+    let expected_pos = vec3!(0.5, 0.0, sqrt_2 - 0.5);
+    let expected_normal = vec3!(1.0 / sqrt_2, 0.0, 1.0 / sqrt_2);
+    assert!(
+        (collision.pos - expected_pos).norm() < 1e-9,
+        "{:?}",
+        collision
+    );
+    assert!(
+        (collision.normal - expected_normal).norm() < 1e-9,
+        "{:?}",
+        collision
+    );
+    assert!(
+        (collision.t - (5.5 - sqrt_2)).abs() < 1e-9,
+        "{:?}",
+        collision
+    );
+    assert!(!collision.ray_is_inside);
+
+    let bounding_box = rotated.bounding_box().unwrap();
+    assert!((bounding_box.max - vec3!(sqrt_2, 1.0, sqrt_2)).norm() < 1e-9);
+    assert!((bounding_box.min + vec3!(sqrt_2, 1.0, sqrt_2)).norm() < 1e-9);
+}
+
+// flat circle around center, perpendicular to the (unit) normal
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Disk {
+    pub center: Vec3,
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "deserialize_axis"))]
+    pub normal: Vec3,
+    pub radius: f64,
+    pub material: Material,
+}
+
+impl Collidable for Disk {
+    fn collide(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Collision<'_>> {
+        debug_assert!(is_unit_axis(&self.normal), "disk normal must be unit");
+        // the plane containing the disk, bounded by the rim
+        let t = collide_plane(&self.center, &self.normal, ray, t_min, t_max)?;
+        let pos = ray.at(t);
+        let offset = pos - self.center;
+        if offset.norm() > self.radius {
+            return None;
+        }
+        let ray_is_inside = ray.direction.dot(&self.normal) >= 0.0;
+        let normal = if ray_is_inside {
+            -self.normal
+        } else {
+            self.normal
+        };
+
+        // planar coordinates, the square [0, 1]² circumscribes the disk
+        let (u, v) = planar_uv(&self.normal, &offset);
+        Some(Collision {
+            pos,
+            normal,
+            ray_is_inside,
+            t,
+            u: 0.5 + u / (2.0 * self.radius),
+            v: 0.5 + v / (2.0 * self.radius),
+            material: &self.material,
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // see Cylinder::bounding_box
+        let mut extent = vec3!();
+        for i in 0..3 {
+            extent[i] = self.radius * (1.0 - self.normal[i] * self.normal[i]).max(0.0).sqrt();
+        }
+        Some(Aabb {
+            min: self.center - extent,
+            max: self.center + extent,
+        })
+    }
+}
+
+#[cfg(test)]
+fn test_disk() -> Disk {
+    Disk {
+        center: vec3!(0.0, 0.0, -2.0),
+        normal: vec3!(0.0, 0.0, 1.0),
+        radius: 1.0,
+        material: Material::Lambertian {
+            albedo: vec3!(0.5, 0.5, 0.5),
+        },
+    }
+}
+
+#[test]
+fn test_ray_collides_disk_center() {
+    let disk = test_disk();
+    let ray = Ray {
+        origin: ZERO,
+        direction: vec3!(0.0, 0.0, -1.0),
+    };
+    let actual = disk.collide(&ray, 0.001, 10.0);
+
+    let expected = Some(Collision {
+        pos: vec3!(0.0, 0.0, -2.0),
+        normal: vec3!(0.0, 0.0, 1.0),
+        ray_is_inside: false,
+        t: 2.0,
+        u: 0.5,
+        v: 0.5,
+        material: &disk.material,
+    });
+    assert_eq!(actual, expected);
+
+    // from behind the normal is flipped towards the ray
+    let ray = Ray {
+        origin: vec3!(0.0, 0.0, -4.0),
+        direction: vec3!(0.0, 0.0, 1.0),
+    };
+    let collision = disk.collide(&ray, 0.001, 10.0).unwrap();
+    assert_eq!(collision.normal, vec3!(0.0, 0.0, -1.0));
+    assert!(collision.ray_is_inside);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_disk_json_normalizes_normal() {
+    let mut disk = test_disk();
+    disk.normal = vec3!(0.0, 0.0, 3.0);
+    let json = serde_json::to_string(&disk).unwrap();
+    assert_eq!(serde_json::from_str::<Disk>(&json).unwrap(), test_disk());
+}
+
+#[test]
+fn test_ray_collides_disk_rim() {
+    let disk = test_disk();
+    let inside_rim = Ray {
+        origin: vec3!(0.0, 0.999, 0.0),
+        direction: vec3!(0.0, 0.0, -1.0),
+    };
+    let collision = disk.collide(&inside_rim, 0.001, 10.0).unwrap();
+    assert_eq!(collision.pos, vec3!(0.0, 0.999, -2.0));
+
+    let outside_rim = Ray {
+        origin: vec3!(0.0, 1.001, 0.0),
+        direction: vec3!(0.0, 0.0, -1.0),
+    };
+    assert_eq!(disk.collide(&outside_rim, 0.001, 10.0), None);
+}
+
+// open cone starting at the apex and widening along the (unit) axis up to height,
+// the base can be closed with a Disk
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Cone {
+    pub apex: Vec3,
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "deserialize_axis"))]
+    pub axis: Vec3,
+    pub half_angle_deg: f64,
+    pub height: f64,
+    pub material: Material,
+}
+
+impl Collidable for Cone {
+    fn collide(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Collision<'_>> {
+        debug_assert!(is_unit_axis(&self.axis), "cone axis must be unit");
+        // points p on the (double) cone satisfy ((p - apex)·axis)^2 = cos^2 * |p - apex|^2
+        let (sin, cos) = self.half_angle_deg.to_radians().sin_cos();
+        let cos_squared = cos * cos;
+        let delta = ray.origin - self.apex;
+        let direction_axial = ray.direction.dot(&self.axis);
+        let delta_axial = delta.dot(&self.axis);
+
+        let a = direction_axial * direction_axial - cos_squared * ray.direction.norm_squared();
+        let half_b = direction_axial * delta_axial - cos_squared * ray.direction.dot(&delta);
+        let c = delta_axial * delta_axial - cos_squared * delta.norm_squared();
+        let roots = if a.abs() < f64::EPSILON {
+            // the ray runs parallel to the surface and crosses the cone at most once
+            if half_b.abs() < f64::EPSILON {
+                return None;
+            }
+            [-c / (2.0 * half_b), f64::NAN]
+        } else {
+            let discriminant = half_b * half_b - a * c;
+            if discriminant < 0.0 {
+                return None;
+            }
+            let discriminant_root = discriminant.sqrt();
+            let (near, far) = (
+                (-half_b - discriminant_root) / a,
+                (-half_b + discriminant_root) / a,
+            );
+            [near.min(far), near.max(far)]
+        };
+
+        for root in roots {
+            if !(t_min..=t_max).contains(&root) {
+                continue;
+            }
+            // the mirrored cone behind the apex has a negative height
+            let to_point = ray.at(root) - self.apex;
+            let height = to_point.dot(&self.axis);
+            if !(0.0..=self.height).contains(&height) {
+                continue;
+            }
+            // tilt the radial direction against the axis by the half angle
+            let radial = (to_point - height * self.axis)
+                .to_unit_vec()
+                .unwrap_or(vec3!());
+            let outward_normal = (cos * radial - sin * self.axis)
+                .to_unit_vec()
+                .unwrap_or(-self.axis);
+
+            let ray_is_inside = ray.direction.dot(&outward_normal) >= 0.0;
+            let normal = if ray_is_inside {
+                -outward_normal
+            } else {
+                outward_normal
+            };
+            // u is the angle around the axis, v the height from 0 at the apex to 1 at the base
+            return Some(Collision {
+                pos: ray.at(root),
+                normal,
+                ray_is_inside,
+                t: root,
+                u: turn_around_axis(&self.axis, &to_point),
+                v: height / self.height,
+                material: &self.material,
+            });
+        }
+        None
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // the base circle extends like a cylinder cap, the apex is the other end
+        let base = self.apex + self.height * self.axis;
+        let radius = self.height * self.half_angle_deg.to_radians().tan();
+        let mut extent = vec3!();
+        for i in 0..3 {
+            extent[i] = radius * (1.0 - self.axis[i] * self.axis[i]).max(0.0).sqrt();
+        }
+        let base_box = Aabb {
+            min: base - extent,
+            max: base + extent,
+        };
+        let apex_box = Aabb {
+            min: self.apex,
+            max: self.apex,
+        };
+        Some(base_box.surrounding(&apex_box))
+    }
+}
+
+#[cfg(test)]
+fn test_cone() -> Cone {
+    // opens downwards from (0, 2, -3) to a base of radius 2 at y = 0
+    Cone {
+        apex: vec3!(0.0, 2.0, -3.0),
+        axis: vec3!(0.0, -1.0, 0.0),
+        half_angle_deg: 45.0,
+        height: 2.0,
+        material: Material::Lambertian {
+            albedo: vec3!(0.5, 0.5, 0.5),
+        },
+    }
+}
+
+#[test]
+fn test_ray_collides_cone_surface() {
+    let cone = test_cone();
+    let ray = Ray {
+        origin: vec3!(0.0, 1.0, 0.0),
+        direction: vec3!(0.0, 0.0, -1.0),
+    };
+    let collision = cone.collide(&ray, 0.001, 10.0).unwrap();
+
+    // the radius is 1 at one unit below the apex
+    assert!((collision.pos - vec3!(0.0, 1.0, -2.0)).norm() < 1e-9);
+    assert!((collision.t - 2.0).abs() < 1e-9);
+    let sqrt_half = 0.5f64.sqrt();
+    assert!(
+        (collision.normal - vec3!(0.0, sqrt_half, sqrt_half)).norm() < 1e-9,
+        "{:?}",
+        collision.normal
+    );
+    assert!(!collision.ray_is_inside);
+    // halfway down and a quarter turn from the tangent (1, 0, 0) of the axis
+    assert!((collision.u - 0.75).abs() < 1e-9, "u = {:?}", collision.u);
+    assert!((collision.v - 0.5).abs() < 1e-9, "v = {:?}", collision.v);
+
+    // from within the cone the normal is flipped towards the ray
+    let ray = Ray {
+        origin: vec3!(0.0, 1.0, -3.0),
+        direction: vec3!(0.0, 0.0, 1.0),
+    };
+    let collision = cone.collide(&ray, 0.001, 10.0).unwrap();
+    assert!((collision.pos - vec3!(0.0, 1.0, -2.0)).norm() < 1e-9);
+    assert!(collision.ray_is_inside);
+    assert!(
+        (collision.normal + vec3!(0.0, sqrt_half, sqrt_half)).norm() < 1e-9,
+        "{:?}",
+        collision.normal
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_cone_json_normalizes_axis() {
+    let mut cone = test_cone();
+    cone.axis = vec3!(0.0, -0.5, 0.0);
+    let json = serde_json::to_string(&cone).unwrap();
+    assert_eq!(serde_json::from_str::<Cone>(&json).unwrap(), test_cone());
+}
+
+#[test]
+fn test_ray_above_cone_apex_misses() {
+    let cone = test_cone();
+    // would hit the mirrored cone above the apex
+    let ray = Ray {
+        origin: vec3!(0.0, 3.0, 0.0),
+        direction: vec3!(0.0, 0.0, -1.0),
+    };
+    assert_eq!(cone.collide(&ray, 0.001, 10.0), None);
+
+    // below the base
+    let ray = Ray {
+        origin: vec3!(0.0, -0.5, 0.0),
+        direction: vec3!(0.0, 0.0, -1.0),
+    };
+    assert_eq!(cone.collide(&ray, 0.001, 10.0), None);
+}
+
+// sphere stretched by radii along the coordinate axes
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Ellipsoid {
+    pub center: Vec3,
+    pub radii: Vec3,
+    pub material: Material,
+}
+
+impl Collidable for Ellipsoid {
+    fn collide(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Collision<'_>> {
+        // scaling by 1 / radii maps the ellipsoid onto the unit sphere
+        // and keeps the ray parameter t of every point
+        let local_ray = Ray {
+            origin: (ray.origin - self.center) / self.radii,
+            direction: ray.direction / self.radii,
+        };
+        let (near, far) = sphere_roots(&vec3!(), 1.0, &local_ray)?;
+        let t = [near, far]
+            .into_iter()
+            .find(|t| (t_min..=t_max).contains(t))?;
+
+        // the point on the unit sphere is its own outward normal there
+        let local_normal = local_ray.at(t);
+        let (u, v) = sphere_uv(&local_normal);
+        let ray_is_inside = local_ray.direction.dot(&local_normal) >= 0.0;
+        let local_normal = if ray_is_inside {
+            -local_normal
+        } else {
+            local_normal
+        };
+        // normals transform with the inverse transpose of the scaling
+        let normal = (local_normal / self.radii)
+            .to_unit_vec()
+            .unwrap_or(local_normal);
+        Some(Collision {
+            pos: ray.at(t),
+            normal,
+            ray_is_inside,
+            t,
+            u,
+            v,
+            material: &self.material,
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let radii = vec3!(self.radii.x.abs(), self.radii.y.abs(), self.radii.z.abs());
+        Some(Aabb {
+            min: self.center - radii,
+            max: self.center + radii,
+        })
+    }
+}
+
+#[test]
+fn test_ellipsoid_with_equal_radii_matches_sphere() {
+    let material = Material::Lambertian {
+        albedo: vec3!(0.5, 0.5, 0.5),
+    };
+    let sphere = Sphere {
+        center: vec3!(0.5, -0.2, -3.0),
+        radius: 1.5,
+        material: material.clone(),
+    };
+    let ellipsoid = Ellipsoid {
+        center: sphere.center,
+        radii: vec3!(1.5, 1.5, 1.5),
+        material,
+    };
+    let rays = [
+        vec3!(0.0, 0.0, -1.0),
+        vec3!(0.3, 0.1, -1.0),
+        vec3!(-0.1, -0.4, -1.0),
+        vec3!(1.0, 0.0, 0.0),
+    ];
+    for direction in rays {
+        let ray = Ray {
+            origin: ZERO,
+            direction,
+        };
+        let expected = sphere.collide(&ray, 0.001, 100.0);
+        let actual = ellipsoid.collide(&ray, 0.001, 100.0);
+        match (expected, actual) {
+            (None, None) => {}
+            (Some(expected), Some(actual)) => {
+                assert!((expected.t - actual.t).abs() < 1e-9);
+                assert!((expected.pos - actual.pos).norm() < 1e-9);
+                assert!((expected.normal - actual.normal).norm() < 1e-9);
+                assert_eq!(expected.ray_is_inside, actual.ray_is_inside);
+                assert!((expected.u - actual.u).abs() < 1e-9);
+                assert!((expected.v - actual.v).abs() < 1e-9);
+                assert_eq!(expected.material, actual.material);
+            }
+            (expected, actual) => panic!("{:?} != {:?}", expected, actual),
+        }
+    }
+}
+
+#[test]
+fn test_ray_collides_stretched_ellipsoid() {
+    let ellipsoid = Ellipsoid {
+        center: vec3!(0.0, 0.0, -5.0),
+        radii: vec3!(2.0, 1.0, 1.0),
+        material: Material::Lambertian {
+            albedo: vec3!(0.5, 0.5, 0.5),
+        },
+    };
+    // x^2 / 4 + y^2 = 1 at (sqrt(2), sqrt(1/2))
+    let x = 2f64.sqrt();
+    let ray = Ray {
+        origin: vec3!(x, 5.0, -5.0),
+        direction: vec3!(0.0, -1.0, 0.0),
+    };
+    let collision = ellipsoid.collide(&ray, 0.001, 100.0).unwrap();
+
+    assert!((collision.pos - vec3!(x, 0.5f64.sqrt(), -5.0)).norm() < 1e-9);
+    assert!(!collision.ray_is_inside);
+    // the gradient (x / 4, y, 0) instead of the direction from the center
+    let expected_normal = vec3!(x / 4.0, 0.5f64.sqrt(), 0.0).to_unit_vec().unwrap();
+    assert!(
+        (collision.normal - expected_normal).norm() < 1e-9,
+        "{:?}",
+        collision.normal
+    );
+
+    let bounding_box = ellipsoid.bounding_box().unwrap();
+    assert_eq!(bounding_box.min, vec3!(-2.0, -1.0, -6.0));
+    assert_eq!(bounding_box.max, vec3!(2.0, 1.0, -4.0));
+}
+
+// all points within radius of the segment from a to b: a cylinder
+// closed by two hemispheres
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Capsule {
+    pub a: Vec3,
+    pub b: Vec3,
+    pub radius: f64,
+    pub material: Material,
+}
+
+impl Collidable for Capsule {
+    fn collide(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Collision<'_>> {
+        // for a == b any axis does, the two hemispheres then form a sphere
+        let axis = (self.b - self.a)
+            .to_unit_vec()
+            .unwrap_or(vec3!(0.0, 1.0, 0.0));
+        let length = (self.b - self.a).norm();
+        let mut closest =
+            collide_cylinder_side(&self.a, &axis, self.radius, length, ray, t_min, t_max);
+
+        // the hemisphere at a points away from b and vice versa
+        for (center, direction) in [(self.a, -axis), (self.b, axis)] {
+            let delta = ray.origin - center;
+            let a = ray.direction.norm_squared();
+            let half_b = delta.dot(&ray.direction);
+            let c = delta.norm_squared() - self.radius * self.radius;
+            let discriminant = half_b * half_b - a * c;
+            if discriminant < 0.0 {
+                continue;
+            }
+            let discriminant_root = discriminant.sqrt();
+            let t_max = closest.map_or(t_max, |(t, _)| t);
+            for root in [
+                (-half_b - discriminant_root) / a,
+                (-half_b + discriminant_root) / a,
+            ] {
+                if root < t_min || root > t_max {
+                    continue;
+                }
+                let radial = ray.at(root) - center;
+                if radial.dot(&direction) >= 0.0 {
+                    closest = Some((root, radial / self.radius));
+                    break;
+                }
+            }
+        }
+
+        let (t, outward_normal) = closest?;
+        let ray_is_inside = ray.direction.dot(&outward_normal) >= 0.0;
+        let normal = if ray_is_inside {
+            -outward_normal
+        } else {
+            outward_normal
+        };
+        // u is the angle around the axis, v the height from 0 at the pole below a
+        // to 1 at the pole above b
+        let pos = ray.at(t);
+        let to_pos = pos - self.a;
+        let v = (to_pos.dot(&axis) + self.radius) / (length + 2.0 * self.radius);
+        Some(Collision {
+            pos,
+            normal,
+            ray_is_inside,
+            t,
+            u: turn_around_axis(&axis, &to_pos),
+            v: v.clamp(0.0, 1.0),
+            material: &self.material,
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let extent = vec3!(self.radius, self.radius, self.radius);
+        let a_box = Aabb {
+            min: self.a - extent,
+            max: self.a + extent,
+        };
+        let b_box = Aabb {
+            min: self.b - extent,
+            max: self.b + extent,
+        };
+        Some(a_box.surrounding(&b_box))
+    }
+}
+
+#[cfg(test)]
+fn test_capsule() -> Capsule {
+    Capsule {
+        a: vec3!(0.0, 0.0, -3.0),
+        b: vec3!(0.0, 2.0, -3.0),
+        radius: 0.5,
+        material: Material::Lambertian {
+            albedo: vec3!(0.5, 0.5, 0.5),
+        },
+    }
+}
+
+#[test]
+fn test_ray_collides_capsule_body() {
+    let capsule = test_capsule();
+    let ray = Ray {
+        origin: vec3!(0.0, 1.0, 0.0),
+        direction: vec3!(0.0, 0.0, -1.0),
+    };
+    let actual = capsule.collide(&ray, 0.001, 10.0);
+
+    let expected = Some(Collision {
+        pos: vec3!(0.0, 1.0, -2.5),
+        normal: vec3!(0.0, 0.0, 1.0),
+        ray_is_inside: false,
+        t: 2.5,
+        u: 0.25,
+        v: 0.5,
+        material: &capsule.material,
+    });
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn test_ray_collides_capsule_cap() {
+    let capsule = test_capsule();
+    let ray = Ray {
+        origin: vec3!(0.3, 5.0, -3.0),
+        direction: vec3!(0.0, -1.0, 0.0),
+    };
+    let collision = capsule.collide(&ray, 0.001, 10.0).unwrap();
+
+    // the normal points away from the endpoint b
+    assert!((collision.pos - vec3!(0.3, 2.4, -3.0)).norm() < 1e-9);
+    assert!((collision.normal - vec3!(0.6, 0.8, 0.0)).norm() < 1e-9);
+    assert!((collision.t - 2.6).abs() < 1e-9);
+
+    // from below the lower hemisphere is hit
+    let ray = Ray {
+        origin: vec3!(0.0, -5.0, -3.0),
+        direction: vec3!(0.0, 1.0, 0.0),
+    };
+    let collision = capsule.collide(&ray, 0.001, 10.0).unwrap();
+    assert!((collision.pos - vec3!(0.0, -0.5, -3.0)).norm() < 1e-9);
+    assert_eq!(collision.normal, vec3!(0.0, -1.0, 0.0));
+}
+
+#[test]
+fn test_ray_alongside_capsule_misses() {
+    let capsule = test_capsule();
+    let parallel_to_axis = Ray {
+        origin: vec3!(0.501, 5.0, -3.0),
+        direction: vec3!(0.0, -1.0, 0.0),
+    };
+    assert_eq!(capsule.collide(&parallel_to_axis, 0.001, 10.0), None);
+
+    let across_body = Ray {
+        origin: vec3!(0.501, 1.0, 0.0),
+        direction: vec3!(0.0, 0.0, -1.0),
+    };
+    assert_eq!(capsule.collide(&across_body, 0.001, 10.0), None);
+}
+
+// relative to (major_radius + minor_radius)^4, a ray whose quartic only touches
+// zero up to this tolerance is taken to graze the torus
+const TORUS_TANGENT_EPSILON: f64 = 1e-9;
+const TORUS_BISECTION_STEPS: usize = 100;
+
+// real roots of x^3 + a*x^2 + b*x + c
+fn solve_monic_cubic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    let q = (a * a - 3.0 * b) / 9.0;
+    let r = (2.0 * a * a * a - 9.0 * a * b + 27.0 * c) / 54.0;
+    if r * r < q * q * q {
+        // three real roots
+        let theta = (r / (q * q * q).sqrt()).clamp(-1.0, 1.0).acos();
+        let scale = -2.0 * q.sqrt();
+        (0..3)
+            .map(|k| {
+                scale * ((theta + 2.0 * std::f64::consts::PI * k as f64) / 3.0).cos() - a / 3.0
+            })
+            .collect()
+    } else {
+        let big_a = -r.signum() * (r.abs() + (r * r - q * q * q).sqrt()).cbrt();
+        let big_b = if big_a == 0.0 { 0.0 } else { q / big_a };
+        vec![big_a + big_b - a / 3.0]
+    }
+}
+
+// ring of radius major_radius around the (unit) axis, swept by a circle of radius
+// minor_radius
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Torus {
+    pub center: Vec3,
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "deserialize_axis"))]
+    pub axis: Vec3,
+    pub major_radius: f64,
+    pub minor_radius: f64,
+    pub material: Material,
+}
+
+impl Torus {
+    // smallest root s in [s_min, s_max] of the torus quartic along the unit
+    // direction: (|p|^2 + R^2 - r^2)^2 - 4R^2 (|p|^2 - (p.axis)^2) for p = origin + s*direction
+    fn first_root(&self, origin: &Vec3, direction: &Vec3, s_min: f64, s_max: f64) -> Option<f64> {
+        let (major, minor) = (self.major_radius, self.minor_radius);
+        let od = origin.dot(direction);
+        let oo = origin.dot(origin);
+        let k = oo + major * major - minor * minor;
+        let ad = self.axis.dot(direction);
+        let ao = self.axis.dot(origin);
+        let four_r2 = 4.0 * major * major;
+
+        let c3 = 4.0 * od;
+        let c2 = 4.0 * od * od + 2.0 * k - four_r2 * (1.0 - ad * ad);
+        let c1 = 4.0 * od * k - 2.0 * four_r2 * (od - ao * ad);
+        let c0 = k * k - four_r2 * (oo - ao * ao);
+        let f = |s: f64| (((s + c3) * s + c2) * s + c1) * s + c0;
+
+        // between two consecutive extrema the quartic is monotone, hence each of
+        // these intervals holds at most one root which bisection finds reliably
+        let mut extrema: Vec<f64> = solve_monic_cubic(0.75 * c3, 0.5 * c2, 0.25 * c1)
+            .into_iter()
+            .filter(|s| *s > s_min && *s < s_max)
+            .collect();
+        extrema.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let tangent_epsilon = TORUS_TANGENT_EPSILON * (major + minor).powi(4);
+
+        let mut lo = s_min;
+        for hi in extrema.into_iter().chain(std::iter::once(s_max)) {
+            let (f_lo, f_hi) = (f(lo), f(hi));
+            if f_lo == 0.0 {
+                return Some(lo);
+            }
+            if f_lo.signum() != f_hi.signum() {
+                let (mut a, mut b) = (lo, hi);
+                for _ in 0..TORUS_BISECTION_STEPS {
+                    let mid = 0.5 * (a + b);
+                    if f(mid).signum() == f_lo.signum() {
+                        a = mid;
+                    } else {
+                        b = mid;
+                    }
+                }
+                return Some(0.5 * (a + b));
+            }
+            // an extremum barely touching zero is a double root of a grazing ray,
+            // rounding may have lifted it just above zero
+            if hi < s_max && f_hi.abs() < tangent_epsilon {
+                return Some(hi);
+            }
+            lo = hi;
+        }
+        None
+    }
+}
+
+impl Collidable for Torus {
+    fn collide(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Collision<'_>> {
+        debug_assert!(is_unit_axis(&self.axis), "torus axis must be unit");
+        // solve along the unit direction s = t * |direction| relative to the center
+        let direction_norm = ray.direction.norm();
+        let direction = ray.direction.to_unit_vec()?;
+        let origin = ray.origin - self.center;
+
+        // restrict the search to the bounding sphere and move the origin onto it,
+        // far away origins would otherwise blow up the coefficients of the quartic
+        let bounding_radius = self.major_radius + self.minor_radius;
+        let od = origin.dot(&direction);
+        let discriminant = od * od - (origin.norm_squared() - bounding_radius * bounding_radius);
+        if discriminant < 0.0 {
+            return None;
+        }
+        let s_min = (t_min * direction_norm).max(-od - discriminant.sqrt());
+        let s_max = (t_max * direction_norm).min(-od + discriminant.sqrt());
+        if s_min > s_max {
+            return None;
+        }
+        let shifted_origin = origin + s_min * direction;
+        let s = s_min + self.first_root(&shifted_origin, &direction, 0.0, s_max - s_min)?;
+
+        let t = s / direction_norm;
+        let pos = ray.at(t);
+        // the outward normal points away from the closest point on the ring
+        let p = origin + s * direction;
+        let radial = p - p.dot(&self.axis) * self.axis;
+        let ring_point = self.major_radius * radial.to_unit_vec().unwrap_or(vec3!());
+        let outward_normal = (p - ring_point).to_unit_vec().unwrap_or(self.axis);
+        let ray_is_inside = ray.direction.dot(&outward_normal) >= 0.0;
+        let normal = if ray_is_inside {
+            -outward_normal
+        } else {
+            outward_normal
+        };
+
+        Some(Collision {
+            pos,
+            normal,
+            ray_is_inside,
+            t,
+            u: 0.0,
+            v: 0.0,
+            material: &self.material,
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // the ring is a circle like the Disk rim, thickened by the minor radius
+        let mut extent = vec3!();
+        for i in 0..3 {
+            extent[i] = self.major_radius * (1.0 - self.axis[i] * self.axis[i]).max(0.0).sqrt()
+                + self.minor_radius;
+        }
+        Some(Aabb {
+            min: self.center - extent,
+            max: self.center + extent,
+        })
+    }
+}
+
+#[cfg(test)]
+fn test_torus() -> Torus {
+    Torus {
+        center: vec3!(0.0, 0.0, -3.0),
+        axis: vec3!(0.0, 1.0, 0.0),
+        major_radius: 1.0,
+        minor_radius: 0.25,
+        material: Material::Lambertian {
+            albedo: vec3!(0.5, 0.5, 0.5),
+        },
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_torus_json_normalizes_axis() {
+    let mut torus = test_torus();
+    torus.axis = vec3!(0.0, 3.0, 0.0);
+    let json = serde_json::to_string(&torus).unwrap();
+    assert_eq!(serde_json::from_str::<Torus>(&json).unwrap(), test_torus());
+}
+
+#[test]
+fn test_ray_through_torus_hole_misses() {
+    let torus = test_torus();
+    let ray = Ray {
+        origin: vec3!(0.0, 5.0, -3.0),
+        direction: vec3!(0.0, -1.0, 0.0),
+    };
+    assert_eq!(torus.collide(&ray, 0.001, 10.0), None);
+}
+
+#[test]
+fn test_ray_collides_torus_outer_ring() {
+    let torus = test_torus();
+    let ray = Ray {
+        origin: vec3!(5.0, 0.0, -3.0),
+        direction: vec3!(-2.0, 0.0, 0.0),
+    };
+    let collision = torus.collide(&ray, 0.001, 10.0).unwrap();
+
+    assert!((collision.pos - vec3!(1.25, 0.0, -3.0)).norm() < 1e-9);
+    assert!((collision.t - 1.875).abs() < 1e-9);
+    assert!((collision.normal - vec3!(1.0, 0.0, 0.0)).norm() < 1e-9);
+    assert!(!collision.ray_is_inside);
+}
+
+#[test]
+fn test_ray_collides_torus_inner_ring() {
+    let torus = test_torus();
+    // from the center of the hole the inner side of the tube faces the ray
+    let ray = Ray {
+        origin: vec3!(0.0, 0.0, -3.0),
+        direction: vec3!(1.0, 0.0, 0.0),
+    };
+    let collision = torus.collide(&ray, 0.001, 10.0).unwrap();
+
+    assert!((collision.pos - vec3!(0.75, 0.0, -3.0)).norm() < 1e-9);
+    assert!((collision.normal - vec3!(-1.0, 0.0, 0.0)).norm() < 1e-9);
+    assert!(!collision.ray_is_inside);
+
+    // leaving the tube from within
+    let ray = Ray {
+        origin: vec3!(1.0, 0.0, -3.0),
+        direction: vec3!(0.0, 1.0, 0.0),
+    };
+    let collision = torus.collide(&ray, 0.001, 10.0).unwrap();
+    assert!((collision.pos - vec3!(1.0, 0.25, -3.0)).norm() < 1e-9);
+    assert!((collision.normal - vec3!(0.0, -1.0, 0.0)).norm() < 1e-9);
+    assert!(collision.ray_is_inside);
+}
+
+#[test]
+fn test_ray_grazing_torus_top() {
+    let torus = test_torus();
+    let grazing = |y: f64| Ray {
+        origin: vec3!(1.0, y, 0.0),
+        direction: vec3!(0.0, 0.0, -1.0),
+    };
+    // the ray dips 1e-6 into the tube, it runs within it from z = -3.037 to z = -2.963
+    let collision = torus.collide(&grazing(0.25 - 1e-6), 0.001, 10.0).unwrap();
+    assert!((collision.pos.z + 2.963).abs() < 1e-3);
+    assert!(collision.normal.y > 0.99);
+
+    assert_eq!(torus.collide(&grazing(0.25 + 1e-6), 0.001, 10.0), None);
+    // exactly tangent rays touch the tube, the quartic is very flat around the
+    // point of contact which limits the accuracy
+    let collision = torus.collide(&grazing(0.25), 0.001, 10.0).unwrap();
+    assert!((collision.pos - vec3!(1.0, 0.25, -3.0)).norm() < 1e-3);
+}
+
+#[test]
+fn test_torus_bounding_box() {
+    let bounding_box = test_torus().bounding_box().unwrap();
+    assert_eq!(bounding_box.min, vec3!(-1.25, -0.25, -4.25));
+    assert_eq!(bounding_box.max, vec3!(1.25, 0.25, -1.75));
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsgOp {
+    Union,
+    Intersection,
+    // the left solid with the right one carved out
+    Difference,
+}
+
+impl CsgOp {
+    fn contains(&self, inside_left: bool, inside_right: bool) -> bool {
+        match self {
+            CsgOp::Union => inside_left || inside_right,
+            CsgOp::Intersection => inside_left && inside_right,
+            CsgOp::Difference => inside_left && !inside_right,
+        }
+    }
+}
+
+// boolean combination of two closed solids, e.g. a sphere with a bite taken out
+pub struct Csg {
+    pub left: Box<dyn Collidable>,
+    pub right: Box<dyn Collidable>,
+    pub op: CsgOp,
+}
+
+impl Collidable for Csg {
+    fn collide(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Collision<'_>> {
+        self.collide_all(ray, t_min, t_max).into_iter().next()
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let left = self.left.bounding_box();
+        let right = self.right.bounding_box();
+        match self.op {
+            CsgOp::Union => Some(left?.surrounding(&right?)),
+            CsgOp::Intersection => left.or(right),
+            CsgOp::Difference => left,
+        }
+    }
+
+    // walk along the crossings of both operands and keep those at which the
+    // combined solid is entered or left
+    fn collide_all(&self, ray: &Ray, t_min: f64, t_max: f64) -> Vec<Collision<'_>> {
+        let left = self.left.collide_all(ray, t_min, t_max);
+        let right = self.right.collide_all(ray, t_min, t_max);
+        // the ray starts inside an operand if its first crossing is an exit
+        let mut inside_left = left.first().is_some_and(|c| c.ray_is_inside);
+        let mut inside_right = right.first().is_some_and(|c| c.ray_is_inside);
+
+        let mut crossings: Vec<(bool, Collision)> = left
+            .into_iter()
+            .map(|collision| (true, collision))
+            .chain(right.into_iter().map(|collision| (false, collision)))
+            .collect();
+        crossings.sort_by(|(_, a), (_, b)| a.t.partial_cmp(&b.t).unwrap());
+
+        let mut collisions = vec![];
+        for (is_left, collision) in crossings {
+            let was_inside = self.op.contains(inside_left, inside_right);
+            if is_left {
+                inside_left = !collision.ray_is_inside;
+            } else {
+                inside_right = !collision.ray_is_inside;
+            }
+            if was_inside != self.op.contains(inside_left, inside_right) {
+                // the normal already faces the ray, only the side changes: a carved
+                // out surface is entered where the right operand is left
+                collisions.push(Collision {
+                    ray_is_inside: was_inside,
+                    ..collision
+                });
+            }
+        }
+        collisions
+    }
+}
+
+#[cfg(test)]
+fn test_bitten_sphere(op: CsgOp) -> Csg {
+    let material = Material::Lambertian {
+        albedo: vec3!(0.5, 0.5, 0.5),
+    };
+    Csg {
+        left: Box::new(Sphere {
+            center: vec3!(0.0, 0.0, -3.0),
+            radius: 1.0,
+            material: material.clone(),
+        }),
+        // overlaps the front of the left sphere
+        right: Box::new(Sphere {
+            center: vec3!(0.0, 0.0, -2.0),
+            radius: 0.5,
+            material,
+        }),
+        op,
+    }
+}
+
+#[test]
+fn test_ray_collides_carved_interior_of_sphere_difference() {
+    let csg = test_bitten_sphere(CsgOp::Difference);
+    let ray = Ray {
+        origin: ZERO,
+        direction: vec3!(0.0, 0.0, -1.0),
+    };
+    let actual = csg.collide(&ray, 0.001, 10.0);
+
+    // the front of the left sphere is carved away, the ray enters the solid
+    // through the back of the bite
+    let expected = Some(Collision {
+        pos: vec3!(0.0, 0.0, -2.5),
+        normal: vec3!(0.0, 0.0, 1.0),
+        ray_is_inside: false,
+        t: 2.5,
+        u: 0.75,
+        v: 0.5,
+        material: &Material::Lambertian {
+            albedo: vec3!(0.5, 0.5, 0.5),
+        },
+    });
+    assert_eq!(actual, expected);
+
+    let ts: Vec<f64> = csg
+        .collide_all(&ray, 0.001, 10.0)
+        .iter()
+        .map(|collision| collision.t)
+        .collect();
+    assert_eq!(ts, vec![2.5, 4.0]);
+
+    // away from the bite the left sphere is untouched
+    let ray = Ray {
+        origin: vec3!(0.8, 0.0, 0.0),
+        direction: vec3!(0.0, 0.0, -1.0),
+    };
+    let collision = csg.collide(&ray, 0.001, 10.0).unwrap();
+    assert!((collision.t - 2.4).abs() < 1e-9);
+    assert!(!collision.ray_is_inside);
+}
+
+#[test]
+fn test_ray_collides_sphere_union_and_intersection() {
+    let ray = Ray {
+        origin: ZERO,
+        direction: vec3!(0.0, 0.0, -1.0),
+    };
+    let ts = |op| -> Vec<f64> {
+        test_bitten_sphere(op)
+            .collide_all(&ray, 0.001, 10.0)
+            .iter()
+            .map(|collision| collision.t)
+            .collect()
+    };
+    assert_eq!(ts(CsgOp::Union), vec![1.5, 4.0]);
+    assert_eq!(ts(CsgOp::Intersection), vec![2.0, 2.5]);
+
+    // starting within both spheres, the intersection is left first
+    let csg = test_bitten_sphere(CsgOp::Intersection);
+    let ray = Ray {
+        origin: vec3!(0.0, 0.0, -2.2),
+        direction: vec3!(0.0, 0.0, -1.0),
+    };
+    let collision = csg.collide(&ray, 0.001, 10.0).unwrap();
+    assert!((collision.t - 0.3).abs() < 1e-9);
+    assert!(collision.ray_is_inside);
+}
+
+#[test]
+fn test_xz_rect_pdf_value() {
+    let rect = XZRect {
+        x0: -1.0,
+        x1: 1.0,
+        z0: -0.5,
+        z1: 0.5,
+        k: 2.0,
+        material: Material::DiffuseLight {
+            emit: vec3!(1.0, 1.0, 1.0),
+        },
+    };
+    // straight above: distance² / area
+    assert_eq!(rect.pdf_value(&ZERO, &vec3!(0.0, 1.0, 0.0)), 2.0);
+    // the length of the direction does not matter
+    let origin = vec3!(0.0, 0.0, 1.5);
+    let pdf = rect.pdf_value(&origin, &vec3!(0.0, 2.0, -2.0));
+    let expected = 8.0 / (0.5_f64.sqrt() * 2.0);
+    assert!((pdf - expected).abs() < 1e-12, "{:?}", pdf);
+    assert_eq!(rect.pdf_value(&ZERO, &vec3!(0.0, -1.0, 0.0)), 0.0);
+
+    for _ in 0..100 {
+        let point = rect.random_point(&ZERO);
+        assert_eq!(point.y, 2.0);
+        assert!(point.x.abs() <= 1.0 && point.z.abs() <= 0.5);
+    }
+    assert_eq!(rect.area(), 2.0);
+}
+
+#[test]
+fn test_quad_emitter_samples_the_parallelogram() {
+    let quad = Quad {
+        origin: vec3!(-1.0, 2.0, -0.5),
+        u: vec3!(2.0, 0.0, 0.0),
+        v: vec3!(0.0, 0.0, 1.0),
+        material: Material::DiffuseLight {
+            emit: vec3!(1.0, 1.0, 1.0),
+        },
+    };
+    assert_eq!(quad.area(), 2.0);
+    // the same rectangle as in test_xz_rect_pdf_value
+    assert_eq!(quad.pdf_value(&ZERO, &vec3!(0.0, 1.0, 0.0)), 2.0);
+    assert_eq!(quad.pdf_value(&ZERO, &vec3!(0.0, -1.0, 0.0)), 0.0);
+    for _ in 0..100 {
+        let point = quad.random_point(&ZERO);
+        assert_eq!(point.y, 2.0);
+        assert!(point.x.abs() <= 1.0 && point.z.abs() <= 0.5);
+    }
+}
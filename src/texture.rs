@@ -0,0 +1,348 @@
+use std::{fmt, sync::OnceLock};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::image::sample_bilinear;
+use crate::rng::Rng;
+use crate::vec::{Color, Vec3};
+use crate::vec3;
+
+const PERLIN_POINT_COUNT: usize = 256;
+// seed of the generator shared by all noise textures
+const PERLIN_SEED: u64 = 1337;
+const PERLIN_GRADIENT_STEP: f64 = 1e-4;
+
+// gradient noise: random unit gradients on an integer lattice
+// which are interpolated trilinearly for points in between
+#[derive(Debug, Clone, PartialEq)]
+pub struct Perlin {
+    gradients: Vec<Vec3>,
+    permutation_x: Vec<usize>,
+    permutation_y: Vec<usize>,
+    permutation_z: Vec<usize>,
+}
+
+fn random_permutation(rng: &mut Rng) -> Vec<usize> {
+    let mut permutation: Vec<usize> = (0..PERLIN_POINT_COUNT).collect();
+    // Fisher–Yates shuffle
+    for i in (1..PERLIN_POINT_COUNT).rev() {
+        let target = (rng.next_f64(0.0, (i + 1) as f64) as usize).min(i);
+        permutation.swap(i, target);
+    }
+    permutation
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Perlin {
+        let mut rng = Rng::new(seed);
+        let gradients = (0..PERLIN_POINT_COUNT).map(|_| rng.unit_vec()).collect();
+        Perlin {
+            gradients,
+            permutation_x: random_permutation(&mut rng),
+            permutation_y: random_permutation(&mut rng),
+            permutation_z: random_permutation(&mut rng),
+        }
+    }
+
+    // noise value in [-1, 1] at point p
+    pub fn noise(&self, p: &Vec3) -> f64 {
+        let (i, j, k) = (p.x.floor(), p.y.floor(), p.z.floor());
+        let (u, v, w) = (p.x - i, p.y - j, p.z - k);
+        let (i, j, k) = (i as i64, j as i64, k as i64);
+        let mask = PERLIN_POINT_COUNT as i64 - 1;
+
+        let mut accumulated = 0.0;
+        // hermite smoothing of the interpolation weights avoids grid artifacts
+        let (uu, vv, ww) = (
+            u * u * (3.0 - 2.0 * u),
+            v * v * (3.0 - 2.0 * v),
+            w * w * (3.0 - 2.0 * w),
+        );
+        for di in 0..2 {
+            for dj in 0..2 {
+                for dk in 0..2 {
+                    let gradient = self.gradients[self.permutation_x[((i + di) & mask) as usize]
+                        ^ self.permutation_y[((j + dj) & mask) as usize]
+                        ^ self.permutation_z[((k + dk) & mask) as usize]];
+                    let (fi, fj, fk) = (di as f64, dj as f64, dk as f64);
+                    let offset = vec3!(u - fi, v - fj, w - fk);
+                    accumulated += (fi * uu + (1.0 - fi) * (1.0 - uu))
+                        * (fj * vv + (1.0 - fj) * (1.0 - vv))
+                        * (fk * ww + (1.0 - fk) * (1.0 - ww))
+                        * gradient.dot(&offset);
+                }
+            }
+        }
+        accumulated
+    }
+
+    // sum of octaves of the absolute noise, each with double the frequency and
+    // half the weight of the previous one, hence below the sum of the weights
+    // 2 - 2^(1 - depth) < 2
+    pub fn turbulence(&self, p: &Vec3, depth: usize) -> f64 {
+        let mut accumulated = 0.0;
+        let mut p = *p;
+        let mut weight = 1.0;
+        for _octave in 0..depth {
+            accumulated += weight * self.noise(&p);
+            weight *= 0.5;
+            p = 2.0 * p;
+        }
+        accumulated.abs()
+    }
+
+    // gradient of the noise at point p, approximated by central differences
+    pub fn gradient(&self, p: &Vec3) -> Vec3 {
+        let h = PERLIN_GRADIENT_STEP;
+        let difference =
+            |offset: Vec3| (self.noise(&(*p + offset)) - self.noise(&(*p - offset))) / (2.0 * h);
+        vec3!(
+            difference(vec3!(h, 0.0, 0.0)),
+            difference(vec3!(0.0, h, 0.0)),
+            difference(vec3!(0.0, 0.0, h))
+        )
+    }
+}
+
+pub(crate) fn shared_perlin() -> &'static Perlin {
+    static PERLIN: OnceLock<Perlin> = OnceLock::new();
+    PERLIN.get_or_init(|| Perlin::new(PERLIN_SEED))
+}
+
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Texture {
+    SolidColor(Color),
+    // 3d checkerboard, the cells have an edge length of pi / scale
+    Checker { even: Color, odd: Color, scale: f64 },
+    // grayscale perlin turbulence, larger scales yield finer noise
+    Noise { scale: f64 },
+    // looked up bilinearly at the texture coordinates
+    Image(ImageTexture),
+}
+
+// linear colors stored row by row starting at the top row, e.g. the pixels of
+// Framebuffer::read_png. The fields are private so that the size always matches
+// the pixels, build it with ImageTexture::new or Texture::image
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(try_from = "UncheckedImageTexture")
+)]
+pub struct ImageTexture {
+    pixels: Vec<Color>,
+    width: usize,
+    height: usize,
+}
+
+// invalid size of an image texture, sampling it would index out of bounds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextureError {
+    EmptyImage,
+    // width * height differs from the number of pixels
+    PixelCountMismatch { expected: usize, actual: usize },
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TextureError::EmptyImage => write!(f, "image texture must not be empty"),
+            TextureError::PixelCountMismatch { expected, actual } => {
+                write!(f, "image texture needs {} pixels, got {}", expected, actual)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TextureError {}
+
+// the shape of ImageTexture in a scene file, converted by ImageTexture::try_from to
+// reject image textures of the wrong size already while loading
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct UncheckedImageTexture {
+    pixels: Vec<Color>,
+    width: usize,
+    height: usize,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<UncheckedImageTexture> for ImageTexture {
+    type Error = TextureError;
+
+    fn try_from(image: UncheckedImageTexture) -> Result<ImageTexture, TextureError> {
+        ImageTexture::new(image.pixels, image.width, image.height)
+    }
+}
+
+impl ImageTexture {
+    pub fn new(
+        pixels: Vec<Color>,
+        width: usize,
+        height: usize,
+    ) -> Result<ImageTexture, TextureError> {
+        if width == 0 || height == 0 {
+            return Err(TextureError::EmptyImage);
+        }
+        if width.checked_mul(height) != Some(pixels.len()) {
+            return Err(TextureError::PixelCountMismatch {
+                expected: width.saturating_mul(height),
+                actual: pixels.len(),
+            });
+        }
+        Ok(ImageTexture {
+            pixels,
+            width,
+            height,
+        })
+    }
+
+    // bilinearly interpolated color at the texture coordinates (u, v)
+    pub fn value(&self, u: f64, v: f64) -> Color {
+        sample_bilinear(&self.pixels, self.width, self.height, u, v)
+    }
+}
+
+impl Texture {
+    pub fn image(pixels: Vec<Color>, width: usize, height: usize) -> Result<Texture, TextureError> {
+        ImageTexture::new(pixels, width, height).map(Texture::Image)
+    }
+
+    // color of the texture at the texture coordinates (u, v) of point p,
+    // only image textures depend on (u, v)
+    pub fn value(&self, u: f64, v: f64, p: &Vec3) -> Color {
+        match self {
+            Texture::SolidColor(color) => *color,
+            Texture::Checker { even, odd, scale } => {
+                let sines = (scale * p.x).sin() * (scale * p.y).sin() * (scale * p.z).sin();
+                if sines < 0.0 {
+                    *odd
+                } else {
+                    *even
+                }
+            }
+            Texture::Noise { scale } => {
+                // an albedo above 1 would reflect more light than the surface receives,
+                // hence the turbulence is remapped to [0, 1] by the sum of its weights
+                let depth = 7;
+                let turbulence = shared_perlin().turbulence(&(*scale * *p), depth);
+                let weight_sum = 2.0 - 2f64.powi(1 - depth as i32);
+                (turbulence / weight_sum).clamp(0.0, 1.0) * vec3!(1.0, 1.0, 1.0)
+            }
+            Texture::Image(image) => image.value(u, v),
+        }
+    }
+}
+
+#[test]
+fn test_solid_color_texture() {
+    let texture = Texture::SolidColor(vec3!(0.1, 0.2, 0.3));
+    assert_eq!(
+        texture.value(0.0, 0.0, &vec3!(4.0, -2.0, 1.0)),
+        vec3!(0.1, 0.2, 0.3)
+    );
+}
+
+#[test]
+fn test_checker_texture_alternates_between_cells() {
+    let even = vec3!(1.0, 1.0, 1.0);
+    let odd = vec3!(0.2, 0.3, 0.1);
+    // cells of edge length 1
+    let texture = Texture::Checker {
+        even,
+        odd,
+        scale: std::f64::consts::PI,
+    };
+
+    assert_eq!(texture.value(0.0, 0.0, &vec3!(0.5, 0.5, 0.5)), even);
+    assert_eq!(texture.value(0.0, 0.0, &vec3!(1.5, 0.5, 0.5)), odd);
+    assert_eq!(texture.value(0.0, 0.0, &vec3!(1.5, 1.5, 0.5)), even);
+}
+
+#[test]
+fn test_perlin_noise_is_deterministic_and_bounded() {
+    let perlin_1 = Perlin::new(7);
+    let perlin_2 = Perlin::new(7);
+    let texture = Texture::Noise { scale: 1.0 };
+    let weight_sum = 2.0 - 2f64.powi(-6);
+    let mut distinct_values = 0;
+    for i in 0..20 {
+        for j in 0..20 {
+            for k in 0..5 {
+                let p = vec3!(0.37 * i as f64, 0.53 * j as f64 - 3.0, 0.71 * k as f64);
+                let noise = perlin_1.noise(&p);
+                assert_eq!(noise, perlin_2.noise(&p));
+                assert!((-1.0..=1.0).contains(&noise), "noise = {:?}", noise);
+                distinct_values += (noise.abs() > 1e-3) as usize;
+
+                let turbulence = perlin_1.turbulence(&p, 7);
+                assert!((0.0..=weight_sum).contains(&turbulence), "{:?}", turbulence);
+                let albedo = texture.value(0.0, 0.0, &p).x;
+                assert!((0.0..=1.0).contains(&albedo), "{:?}", albedo);
+            }
+        }
+    }
+    // the noise must not be flat
+    assert!(distinct_values > 1000);
+}
+
+#[test]
+fn test_noise_texture_is_grayscale() {
+    let texture = Texture::Noise { scale: 4.0 };
+    let color = texture.value(0.0, 0.0, &vec3!(0.3, 1.7, -2.2));
+    assert_eq!(color.x, color.y);
+    assert_eq!(color.y, color.z);
+    assert_eq!(color, texture.value(0.0, 0.0, &vec3!(0.3, 1.7, -2.2)));
+}
+
+#[test]
+fn test_image_texture_interpolates_bilinearly() {
+    let pixels = vec![
+        vec3!(1.0, 0.0, 0.0),
+        vec3!(0.0, 1.0, 0.0),
+        vec3!(0.0, 0.0, 1.0),
+        vec3!(1.0, 1.0, 1.0),
+    ];
+    let texture = Texture::image(pixels, 2, 2).unwrap();
+    let p = vec3!();
+
+    // (0, 0) is the top left corner
+    assert_eq!(texture.value(0.0, 0.0, &p), vec3!(1.0, 0.0, 0.0));
+    assert_eq!(texture.value(1.0, 0.0, &p), vec3!(0.0, 1.0, 0.0));
+    assert_eq!(texture.value(0.0, 1.0, &p), vec3!(0.0, 0.0, 1.0));
+    assert_eq!(texture.value(1.0, 1.0, &p), vec3!(1.0, 1.0, 1.0));
+    assert_eq!(texture.value(0.5, 0.5, &p), vec3!(0.5, 0.5, 0.5));
+}
+
+#[test]
+fn test_image_texture_checks_its_size() {
+    let pixels = vec![vec3!(0.5, 0.5, 0.5); 6];
+    assert!(Texture::image(pixels.clone(), 3, 2).is_ok());
+    assert_eq!(
+        Texture::image(pixels.clone(), 2, 2),
+        Err(TextureError::PixelCountMismatch {
+            expected: 4,
+            actual: 6
+        })
+    );
+    assert_eq!(Texture::image(vec![], 0, 0), Err(TextureError::EmptyImage));
+    assert_eq!(Texture::image(pixels, 6, 0), Err(TextureError::EmptyImage));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_image_texture_json_checks_its_size() {
+    let texture = Texture::image(vec![vec3!(0.5, 0.5, 0.5); 2], 2, 1).unwrap();
+    let json = serde_json::to_string(&texture).unwrap();
+    assert_eq!(serde_json::from_str::<Texture>(&json).unwrap(), texture);
+
+    let json = json.replace("\"height\":1", "\"height\":2");
+    let error = serde_json::from_str::<Texture>(&json).unwrap_err();
+    assert!(
+        error.to_string().contains("needs 4 pixels, got 2"),
+        "{}",
+        error
+    );
+}
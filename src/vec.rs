@@ -1,4 +1,14 @@
-use std::ops::{Add, Mul, Neg, Sub};
+use std::fmt;
+use std::ops::{
+    Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Neg, Sub, SubAssign,
+};
+
+#[cfg(feature = "serde")]
+use serde::{
+    de::{self, MapAccess, SeqAccess, Visitor},
+    ser::SerializeTuple,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Vec3 {
@@ -97,7 +107,202 @@ impl Mul<Vec3> for f64 {
     }
 }
 
+// division by zero follows f64, i.e. yields infinities (or NaN for 0/0)
+impl Div<f64> for Vec3 {
+    type Output = Self;
+
+    fn div(self, scalar: f64) -> Self::Output {
+        Self {
+            x: self.x / scalar,
+            y: self.y / scalar,
+            z: self.z / scalar,
+        }
+    }
+}
+
+impl Div<Vec3> for Vec3 {
+    type Output = Self;
+
+    fn div(self, v: Vec3) -> Self::Output {
+        Self {
+            x: self.x / v.x,
+            y: self.y / v.y,
+            z: self.z / v.z,
+        }
+    }
+}
+
+impl AddAssign for Vec3 {
+    fn add_assign(&mut self, rhs: Self) {
+        self.x += rhs.x;
+        self.y += rhs.y;
+        self.z += rhs.z;
+    }
+}
+
+impl SubAssign for Vec3 {
+    fn sub_assign(&mut self, rhs: Self) {
+        self.x -= rhs.x;
+        self.y -= rhs.y;
+        self.z -= rhs.z;
+    }
+}
+
+impl MulAssign<f64> for Vec3 {
+    fn mul_assign(&mut self, scalar: f64) {
+        self.x *= scalar;
+        self.y *= scalar;
+        self.z *= scalar;
+    }
+}
+
+impl DivAssign<f64> for Vec3 {
+    fn div_assign(&mut self, scalar: f64) {
+        self.x /= scalar;
+        self.y /= scalar;
+        self.z /= scalar;
+    }
+}
+
+// v[0], v[1], v[2] are the x, y and z component, e.g. for per-axis algorithms
+impl Index<usize> for Vec3 {
+    type Output = f64;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        match index {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("Vec3 index out of range: {} is not in 0..3", index),
+        }
+    }
+}
+
+impl IndexMut<usize> for Vec3 {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        match index {
+            0 => &mut self.x,
+            1 => &mut self.y,
+            2 => &mut self.z,
+            _ => panic!("Vec3 index out of range: {} is not in 0..3", index),
+        }
+    }
+}
+
+// conversions from and to plain arrays and tuples, e.g. [1.0, 2.0, 3.0].into()
+impl From<[f64; 3]> for Vec3 {
+    fn from([x, y, z]: [f64; 3]) -> Vec3 {
+        Vec3 { x, y, z }
+    }
+}
+
+impl From<(f64, f64, f64)> for Vec3 {
+    fn from((x, y, z): (f64, f64, f64)) -> Vec3 {
+        Vec3 { x, y, z }
+    }
+}
+
+impl From<Vec3> for [f64; 3] {
+    fn from(v: Vec3) -> [f64; 3] {
+        [v.x, v.y, v.z]
+    }
+}
+
+impl From<Vec3> for (f64, f64, f64) {
+    fn from(v: Vec3) -> (f64, f64, f64) {
+        (v.x, v.y, v.z)
+    }
+}
+
+// serialized as [x, y, z], the {"x": .., "y": .., "z": ..} form of older scene
+// files is still accepted
+#[cfg(feature = "serde")]
+impl Serialize for Vec3 {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut tuple = serializer.serialize_tuple(3)?;
+        tuple.serialize_element(&self.x)?;
+        tuple.serialize_element(&self.y)?;
+        tuple.serialize_element(&self.z)?;
+        tuple.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+struct Vec3Visitor;
+
+#[cfg(feature = "serde")]
+impl<'de> Visitor<'de> for Vec3Visitor {
+    type Value = Vec3;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an array [x, y, z] or a map with the keys x, y and z")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec3, A::Error> {
+        let mut xyz = [0.0; 3];
+        for (i, value) in xyz.iter_mut().enumerate() {
+            *value = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(i, &self))?;
+        }
+        if seq.next_element::<f64>()?.is_some() {
+            return Err(de::Error::invalid_length(4, &self));
+        }
+        Ok(Vec3::from(xyz))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Vec3, A::Error> {
+        let mut xyz = [None; 3];
+        while let Some(key) = map.next_key::<String>()? {
+            let i = match key.as_str() {
+                "x" => 0,
+                "y" => 1,
+                "z" => 2,
+                _ => return Err(de::Error::unknown_field(&key, &["x", "y", "z"])),
+            };
+            if xyz[i].is_some() {
+                return Err(de::Error::custom(format!("duplicate field `{}`", key)));
+            }
+            xyz[i] = Some(map.next_value()?);
+        }
+        match xyz {
+            [Some(x), Some(y), Some(z)] => Ok(vec3!(x, y, z)),
+            _ => Err(de::Error::custom("missing field, expected x, y and z")),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Vec3 {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Vec3, D::Error> {
+        deserializer.deserialize_any(Vec3Visitor)
+    }
+}
+
+// compact "(x, y, z)" for logs, a precision like {:.3} applies to every component
+impl fmt::Display for Vec3 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match f.precision() {
+            Some(precision) => write!(
+                f,
+                "({:.*}, {:.*}, {:.*})",
+                precision, self.x, precision, self.y, precision, self.z
+            ),
+            None => write!(f, "({}, {}, {})", self.x, self.y, self.z),
+        }
+    }
+}
+
 impl Vec3 {
+    // component along the i-th axis, same as v[i]
+    pub fn axis(&self, i: usize) -> f64 {
+        self[i]
+    }
+
+    pub fn components(&self) -> [f64; 3] {
+        [self.x, self.y, self.z]
+    }
+
     pub fn norm_squared(&self) -> f64 {
         self.x * self.x + self.y * self.y + self.z * self.z
     }
@@ -106,13 +311,19 @@ impl Vec3 {
         (self.x * self.x + self.y * self.y + self.z * self.z).sqrt()
     }
 
-    pub fn to_unit_vec(&self) -> Vec3 {
-        let norm: f64 = self.norm();
-        assert_ne!(
-            norm, 0.0,
-            "Zero vector cannot be converted to a unique unit vector"
-        );
-        return vec3!(self.x, self.y, self.z) * (1.0 / norm);
+    // None for zero vectors which have no unique direction, and for vectors whose
+    // norm is subnormal, infinite or NaN, which cannot be divided by it accurately
+    pub fn to_unit_vec(&self) -> Option<Vec3> {
+        let norm = self.norm();
+        if !norm.is_normal() {
+            return None;
+        }
+        Some(*self / norm)
+    }
+
+    // for vectors known to be non-zero, e.g. ray directions
+    pub fn to_unit_vec_unchecked(&self) -> Vec3 {
+        *self / self.norm()
     }
 
     pub fn dot(&self, other: &Vec3) -> f64 {
@@ -121,24 +332,102 @@ impl Vec3 {
 
     pub fn cross(&self, other: &Vec3) -> Vec3 {
         Vec3 {
-            x: &self.y * &other.z - &self.z * &other.y,
-            y: &self.z * &other.x - &self.x * &other.z,
-            z: &self.x * &other.y - &self.y * &other.x,
+            x: self.y * other.z - self.z * other.y,
+            y: self.z * other.x - self.x * other.z,
+            z: self.x * other.y - self.y * other.x,
+        }
+    }
+
+    // mirror self at the plane with the given (unit) normal
+    pub fn reflect(&self, normal: &Vec3) -> Vec3 {
+        *self - 2.0 * self.dot(normal) * *normal
+    }
+
+    // Snell's law for the unit vector self hitting a surface with the (unit) normal
+    // facing it, ratio being the refraction index of the medium left divided by the
+    // one entered. Beyond the critical angle the tangential part is kept as is
+    pub fn refract(&self, normal: &Vec3, ratio: f64) -> Vec3 {
+        let cos_theta_1 = (-self.dot(normal)).min(1.0);
+        let cos_theta_2 = (1.0 - ratio * ratio * (1.0 - cos_theta_1 * cos_theta_1))
+            .max(0.0)
+            .sqrt();
+        ratio * *self + (ratio * cos_theta_1 - cos_theta_2) * *normal
+    }
+
+    // angle in radians in [0, pi], 0 if either vector is zero
+    pub fn angle_between(&self, other: &Vec3) -> f64 {
+        let norms = self.norm() * other.norm();
+        if norms == 0.0 {
+            return 0.0;
         }
+        (self.dot(other) / norms).clamp(-1.0, 1.0).acos()
+    }
+
+    // component of self along onto, ZERO if onto is zero
+    pub fn project_onto(&self, onto: &Vec3) -> Vec3 {
+        let norm_squared = onto.norm_squared();
+        if norm_squared == 0.0 {
+            return ZERO;
+        }
+        (self.dot(onto) / norm_squared) * *onto
+    }
+
+    // orthonormal basis (tangent, bitangent, normal) around the (unit) vector self,
+    // branchless construction of Duff et al. 2017 "Building an Orthonormal Basis,
+    // Revisited" which stays stable for normals close to ±z
+    pub fn build_onb(&self) -> (Vec3, Vec3, Vec3) {
+        let sign = 1.0_f64.copysign(self.z);
+        let a = -1.0 / (sign + self.z);
+        let b = self.x * self.y * a;
+        let tangent = vec3!(1.0 + sign * self.x * self.x * a, sign * b, -sign * self.x);
+        let bitangent = vec3!(b, sign + self.y * self.y * a, -self.y);
+        (tangent, bitangent, *self)
     }
 
     pub fn rotate(&self, unit_vec: &Vec3, theta_rad: f64) -> Vec3 {
-        assert_eq!(unit_vec.norm(), 1.0);
-        let v_rot = theta_rad.cos() * *self
+        // normalized vectors may be off by a few ULP
+        assert!(
+            (unit_vec.norm() - 1.0).abs() < 1e-9,
+            "rotation axis {:?} is not a unit vector",
+            unit_vec
+        );
+        theta_rad.cos() * *self
             + (1.0 - theta_rad.cos()) * (unit_vec.dot(self) * *unit_vec)
-            + theta_rad.sin() * (unit_vec.cross(self));
-        return v_rot;
+            + theta_rad.sin() * (unit_vec.cross(self))
+    }
+
+    // linear interpolation, self for t = 0 and other for t = 1
+    pub fn lerp(&self, other: &Vec3, t: f64) -> Vec3 {
+        (1.0 - t) * *self + t * *other
+    }
+
+    // every component clamped to [lo, hi]
+    pub fn clamp(&self, lo: f64, hi: f64) -> Vec3 {
+        vec3!(
+            self.x.clamp(lo, hi),
+            self.y.clamp(lo, hi),
+            self.z.clamp(lo, hi)
+        )
+    }
+
+    pub fn min_components(&self, other: &Vec3) -> Vec3 {
+        vec3!(
+            self.x.min(other.x),
+            self.y.min(other.y),
+            self.z.min(other.z)
+        )
+    }
+
+    pub fn max_components(&self, other: &Vec3) -> Vec3 {
+        vec3!(
+            self.x.max(other.x),
+            self.y.max(other.y),
+            self.z.max(other.z)
+        )
     }
 
     pub fn almost_zero(&self) -> bool {
-        return self.x.abs() < f64::EPSILON
-            && self.y.abs() < f64::EPSILON
-            && self.z.abs() < f64::EPSILON;
+        self.x.abs() < f64::EPSILON && self.y.abs() < f64::EPSILON && self.z.abs() < f64::EPSILON
     }
 }
 
@@ -210,6 +499,101 @@ fn test_vec3_vec3_mul() {
     assert_eq!(actual, expected);
 }
 
+#[test]
+fn test_vec3_scalar_div() {
+    let v: Vec3 = vec3!(1.0, -0.5, 2.0);
+    let actual = v / 4.0;
+    let expected: Vec3 = vec3!(0.25, -0.125, 0.5);
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn test_vec3_vec3_div() {
+    let v: Vec3 = vec3!(1.0, -0.5, 2.0);
+    let w: Vec3 = vec3!(2.0, -0.5, -8.0);
+    let actual = v / w;
+    let expected: Vec3 = vec3!(0.5, 1.0, -0.25);
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn test_vec3_div_by_zero() {
+    let v: Vec3 = vec3!(1.0, -0.5, 0.0);
+    let actual = v / 0.0;
+    assert_eq!(actual.x, f64::INFINITY);
+    assert_eq!(actual.y, f64::NEG_INFINITY);
+    assert!(actual.z.is_nan());
+
+    let actual = v / vec3!(0.0, 0.0, 1.0);
+    assert_eq!(actual, vec3!(f64::INFINITY, f64::NEG_INFINITY, 0.0));
+}
+
+#[test]
+fn test_vec3_add_assign() {
+    let mut v: Vec3 = vec3!(1.0, 0.5, 2.0);
+    v += vec3!(0.0, 1.5, 1.0);
+    let expected: Vec3 = vec3!(1.0, 2.0, 3.0);
+    assert_eq!(v, expected);
+}
+
+#[test]
+fn test_vec3_sub_assign() {
+    let mut v: Vec3 = vec3!(1.0, -0.5, 2.0);
+    v -= vec3!(2.0, 1.5, 1.0);
+    let expected: Vec3 = vec3!(-1.0, -2.0, 1.0);
+    assert_eq!(v, expected);
+}
+
+#[test]
+fn test_vec3_mul_assign() {
+    let mut v: Vec3 = vec3!(1.0, -0.5, 2.0);
+    v *= 8.0;
+    let expected: Vec3 = vec3!(8.0, -4.0, 16.0);
+    assert_eq!(v, expected);
+}
+
+#[test]
+fn test_vec3_div_assign() {
+    let mut v: Vec3 = vec3!(1.0, -0.5, 2.0);
+    v /= 4.0;
+    let expected: Vec3 = vec3!(0.25, -0.125, 0.5);
+    assert_eq!(v, expected);
+}
+
+#[test]
+fn test_vec3_index() {
+    let v: Vec3 = vec3!(1.0, -0.5, 2.0);
+    assert_eq!(v[0], 1.0);
+    assert_eq!(v[1], -0.5);
+    assert_eq!(v[2], 2.0);
+    assert_eq!(v.axis(1), -0.5);
+    assert_eq!(v.components(), [1.0, -0.5, 2.0]);
+}
+
+#[test]
+fn test_vec3_index_mut() {
+    let mut v: Vec3 = vec3!();
+    v[0] = 1.0;
+    v[1] = -0.5;
+    v[2] += 2.0;
+    let expected: Vec3 = vec3!(1.0, -0.5, 2.0);
+    assert_eq!(v, expected);
+}
+
+#[test]
+#[should_panic(expected = "Vec3 index out of range: 3 is not in 0..3")]
+fn test_vec3_index_out_of_range() {
+    let v: Vec3 = vec3!(1.0, -0.5, 2.0);
+    let _ = v[3];
+}
+
+#[test]
+#[should_panic(expected = "Vec3 index out of range: 7 is not in 0..3")]
+fn test_vec3_index_mut_out_of_range() {
+    let mut v: Vec3 = vec3!();
+    v[7] = 1.0;
+}
+
 #[test]
 fn test_norm_squared() {
     let v = vec3!(5.0f64.sqrt(), 2.0, 4.0);
@@ -244,6 +628,26 @@ fn test_cross() {
     assert_eq!(actual, expected);
 }
 
+#[test]
+fn test_reflect() {
+    let v = vec3!(1.0, -1.0, 0.0);
+    assert_eq!(v.reflect(&vec3!(0.0, 1.0, 0.0)), vec3!(1.0, 1.0, 0.0));
+}
+
+#[test]
+fn test_refract_follows_snells_law() {
+    // from vacuum into window glass at 30 degrees, cf. test_dialectric_refraction_angle
+    let theta_1 = 30f64.to_radians();
+    let v = vec3!(theta_1.sin(), -theta_1.cos(), 0.0);
+    let normal = vec3!(0.0, 1.0, 0.0);
+    let refracted = v.refract(&normal, 1.0 / 1.52);
+
+    assert!((refracted.norm() - 1.0).abs() < 1e-12);
+    let theta_2 = refracted.dot(&(-normal)).acos().to_degrees();
+    assert!((theta_2 - 19.2049).abs() < 1e-3, "theta_2 = {:?}", theta_2);
+    assert!((theta_1.sin() - 1.52 * theta_2.to_radians().sin()).abs() < 1e-12);
+}
+
 #[test]
 fn test_rotate_60_degrees() {
     let v = vec3!(0.5, 0.5, -1.0);
@@ -281,7 +685,154 @@ fn test_unit_vec() {
     let v = vec3!(1.0, 1.0, 0.0);
     let actual = v.to_unit_vec();
     let expected = vec3!(1f64 / 2f64.sqrt(), 1f64 / 2f64.sqrt(), 0.0);
-    assert_eq!(actual, expected);
+    assert_eq!(actual, Some(expected));
+}
+
+#[test]
+fn test_zero_vec_has_no_unit_vec() {
+    assert_eq!(ZERO.to_unit_vec(), None);
+    assert_eq!(vec3!(f64::NAN, 0.0, 0.0).to_unit_vec(), None);
+    assert_eq!(vec3!(f64::INFINITY, 0.0, 0.0).to_unit_vec(), None);
+    // short vectors still have a direction
+    assert_eq!(
+        vec3!(1e-17, 0.0, 0.0).to_unit_vec(),
+        Some(vec3!(1.0, 0.0, 0.0))
+    );
+}
+
+#[test]
+fn test_rotate_around_normalized_axis() {
+    let rotation_axis = vec3!(3.0, 4.0, 0.0).to_unit_vec().unwrap();
+    let v = vec3!(0.0, 0.0, 1.0);
+    let actual = v.rotate(&rotation_axis, 90f64.to_radians());
+    // the rotated vector stays perpendicular to the axis
+    let expected = vec3!(0.8, -0.6, 0.0);
+
+    let distance = (actual - expected).norm();
+    assert!(distance < 1e-9, "{:?} != {:?}", actual, expected);
+}
+
+#[test]
+#[should_panic(expected = "is not a unit vector")]
+fn test_rotate_around_non_unit_axis_panics() {
+    let v = vec3!(0.0, 0.0, 1.0);
+    v.rotate(&vec3!(0.0, 2.0, 0.0), 1.0);
+}
+
+#[test]
+fn test_lerp() {
+    let a = vec3!(1.0, 1.0, 1.0);
+    let b = vec3!(0.5, 0.7, 1.0);
+    assert_eq!(a.lerp(&b, 0.0), a);
+    assert_eq!(a.lerp(&b, 1.0), b);
+    assert_eq!(a.lerp(&b, 0.5), vec3!(0.75, 0.85, 1.0));
+}
+
+#[test]
+fn test_clamp() {
+    let v = vec3!(-0.5, 0.3, 1.5);
+    assert_eq!(v.clamp(0.0, 1.0), vec3!(0.0, 0.3, 1.0));
+}
+
+#[test]
+fn test_min_and_max_components() {
+    let a = vec3!(1.0, -2.0, 3.0);
+    let b = vec3!(0.0, 5.0, 3.0);
+    assert_eq!(a.min_components(&b), vec3!(0.0, -2.0, 3.0));
+    assert_eq!(a.max_components(&b), vec3!(1.0, 5.0, 3.0));
+}
+
+#[test]
+fn test_angle_between() {
+    let x = vec3!(1.0, 0.0, 0.0);
+    assert_eq!(
+        x.angle_between(&vec3!(0.0, 3.0, 0.0)),
+        std::f64::consts::FRAC_PI_2
+    );
+    assert_eq!(x.angle_between(&x), 0.0);
+    assert_eq!(
+        x.angle_between(&vec3!(-2.0, 0.0, 0.0)),
+        std::f64::consts::PI
+    );
+    // parallel vectors may round the cosine beyond 1, which must not yield NaN
+    let v = vec3!(0.1, 0.2, 0.3);
+    assert!(v.angle_between(&(3.0 * v)) < 1e-7);
+    assert_eq!(x.angle_between(&ZERO), 0.0);
+}
+
+#[test]
+fn test_project_onto() {
+    let v = vec3!(2.0, 2.0, 0.0);
+    assert_eq!(v.project_onto(&vec3!(1.0, 0.0, 0.0)), vec3!(2.0, 0.0, 0.0));
+    assert_eq!(v.project_onto(&vec3!(0.0, -4.0, 0.0)), vec3!(0.0, 2.0, 0.0));
+    assert_eq!(v.project_onto(&ZERO), ZERO);
+}
+
+#[test]
+fn test_build_onb_is_orthonormal() {
+    let normals = [
+        vec3!(0.0, 0.0, 1.0),
+        vec3!(0.0, 0.0, -1.0),
+        vec3!(1e-9, -1e-9, 1.0).to_unit_vec_unchecked(),
+        vec3!(1e-9, 1e-9, -1.0).to_unit_vec_unchecked(),
+        vec3!(1.0, 0.0, 0.0),
+        vec3!(0.0, -1.0, 0.0),
+        vec3!(1.0, 2.0, 3.0).to_unit_vec_unchecked(),
+        vec3!(-0.3, 0.1, -0.9).to_unit_vec_unchecked(),
+    ];
+    for normal in normals {
+        let (tangent, bitangent, n) = normal.build_onb();
+        assert_eq!(n, normal);
+        for v in [tangent, bitangent] {
+            assert!((v.norm() - 1.0).abs() < 1e-12, "{:?} for {:?}", v, normal);
+            assert!(v.dot(&normal).abs() < 1e-12, "{:?} for {:?}", v, normal);
+        }
+        assert!(tangent.dot(&bitangent).abs() < 1e-12, "{:?}", normal);
+        // right handed: tangent x bitangent = normal
+        assert!(
+            (tangent.cross(&bitangent) - normal).norm() < 1e-12,
+            "{:?}",
+            normal
+        );
+    }
+}
+
+#[test]
+fn test_vec3_from_and_into_array_and_tuple() {
+    let v: Vec3 = [1.0, 2.0, 3.0].into();
+    assert_eq!(v, vec3!(1.0, 2.0, 3.0));
+    let array: [f64; 3] = v.into();
+    assert_eq!(array, [1.0, 2.0, 3.0]);
+
+    let v = Vec3::from((-1.0, 0.5, 4.0));
+    assert_eq!(v, vec3!(-1.0, 0.5, 4.0));
+    let tuple: (f64, f64, f64) = v.into();
+    assert_eq!(tuple, (-1.0, 0.5, 4.0));
+}
+
+#[test]
+fn test_display_vec3() {
+    let v = vec3!(0.1, 0.2, 0.5);
+    assert_eq!(format!("{}", v), "(0.1, 0.2, 0.5)");
+    assert_eq!(format!("{:.3}", v), "(0.100, 0.200, 0.500)");
+    assert_eq!(
+        format!("{:.1}", vec3!(1.0 / 3.0, -2.0, 0.25)),
+        "(0.3, -2.0, 0.2)"
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_vec3_serializes_as_array() {
+    let v = vec3!(0.1, -2.0, 3.5);
+    assert_eq!(serde_json::to_string(&v).unwrap(), "[0.1,-2.0,3.5]");
+    assert_eq!(serde_json::from_str::<Vec3>("[0.1,-2.0,3.5]").unwrap(), v);
+    let from_map = serde_json::from_str::<Vec3>(r#"{"x": 0.1, "y": -2.0, "z": 3.5}"#);
+    assert_eq!(from_map.unwrap(), v);
+
+    assert!(serde_json::from_str::<Vec3>("[0.1, -2.0]").is_err());
+    assert!(serde_json::from_str::<Vec3>("[0.1, -2.0, 3.5, 1.0]").is_err());
+    assert!(serde_json::from_str::<Vec3>(r#"{"x": 0.1, "y": -2.0}"#).is_err());
 }
 
 pub type Color = Vec3;